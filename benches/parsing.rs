@@ -1,6 +1,7 @@
 //! Benchmarks for the BBCode parser using Criterion.
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId, Throughput};
+use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId, Throughput};
+use std::hint::black_box;
 use bbcode::{parse, Parser, Renderer};
 
 // ============================================================================
@@ -127,6 +127,12 @@ pub struct AttachmentDatabase {
     attachment_data: HashMap<u64, AttachmentData>,
 }
 
+impl Default for AttachmentDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AttachmentDatabase {
     /// Creates a new database with sample data from the XenForo schema.
     pub fn new() -> Self {
@@ -378,20 +384,14 @@ impl AttachDisplayOptions {
                 for (key, value) in map {
                     let key_lower = key.to_ascii_lowercase();
                     match key_lower.as_str() {
-                        "type" => {
-                            if value.eq_ignore_ascii_case("full") {
-                                opts.full = true;
-                            }
+                        "type" if value.eq_ignore_ascii_case("full") => {
+                            opts.full = true;
                         }
-                        "width" => {
-                            if Self::is_valid_dimension(value) {
-                                opts.width = Some(value.to_string());
-                            }
+                        "width" if Self::is_valid_dimension(value) => {
+                            opts.width = Some(value.to_string());
                         }
-                        "height" => {
-                            if Self::is_valid_dimension(value) {
-                                opts.height = Some(value.to_string());
-                            }
+                        "height" if Self::is_valid_dimension(value) => {
+                            opts.height = Some(value.to_string());
                         }
                         "alt" | "title" => {
                             opts.alt = Some(value.to_string());
@@ -425,10 +425,8 @@ impl AttachDisplayOptions {
         }
 
         // Check for percentage
-        if s.ends_with('%') {
-            return s[..s.len() - 1]
-                .chars()
-                .all(|c| c.is_ascii_digit() || c == '.');
+        if let Some(percent) = s.strip_suffix('%') {
+            return percent.chars().all(|c| c.is_ascii_digit() || c == '.');
         }
 
         // Check for pixels
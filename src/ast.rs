@@ -3,15 +3,24 @@
 //! This module defines the tree structure that represents parsed BBCode.
 //! All string data uses `&str` references for zero-copy parsing.
 
-use std::borrow::Cow;
-use std::collections::HashMap;
-use std::fmt;
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+use alloc::collections::BTreeMap;
+
+use crate::tags::TagRegistry;
 
 /// The type of BBCode tag, determining its parsing and rendering behavior.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TagType {
     /// Standard inline formatting tags like [b], [i], [u], [s].
     /// These can nest freely and auto-close at block boundaries.
+    #[default]
     Inline,
 
     /// Block-level tags like [quote], [code], [list].
@@ -30,10 +39,20 @@ pub enum TagType {
     Void,
 }
 
-impl Default for TagType {
-    fn default() -> Self {
-        Self::Inline
-    }
+/// What an auto-detected link in [`Node::AutoUrl`] was written as, which
+/// decides how it is linked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AutoLinkKind {
+    /// A URL with its scheme, like `https://example.com`.
+    #[default]
+    Url,
+
+    /// A bare `www.` host, linked over `https://`.
+    Www,
+
+    /// An email address, linked with `mailto:`.
+    Email,
 }
 
 /// Represents the value of a tag's option/attribute.
@@ -42,22 +61,18 @@ impl Default for TagType {
 /// - Simple: `[tag=value]` → `Scalar("value")`
 /// - Quoted: `[tag="value with spaces"]` → `Scalar("value with spaces")`
 /// - Keyed: `[tag attr1=val1 attr2="val 2"]` → `Map({...})`
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TagOption<'a> {
     /// No option provided.
+    #[default]
     None,
 
     /// A single scalar value like `[url=https://example.com]`.
     Scalar(Cow<'a, str>),
 
     /// Key-value pairs like `[img width="100" height="200"]`.
-    Map(HashMap<Cow<'a, str>, Cow<'a, str>>),
-}
-
-impl<'a> Default for TagOption<'a> {
-    fn default() -> Self {
-        Self::None
-    }
+    Map(BTreeMap<Cow<'a, str>, Cow<'a, str>>),
 }
 
 impl<'a> TagOption<'a> {
@@ -90,7 +105,7 @@ impl<'a> TagOption<'a> {
 
     /// Returns the map if present.
     #[inline]
-    pub fn as_map(&self) -> Option<&HashMap<Cow<'a, str>, Cow<'a, str>>> {
+    pub fn as_map(&self) -> Option<&BTreeMap<Cow<'a, str>, Cow<'a, str>>> {
         match self {
             Self::Map(m) => Some(m),
             _ => None,
@@ -126,7 +141,11 @@ impl<'a> TagOption<'a> {
 ///
 /// This is the core structure representing parsed BBCode. Each node can be
 /// either a text fragment or a tag with children.
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// `Clone`, `PartialEq` and [`Node::into_owned`] walk the tree with an
+/// explicit stack, so they handle any nesting depth.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Node<'a> {
     /// Plain text content.
     Text(Cow<'a, str>),
@@ -134,8 +153,9 @@ pub enum Node<'a> {
     /// A line break (preserved from input).
     LineBreak,
 
-    /// An auto-detected URL that wasn't wrapped in [url] tags.
-    AutoUrl(Cow<'a, str>),
+    /// An auto-detected URL that wasn't wrapped in [url] tags, as written,
+    /// with the kind of link the parser found.
+    AutoUrl(Cow<'a, str>, AutoLinkKind),
 
     /// A BBCode tag with its name, option, and children.
     Tag(TagNode<'a>),
@@ -205,30 +225,185 @@ impl<'a> Node<'a> {
         }
     }
 
+    /// Appends the BBCode source of this node to `out`.
+    ///
+    /// Broken tags are written from their stored `raw_open`/`raw_close`;
+    /// other tags are rebuilt from their name, option and children, so edits
+    /// to a node's option are reflected. Line breaks become `\n` and
+    /// auto-detected URLs are written bare.
+    pub fn write_bbcode(&self, out: &mut String) {
+        enum Piece<'n, 'a> {
+            Node(&'n Node<'a>),
+            Close(&'n TagNode<'a>),
+        }
+
+        // Walk with an explicit stack so deep nesting can't overflow
+        let mut stack = vec![Piece::Node(self)];
+        while let Some(piece) = stack.pop() {
+            let tag = match piece {
+                Piece::Node(Node::Text(text)) => {
+                    out.push_str(text);
+                    continue;
+                }
+                Piece::Node(Node::LineBreak) => {
+                    out.push('\n');
+                    continue;
+                }
+                Piece::Node(Node::AutoUrl(url, _)) => {
+                    out.push_str(url);
+                    continue;
+                }
+                Piece::Node(Node::Tag(tag)) => tag,
+                Piece::Close(tag) => {
+                    tag.write_bbcode_close(out);
+                    continue;
+                }
+            };
+
+            tag.write_bbcode_open(out);
+            stack.push(Piece::Close(tag));
+            stack.extend(tag.children.iter().rev().map(Piece::Node));
+        }
+    }
+
+    /// Returns the visible text of this node and everything below it.
+    ///
+    /// Text and auto-detected URLs are concatenated in order, line breaks
+    /// become `\n` and tag markup is ignored. Unlike
+    /// [`TagNode::inner_text`] this is meant for any subtree, e.g. for
+    /// word counts or search indexing.
+    pub fn text_content(&self) -> String {
+        let mut out = String::new();
+        push_text_content(core::slice::from_ref(self), &mut out, None);
+        out
+    }
+
     /// Converts the node to an owned version.
     pub fn into_owned(self) -> Node<'static> {
         match self {
             Node::Text(t) => Node::Text(Cow::Owned(t.into_owned())),
             Node::LineBreak => Node::LineBreak,
-            Node::AutoUrl(u) => Node::AutoUrl(Cow::Owned(u.into_owned())),
+            Node::AutoUrl(u, kind) => Node::AutoUrl(Cow::Owned(u.into_owned()), kind),
             Node::Tag(t) => Node::Tag(t.into_owned()),
         }
     }
 }
 
+impl Clone for Node<'_> {
+    fn clone(&self) -> Self {
+        match self {
+            Node::Text(t) => Node::Text(t.clone()),
+            Node::LineBreak => Node::LineBreak,
+            Node::AutoUrl(u, kind) => Node::AutoUrl(u.clone(), *kind),
+            Node::Tag(t) => Node::Tag(t.clone()),
+        }
+    }
+}
+
+impl PartialEq for Node<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Node::Text(a), Node::Text(b)) => a == b,
+            (Node::LineBreak, Node::LineBreak) => true,
+            (Node::AutoUrl(a, a_kind), Node::AutoUrl(b, b_kind)) => a == b && a_kind == b_kind,
+            (Node::Tag(a), Node::Tag(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Node<'_> {}
+
+/// One input node split up by [`rebuild_nodes`].
+enum Split<'b, I> {
+    /// A finished node with nothing left to rebuild.
+    Leaf(Node<'b>),
+    /// A tag without children, plus the input children to rebuild into it.
+    Tag(TagNode<'b>, I),
+}
+
+/// Rebuilds a list of nodes with an explicit stack, so deep nesting can't
+/// overflow.
+fn rebuild_nodes<'b, I: Iterator>(
+    nodes: I,
+    mut split: impl FnMut(I::Item) -> Split<'b, I>,
+) -> Vec<Node<'b>> {
+    let mut stack: Vec<(Option<TagNode<'b>>, I, Vec<Node<'b>>)> = vec![(None, nodes, Vec::new())];
+    loop {
+        let (_, rest, done) = stack.last_mut().expect("root frame stays on the stack");
+        match rest.next().map(&mut split) {
+            Some(Split::Leaf(node)) => done.push(node),
+            Some(Split::Tag(tag, children)) => stack.push((Some(tag), children, Vec::new())),
+            None => {
+                let (tag, _, done) = stack.pop().expect("frame was just seen");
+                match (tag, stack.last_mut()) {
+                    (Some(mut tag), Some((_, _, parent))) => {
+                        tag.children = done;
+                        parent.push(Node::Tag(tag));
+                    }
+                    _ => return done,
+                }
+            }
+        }
+    }
+}
+
+/// Clones a list of nodes without recursing per level.
+fn clone_nodes<'a>(nodes: &[Node<'a>]) -> Vec<Node<'a>> {
+    rebuild_nodes(nodes.iter(), |node| match node {
+        Node::Tag(tag) => Split::Tag(tag.clone_without_children(), tag.children.iter()),
+        leaf => Split::Leaf(leaf.clone()),
+    })
+}
+
+/// Converts a list of nodes to owned versions without recursing per level.
+fn nodes_into_owned(nodes: Vec<Node<'_>>) -> Vec<Node<'static>> {
+    rebuild_nodes(nodes.into_iter(), |node| match node {
+        Node::Tag(mut tag) => {
+            let children = core::mem::take(&mut tag.children);
+            Split::Tag(tag.into_owned(), children.into_iter())
+        }
+        leaf => Split::Leaf(leaf.into_owned()),
+    })
+}
+
+/// Compares two lists of nodes without recursing per level.
+fn nodes_eq(a: &[Node<'_>], b: &[Node<'_>]) -> bool {
+    let mut stack = vec![(a, b)];
+    while let Some((a, b)) = stack.pop() {
+        if a.len() != b.len() {
+            return false;
+        }
+        for pair in a.iter().zip(b) {
+            match pair {
+                (Node::Tag(x), Node::Tag(y)) => {
+                    if !x.eq_without_children(y) {
+                        return false;
+                    }
+                    stack.push((&x.children, &y.children));
+                }
+                (x, y) if x != y => return false,
+                _ => {}
+            }
+        }
+    }
+    true
+}
+
 impl fmt::Display for Node<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Node::Text(t) => write!(f, "{}", t),
             Node::LineBreak => writeln!(f),
-            Node::AutoUrl(u) => write!(f, "{}", u),
+            Node::AutoUrl(u, _) => write!(f, "{}", u),
             Node::Tag(t) => write!(f, "{}", t),
         }
     }
 }
 
 /// A BBCode tag node with its name, option, and children.
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TagNode<'a> {
     /// The tag name (lowercase).
     pub name: Cow<'a, str>,
@@ -239,6 +414,11 @@ pub struct TagNode<'a> {
     /// The tag's option/attribute value.
     pub option: TagOption<'a>,
 
+    /// The option text exactly as written between `=` (or the space before
+    /// keyed options) and `]`, including any quotes. Useful for custom
+    /// handlers with their own option syntax, such as `[wiki=Page|section]`.
+    pub raw_option: Option<Cow<'a, str>>,
+
     /// Child nodes (text and nested tags).
     pub children: Vec<Node<'a>>,
 
@@ -253,6 +433,11 @@ pub struct TagNode<'a> {
 
     /// If true, this tag failed validation and should be rendered as raw text.
     pub broken: bool,
+
+    /// Byte range of the tag in the original input, from the start of the
+    /// opening tag to the end of the closing tag (or of its content when
+    /// unclosed). Empty for nodes not produced by the parser.
+    pub span: Range<usize>,
 }
 
 impl<'a> TagNode<'a> {
@@ -330,32 +515,136 @@ impl<'a> TagNode<'a> {
         }
 
         let mut result = String::new();
-        for child in &self.children {
-            match child {
-                Node::Text(t) => result.push_str(t),
-                Node::LineBreak => result.push('\n'),
-                Node::AutoUrl(u) => result.push_str(u),
-                Node::Tag(t) => result.push_str(&t.inner_text()),
+        push_text_content(&self.children, &mut result, None);
+        Cow::Owned(result)
+    }
+
+    /// Returns the tag name as written, falling back to the lowercase name.
+    fn source_name(&self) -> &str {
+        if self.raw_name.is_empty() {
+            &self.name
+        } else {
+            &self.raw_name
+        }
+    }
+
+    /// Writes the opening tag for [`Node::write_bbcode`].
+    fn write_bbcode_open(&self, out: &mut String) {
+        // Broken tags, and nodes the parser synthesized from non-tag text
+        // such as `@mentions`, are written as they appeared
+        if self.broken || !(self.raw_open.is_empty() || self.raw_open.starts_with('[')) {
+            out.push_str(&self.raw_open);
+            return;
+        }
+
+        out.push('[');
+        out.push_str(self.source_name());
+        match &self.option {
+            TagOption::None => {}
+            TagOption::Scalar(value) => {
+                out.push('=');
+                push_option_value(value, out);
+            }
+            TagOption::Map(map) => {
+                for (key, value) in map {
+                    out.push(' ');
+                    out.push_str(key);
+                    out.push('=');
+                    push_option_value(value, out);
+                }
             }
         }
-        Cow::Owned(result)
+        out.push(']');
+    }
+
+    /// Writes the closing tag for [`Node::write_bbcode`].
+    fn write_bbcode_close(&self, out: &mut String) {
+        if self.broken {
+            out.push_str(&self.raw_close);
+        } else if !(self.raw_open.is_empty() || self.raw_open.starts_with('[')) {
+            // Synthesized from non-tag text, which has no closing tag
+        } else if !self.raw_close.is_empty() || (self.closed && !is_self_closing_tag(&self.name)) {
+            out.push_str("[/");
+            out.push_str(self.source_name());
+            out.push(']');
+        }
     }
 
     /// Converts the tag to an owned version.
-    pub fn into_owned(self) -> TagNode<'static> {
+    pub fn into_owned(mut self) -> TagNode<'static> {
+        let children = nodes_into_owned(core::mem::take(&mut self.children));
         TagNode {
             name: Cow::Owned(self.name.into_owned()),
             raw_name: Cow::Owned(self.raw_name.into_owned()),
             option: self.option.into_owned(),
-            children: self.children.into_iter().map(|c| c.into_owned()).collect(),
+            raw_option: self.raw_option.map(|raw| Cow::Owned(raw.into_owned())),
+            children,
             closed: self.closed,
             raw_open: Cow::Owned(self.raw_open.into_owned()),
             raw_close: Cow::Owned(self.raw_close.into_owned()),
             broken: self.broken,
+            span: self.span,
+        }
+    }
+
+    /// Clones everything but the children.
+    fn clone_without_children(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            raw_name: self.raw_name.clone(),
+            option: self.option.clone(),
+            raw_option: self.raw_option.clone(),
+            children: Vec::new(),
+            closed: self.closed,
+            raw_open: self.raw_open.clone(),
+            raw_close: self.raw_close.clone(),
+            broken: self.broken,
+            span: self.span.clone(),
         }
     }
+
+    /// Compares everything but the children.
+    fn eq_without_children(&self, other: &Self) -> bool {
+        let Self {
+            name,
+            raw_name,
+            option,
+            raw_option,
+            children: _,
+            closed,
+            raw_open,
+            raw_close,
+            broken,
+            span,
+        } = self;
+        *name == other.name
+            && *raw_name == other.raw_name
+            && *option == other.option
+            && *raw_option == other.raw_option
+            && *closed == other.closed
+            && *raw_open == other.raw_open
+            && *raw_close == other.raw_close
+            && *broken == other.broken
+            && *span == other.span
+    }
+}
+
+impl Clone for TagNode<'_> {
+    fn clone(&self) -> Self {
+        let mut tag = self.clone_without_children();
+        tag.children = clone_nodes(&self.children);
+        tag
+    }
 }
 
+impl PartialEq for TagNode<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.eq_without_children(other) && nodes_eq(&self.children, &other.children)
+    }
+}
+
+impl Eq for TagNode<'_> {}
+
 impl fmt::Display for TagNode<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "[{}]", self.raw_name)?;
@@ -369,8 +658,62 @@ impl fmt::Display for TagNode<'_> {
     }
 }
 
+/// Read-only traversal of a [`Document`], driven by [`Document::accept`].
+///
+/// Every method does nothing by default; implement only the ones you need.
+///
+/// # Example
+///
+/// ```rust
+/// use bbcode::{Parser, TagNode, Visitor};
+///
+/// #[derive(Default)]
+/// struct LinkCollector(Vec<String>);
+///
+/// impl Visitor for LinkCollector {
+///     fn visit_tag(&mut self, tag: &TagNode<'_>) {
+///         if tag.name == "url" {
+///             if let Some(href) = tag.option.as_scalar() {
+///                 self.0.push(href.to_string());
+///             }
+///         }
+///     }
+///
+///     fn visit_auto_url(&mut self, url: &str) {
+///         self.0.push(url.to_string());
+///     }
+/// }
+///
+/// let doc = Parser::new().parse("https://a.example [b][url=https://b.example]b[/url][/b]");
+/// let mut links = LinkCollector::default();
+/// doc.accept(&mut links);
+/// assert_eq!(links.0, ["https://a.example", "https://b.example"]);
+/// ```
+pub trait Visitor {
+    /// Called for each text node.
+    fn visit_text(&mut self, _text: &str) {}
+
+    /// Called for each tag, before its children are visited.
+    fn visit_tag(&mut self, _tag: &TagNode<'_>) {}
+
+    /// Called for each auto-detected URL.
+    fn visit_auto_url(&mut self, _url: &str) {}
+
+    /// Called for each line break.
+    fn visit_line_break(&mut self) {}
+}
+
 /// The root document node containing all parsed content.
+///
+/// Dropping, cloning, comparing and [`Document::into_owned`] work with an
+/// explicit stack, so any nesting depth is safe. `Debug`, `Display` and the
+/// serde impls still recurse per level; keep `ParserConfig::max_depth`
+/// bounded if you use them on untrusted input.
+///
+/// `Document` implements `Drop`, so `nodes` can't be moved out of it by
+/// destructuring; use [`Document::into_nodes`] or `mem::take` instead.
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Document<'a> {
     /// The top-level nodes in the document.
     pub nodes: Vec<Node<'a>>,
@@ -395,6 +738,18 @@ impl<'a> Document<'a> {
         self.nodes.push(node);
     }
 
+    /// Removes all nodes, keeping the allocated capacity for reuse.
+    ///
+    /// Nested nodes are dropped iteratively, so deeply nested documents
+    /// can't overflow the stack.
+    pub fn clear(&mut self) {
+        while let Some(node) = self.nodes.pop() {
+            if let Node::Tag(mut tag) = node {
+                self.nodes.append(&mut tag.children);
+            }
+        }
+    }
+
     /// Returns true if the document is empty.
     #[inline]
     pub fn is_empty(&self) -> bool {
@@ -413,12 +768,341 @@ impl<'a> Document<'a> {
         self.nodes.iter()
     }
 
+    /// Returns the URL of the first `[img]` in the document, if any.
+    ///
+    /// The URL is returned as written (trimmed) and is not validated; pass it
+    /// through the renderer's scheme rules before emitting it. Useful for
+    /// thumbnails where a full render would be wasted work.
+    pub fn first_image(&self) -> Option<&str> {
+        // Visit tags in document order without recursing
+        let mut stack: Vec<&Node> = self.nodes.iter().rev().collect();
+        while let Some(node) = stack.pop() {
+            let Some(tag) = node.as_tag() else {
+                continue;
+            };
+            if tag.name == "img" && !tag.broken {
+                if let [Node::Text(url) | Node::AutoUrl(url, _)] = tag.children.as_slice() {
+                    let url = url.trim();
+                    if !url.is_empty() {
+                        return Some(url);
+                    }
+                }
+            }
+            stack.extend(tag.children.iter().rev());
+        }
+        None
+    }
+
+    /// Returns the visible text of the whole document.
+    ///
+    /// See [`Node::text_content`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bbcode::Parser;
+    ///
+    /// let doc = Parser::new().parse("[b]Hello [i]World[/i][/b]");
+    /// assert_eq!(doc.text_content(), "Hello World");
+    /// ```
+    pub fn text_content(&self) -> String {
+        let mut out = String::new();
+        push_text_content(&self.nodes, &mut out, None);
+        out
+    }
+
+    /// Returns the number of words in the document's visible text.
+    ///
+    /// Words are separated by Unicode whitespace. Verbatim tags such as
+    /// `[code]` and `[icode]` are not prose and are left out of the count.
+    pub fn word_count(&self) -> usize {
+        self.word_count_with_registry(&TagRegistry::new())
+    }
+
+    /// Like [`word_count`](Self::word_count), but takes the verbatim tags to
+    /// leave out from `registry`, so custom verbatim tags are recognized.
+    pub fn word_count_with_registry(&self, registry: &TagRegistry) -> usize {
+        let mut text = String::new();
+        push_text_content(&self.nodes, &mut text, Some(registry));
+        text.split_whitespace().count()
+    }
+
+    /// Returns the estimated reading time in whole minutes at `wpm` words
+    /// per minute, rounded up from [`word_count`](Self::word_count).
+    ///
+    /// A `wpm` of 0 is treated as 1.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bbcode::Parser;
+    ///
+    /// let doc = Parser::new().parse("[b]one two[/b] three [code]let x = 1;[/code]");
+    /// assert_eq!(doc.word_count(), 3);
+    /// assert_eq!(doc.reading_time_minutes(2), 2);
+    /// ```
+    pub fn reading_time_minutes(&self, wpm: usize) -> usize {
+        self.word_count().div_ceil(wpm.max(1))
+    }
+
+    /// Returns a plaintext summary of the first paragraph, e.g. for a
+    /// `<meta name="description">`.
+    ///
+    /// Collection stops at the first blank line or block-level tag boundary.
+    /// Whitespace is collapsed, image URLs and spoiler contents are skipped,
+    /// and text longer than `max_chars` is cut at a word boundary and ends
+    /// with an ellipsis. The result never exceeds `max_chars` characters.
+    pub fn summary(&self, max_chars: usize) -> String {
+        self.summary_with_registry(max_chars, &TagRegistry::new())
+    }
+
+    /// Like [`summary`](Self::summary), but takes the block-level tags that
+    /// end the paragraph from `registry`, so custom tags are recognized.
+    pub fn summary_with_registry(&self, max_chars: usize, registry: &TagRegistry) -> String {
+        let mut collector = SummaryCollector {
+            registry,
+            text: String::new(),
+            chars: 0,
+            limit: max_chars.saturating_add(1),
+            line_breaks: 0,
+            done: false,
+        };
+        collector.collect(&self.nodes);
+
+        let mut text = collector.text;
+        let trimmed_len = text.trim_end().len();
+        text.truncate(trimmed_len);
+
+        if text.chars().count() <= max_chars {
+            return text;
+        }
+        if max_chars == 0 {
+            return String::new();
+        }
+
+        // Leave room for the ellipsis, then back off to a word boundary
+        let cut = text
+            .char_indices()
+            .nth(max_chars - 1)
+            .map_or(text.len(), |(i, _)| i);
+        let at_word_end = text[cut..].starts_with(' ');
+        text.truncate(cut);
+        if !at_word_end {
+            if let Some(space) = text.rfind(' ') {
+                text.truncate(space);
+            }
+        }
+        let trimmed_len = text
+            .trim_end_matches(|c: char| c.is_whitespace() || c == ',' || c == ';')
+            .len();
+        text.truncate(trimmed_len);
+        text.push('\u{2026}');
+        text
+    }
+
+    /// Returns true if rendering the document with `config` produces no
+    /// visible content, e.g. a post of only whitespace and empty formatting.
+    ///
+    /// See [`Renderer::is_empty_render`](crate::Renderer::is_empty_render),
+    /// which also takes registered custom tags and handlers into account.
+    pub fn is_empty_render(&self, config: &crate::RenderConfig) -> bool {
+        crate::Renderer::with_config(config.clone()).is_empty_render(self)
+    }
+
+    /// Walks the document in source order, calling `visitor` for every node.
+    ///
+    /// Tags are visited before their children. The walk uses an explicit
+    /// stack, so deeply nested documents are safe.
+    pub fn accept(&self, visitor: &mut impl Visitor) {
+        let mut stack: Vec<&Node<'a>> = self.nodes.iter().rev().collect();
+        while let Some(node) = stack.pop() {
+            match node {
+                Node::Text(text) => visitor.visit_text(text),
+                Node::LineBreak => visitor.visit_line_break(),
+                Node::AutoUrl(url, _) => visitor.visit_auto_url(url),
+                Node::Tag(tag) => {
+                    visitor.visit_tag(tag);
+                    stack.extend(tag.children.iter().rev());
+                }
+            }
+        }
+    }
+
+    /// Reconstructs BBCode source for the document, e.g. to prefill an "edit
+    /// post" form.
+    ///
+    /// The result re-parses to an equivalent document. See
+    /// [`Node::write_bbcode`] for how each node is written.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bbcode::Parser;
+    ///
+    /// let doc = Parser::new().parse("[B]hi[/B] [quote=\"A B\"]x[/quote]");
+    /// assert_eq!(doc.to_bbcode(), "[B]hi[/B] [quote=\"A B\"]x[/quote]");
+    /// ```
+    pub fn to_bbcode(&self) -> String {
+        let mut out = String::new();
+        for node in &self.nodes {
+            node.write_bbcode(&mut out);
+        }
+        out
+    }
+
     /// Converts the document to an owned version.
-    pub fn into_owned(self) -> Document<'static> {
+    pub fn into_owned(mut self) -> Document<'static> {
         Document {
-            nodes: self.nodes.into_iter().map(|n| n.into_owned()).collect(),
+            nodes: nodes_into_owned(core::mem::take(&mut self.nodes)),
         }
     }
+
+    /// Takes the top-level nodes out of the document.
+    ///
+    /// Unlike dropping the document, dropping the returned nodes recurses
+    /// per nesting level.
+    pub fn into_nodes(mut self) -> Vec<Node<'a>> {
+        core::mem::take(&mut self.nodes)
+    }
+}
+
+/// Accumulates collapsed plaintext for [`Document::summary`].
+struct SummaryCollector<'r> {
+    registry: &'r TagRegistry,
+    text: String,
+    chars: usize,
+    limit: usize,
+    line_breaks: usize,
+    done: bool,
+}
+
+impl SummaryCollector<'_> {
+    fn collect(&mut self, nodes: &[Node<'_>]) {
+        // Pending nodes in document order, with `None` marking the end of a
+        // block tag; an explicit stack so deep nesting can't overflow
+        let mut stack: Vec<Option<&Node>> = nodes.iter().rev().map(Some).collect();
+        while let Some(item) = stack.pop() {
+            if self.done {
+                return;
+            }
+            let Some(node) = item else {
+                self.done = self.has_text();
+                continue;
+            };
+            match node {
+                Node::Text(text) | Node::AutoUrl(text, _) => {
+                    self.line_breaks = 0;
+                    self.push_text(text);
+                }
+                Node::LineBreak => {
+                    self.line_breaks += 1;
+                    if self.line_breaks >= 2 && self.has_text() {
+                        self.done = true;
+                    } else {
+                        self.push_text(" ");
+                    }
+                }
+                Node::Tag(tag) => match &*tag.name {
+                    "img" | "image" | "spoiler" | "ispoiler" => {}
+                    "br" => self.push_text(" "),
+                    _ => {
+                        let block = self.registry.is_block_level(&tag.name);
+                        if block && self.has_text() {
+                            self.done = true;
+                            return;
+                        }
+                        if block {
+                            stack.push(None);
+                        }
+                        stack.extend(tag.children.iter().rev().map(Some));
+                    }
+                },
+            }
+        }
+    }
+
+    fn has_text(&self) -> bool {
+        self.chars > 0
+    }
+
+    fn push_text(&mut self, text: &str) {
+        for c in text.chars() {
+            if self.chars >= self.limit {
+                self.done = true;
+                return;
+            }
+            if c.is_whitespace() {
+                if self.chars == 0 || self.text.ends_with(' ') {
+                    continue;
+                }
+                self.text.push(' ');
+            } else {
+                self.text.push(c);
+            }
+            self.chars += 1;
+        }
+    }
+}
+
+/// Appends the text of `nodes` and their descendants to `out`.
+///
+/// With `skip_verbatim`, tags the registry resolves as verbatim are replaced
+/// by a single space.
+fn push_text_content(nodes: &[Node<'_>], out: &mut String, skip_verbatim: Option<&TagRegistry>) {
+    // Walk the subtree with an explicit stack of remaining siblings so deep
+    // nesting can't overflow the call stack
+    let mut stack = vec![nodes];
+    while let Some(nodes) = stack.last_mut() {
+        let Some((child, rest)) = nodes.split_first() else {
+            stack.pop();
+            continue;
+        };
+        *nodes = rest;
+        match child {
+            Node::Text(t) | Node::AutoUrl(t, _) => out.push_str(t),
+            Node::LineBreak => out.push('\n'),
+            Node::Tag(t)
+                if skip_verbatim.is_some_and(|registry| {
+                    registry
+                        .resolve(&t.name)
+                        .is_some_and(|tag| tag.is_verbatim())
+                }) =>
+            {
+                out.push(' ');
+            }
+            Node::Tag(t) => stack.push(&t.children),
+        }
+    }
+}
+
+/// Writes a tag option value, quoting it when needed to re-parse the same.
+fn push_option_value(value: &str, out: &mut String) {
+    let needs_quotes = value.is_empty()
+        || value.starts_with(['"', '\''])
+        || value.contains(|c: char| c.is_whitespace() || c == ']' || c == '[');
+    if !needs_quotes {
+        out.push_str(value);
+    } else {
+        let quote = if value.contains('"') { '\'' } else { '"' };
+        out.push(quote);
+        out.push_str(value);
+        out.push(quote);
+    }
+}
+
+/// Returns true for standard tags that never have a closing tag.
+fn is_self_closing_tag(name: &str) -> bool {
+    crate::tags::STANDARD_TAGS.iter().any(|def| {
+        (def.name == name || def.aliases.contains(&name)) && def.tag_type == TagType::SelfClosing
+    })
+}
+
+impl Drop for Document<'_> {
+    /// Drops the tree iteratively so deeply nested documents can't overflow
+    /// the stack.
+    fn drop(&mut self) {
+        self.clear();
+    }
 }
 
 impl fmt::Display for Document<'_> {
@@ -459,7 +1143,7 @@ mod tests {
 
     #[test]
     fn tag_option_map() {
-        let mut map = HashMap::new();
+        let mut map = BTreeMap::new();
         map.insert(Cow::Borrowed("width"), Cow::Borrowed("100"));
         map.insert(Cow::Borrowed("height"), Cow::Borrowed("200"));
         let opt = TagOption::Map(map);
@@ -642,6 +1326,207 @@ mod tests {
         assert!(!owned.is_empty());
     }
 
+    #[test]
+    fn document_first_image() {
+        let doc = crate::Parser::new().parse(
+            "Hi [b][img]https://a.example/1.png[/img][/b] [img]https://a.example/2.png[/img]",
+        );
+        assert_eq!(doc.first_image(), Some("https://a.example/1.png"));
+
+        let doc = crate::Parser::new().parse("[b]no images here[/b]");
+        assert_eq!(doc.first_image(), None);
+    }
+
+    #[test]
+    fn text_content_descends_into_tags() {
+        let doc = crate::Parser::new().parse("[b]Hello [i]World[/i][/b]");
+        assert_eq!(doc.text_content(), "Hello World");
+        assert_eq!(doc.nodes[0].text_content(), "Hello World");
+
+        let doc = crate::Parser::new().parse("a\n[url=https://x.com]b[/url] https://y.com");
+        assert_eq!(doc.text_content(), "a\nb https://y.com");
+    }
+
+    #[test]
+    fn word_count_empty_document() {
+        let doc = crate::Parser::new().parse("");
+        assert_eq!(doc.word_count(), 0);
+        assert_eq!(doc.reading_time_minutes(200), 0);
+    }
+
+    #[test]
+    fn word_count_mixed_post() {
+        let doc = crate::Parser::new().parse(
+            "Hel[b]lo[/b] [i]big[/i]\nworld, see https://x.com\
+             [code]fn main() { println!(\"not counted\"); }[/code]done",
+        );
+        assert_eq!(doc.word_count(), 6);
+        assert_eq!(doc.reading_time_minutes(4), 2);
+        assert_eq!(doc.reading_time_minutes(0), 6);
+    }
+
+    #[test]
+    fn word_count_skips_registry_verbatim_tags() {
+        let doc =
+            crate::Parser::new().parse("run [icode]cargo test[/icode] [PHP]echo 1;[/PHP] now");
+        assert_eq!(doc.word_count(), 2);
+
+        let mut registry = crate::TagRegistry::new();
+        registry.register(crate::CustomTagDef {
+            name: "sql".into(),
+            tag_type: TagType::Verbatim,
+            ..Default::default()
+        });
+        let doc = crate::Parser::with_registry(registry.clone())
+            .parse("query [sql]SELECT name FROM users[/sql] done");
+        assert_eq!(doc.word_count_with_registry(&registry), 2);
+        assert_eq!(doc.word_count(), 6);
+    }
+
+    #[test]
+    fn document_summary_first_paragraph() {
+        let doc = crate::Parser::new()
+            .parse("Hello [b]world[/b],   this is\na post.\n\nSecond paragraph here.");
+        assert_eq!(doc.summary(160), "Hello world, this is a post.");
+    }
+
+    #[test]
+    fn document_summary_truncates_at_word() {
+        let doc = crate::Parser::new().parse("The quick brown fox jumps over the lazy dog\n\nMore");
+        assert_eq!(doc.summary(20), "The quick brown fox\u{2026}");
+        assert_eq!(doc.summary(18), "The quick brown\u{2026}");
+        assert!(doc.summary(20).chars().count() <= 20);
+    }
+
+    #[test]
+    fn document_summary_stops_at_block() {
+        let doc = crate::Parser::new()
+            .parse("Intro text [quote]quoted[/quote] after [img]https://a.example/x.png[/img]");
+        assert_eq!(doc.summary(160), "Intro text");
+
+        let doc = crate::Parser::new()
+            .parse("[spoiler]secret[/spoiler][center]Centered title[/center] rest");
+        assert_eq!(doc.summary(160), "Centered title");
+    }
+
+    #[test]
+    fn document_summary_stops_at_registry_block() {
+        let mut registry = crate::TagRegistry::new();
+        registry.register(crate::CustomTagDef {
+            name: "note".into(),
+            tag_type: TagType::Block,
+            ..Default::default()
+        });
+        let doc =
+            crate::Parser::with_registry(registry.clone()).parse("Intro [NOTE]aside[/NOTE] after");
+        assert_eq!(doc.summary_with_registry(160, &registry), "Intro");
+        assert_eq!(doc.summary(160), "Intro aside after");
+    }
+
+    #[test]
+    fn document_is_empty_render() {
+        let config = crate::RenderConfig::default();
+        let doc = crate::Parser::new().parse(" \n\n[b]  [/b]\t");
+        assert!(doc.is_empty_render(&config));
+
+        let doc = crate::Parser::new().parse("\n[i]hello[/i]");
+        assert!(!doc.is_empty_render(&config));
+    }
+
+    #[test]
+    fn document_to_bbcode_round_trip() {
+        let parser = crate::Parser::new();
+        for input in [
+            "[b]hi[/b]\n[url=https://x.com]y[/url]",
+            "See https://x.com/a, then [I]this[/I]",
+            "[quote=\"Some One\"]a[hr]b[/quote][quote='say \"hi\"']c[/quote]",
+            "[img width=100 alt=\"a cat\"]https://x.com/c.png[/img]",
+            "[list][*]one[*]two [b]bold[/list]",
+            "[b]unclosed [i]broken[/b] [code][b]x[/code] [unknown=1]z[/unknown]",
+        ] {
+            let doc = parser.parse(input);
+            let source = doc.to_bbcode();
+            let reparsed = parser.parse(&source);
+            assert_eq!(reparsed.to_bbcode(), source, "{input:?}");
+            assert_eq!(
+                crate::parse(&source),
+                crate::parse(input),
+                "{input:?} became {source:?}"
+            );
+        }
+
+        let doc = parser.parse("[b]hi[/b]\n[url=https://x.com]y[/url]");
+        assert_eq!(doc.to_bbcode(), "[b]hi[/b]\n[url=https://x.com]y[/url]");
+        assert_eq!(parser.parse(&doc.to_bbcode()), doc);
+    }
+
+    #[test]
+    fn document_to_bbcode_rebuilds_tags() {
+        let mut doc = crate::Parser::new().parse("[COLOR=red]x[/COLOR][hr]");
+        doc.nodes[0].as_tag_mut().unwrap().option = TagOption::Scalar("dark blue".into());
+        assert_eq!(doc.to_bbcode(), "[COLOR=\"dark blue\"]x[/COLOR][hr]");
+
+        let mut tag = TagNode::new("size").with_option(TagOption::Scalar("3".into()));
+        tag.push_child(Node::text("big"));
+        tag.closed = true;
+        let doc = Document::with_nodes(vec![Node::Tag(tag), Node::LineBreak, Node::tag("br")]);
+        assert_eq!(doc.to_bbcode(), "[size=3]big[/size]\n[br]");
+
+        let parser = crate::Parser::with_config(crate::ParserConfig {
+            parse_mentions: true,
+            ..Default::default()
+        });
+        assert_eq!(parser.parse("hi @bob!").to_bbcode(), "hi @bob!");
+    }
+
+    #[test]
+    fn document_accept_visitor() {
+        #[derive(Default)]
+        struct Collector {
+            images: Vec<String>,
+            tags: usize,
+            texts: usize,
+            line_breaks: usize,
+        }
+
+        impl Visitor for Collector {
+            fn visit_text(&mut self, _text: &str) {
+                self.texts += 1;
+            }
+
+            fn visit_tag(&mut self, tag: &TagNode<'_>) {
+                self.tags += 1;
+                if tag.name == "img" && !tag.broken {
+                    self.images.push(tag.inner_text().trim().to_string());
+                }
+            }
+
+            fn visit_line_break(&mut self) {
+                self.line_breaks += 1;
+            }
+        }
+
+        let doc = crate::Parser::new().parse(
+            "[img]https://x.com/1.png[/img] text\n\
+             [quote][b][IMG]https://x.com/2.png[/IMG][/b][/quote]\n\
+             [list][*][img=10x10]https://x.com/3.png[/img][/list]",
+        );
+        let mut collector = Collector::default();
+        doc.accept(&mut collector);
+
+        assert_eq!(
+            collector.images,
+            [
+                "https://x.com/1.png",
+                "https://x.com/2.png",
+                "https://x.com/3.png"
+            ]
+        );
+        assert_eq!(collector.tags, 7);
+        assert_eq!(collector.line_breaks, 2);
+        assert!(collector.texts >= 4);
+    }
+
     #[test]
     fn tag_type_default() {
         assert_eq!(TagType::default(), TagType::Inline);
@@ -3,7 +3,9 @@
 //! This module defines all error types using `thiserror` for zero-overhead,
 //! typed errors.
 
-use std::borrow::Cow;
+use alloc::borrow::Cow;
+use alloc::string::String;
+use core::ops::Range;
 use thiserror::Error;
 
 /// Errors that can occur during BBCode parsing.
@@ -17,13 +19,23 @@ pub enum ParseError {
     #[error("invalid tag name: {name}")]
     InvalidTagName { name: String },
 
-    /// An unclosed tag was found.
+    /// An unclosed tag was found. `span` covers the opening tag.
     #[error("unclosed tag: [{tag}]")]
-    UnclosedTag { tag: String },
+    UnclosedTag { tag: String, span: Range<usize> },
 
-    /// A closing tag was found without a matching opening tag.
+    /// A closing tag was found without a matching opening tag. `span`
+    /// covers the closing tag.
     #[error("unmatched closing tag: [/{tag}]")]
-    UnmatchedClosingTag { tag: String },
+    UnmatchedClosingTag { tag: String, span: Range<usize> },
+
+    /// A closing tag was found while a tag opened inside it was still open,
+    /// as in `[b][i]x[/b]`. `span` covers the closing tag.
+    #[error("mismatched closing tag: [/{tag}] while [{open}] is open")]
+    MismatchedClosingTag {
+        tag: String,
+        open: String,
+        span: Range<usize>,
+    },
 
     /// An invalid attribute value was provided.
     #[error("invalid attribute value for [{tag}]: {message}")]
@@ -57,7 +69,7 @@ pub enum ParseError {
 /// Errors that can occur during HTML rendering.
 #[derive(Debug, Error, PartialEq, Eq, Clone)]
 pub enum RenderError {
-    /// An IO error occurred during rendering.
+    /// Rendering failed, e.g. a custom tag handler reported an error.
     #[error("render error: {message}")]
     Generic { message: Cow<'static, str> },
 }
@@ -84,14 +96,26 @@ mod tests {
 
         let err = ParseError::UnclosedTag {
             tag: "b".to_string(),
+            span: 0..3,
         };
         assert_eq!(err.to_string(), "unclosed tag: [b]");
 
         let err = ParseError::UnmatchedClosingTag {
             tag: "i".to_string(),
+            span: 0..4,
         };
         assert_eq!(err.to_string(), "unmatched closing tag: [/i]");
 
+        let err = ParseError::MismatchedClosingTag {
+            tag: "b".to_string(),
+            open: "i".to_string(),
+            span: 7..11,
+        };
+        assert_eq!(
+            err.to_string(),
+            "mismatched closing tag: [/b] while [i] is open"
+        );
+
         let err = ParseError::InvalidAttribute {
             tag: "color".to_string(),
             message: "must be a valid CSS color".to_string(),
@@ -159,6 +183,7 @@ mod tests {
     fn error_clone() {
         let err = ParseError::UnclosedTag {
             tag: "quote".to_string(),
+            span: 0..7,
         };
         let cloned = err.clone();
         assert_eq!(err, cloned);
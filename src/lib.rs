@@ -70,13 +70,25 @@
 //! // Custom renderer config
 //! let render_config = RenderConfig {
 //!     class_prefix: "my-bbcode".into(),
-//!     nofollow_links: true,
+//!     link_rel: vec!["nofollow".into(), "noreferrer".into()],
 //!     ..Default::default()
 //! };
 //!
 //! let renderer = Renderer::with_config(render_config);
 //! let html = renderer.render(&doc);
 //! ```
+//!
+//! ## `no_std`
+//!
+//! The default `std` feature can be disabled to build for `no_std` targets
+//! with an allocator. Parsing and rendering work the same either way; the
+//! [`Map`] and [`Set`] types in the API become `BTreeMap`/`BTreeSet`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use alloc::string::String;
 
 pub mod ast;
 pub mod error;
@@ -86,12 +98,38 @@ pub mod tags;
 pub mod tokenizer;
 
 // Re-exports for convenience
-pub use ast::{Document, Node, TagNode, TagOption, TagType};
+pub use ast::{AutoLinkKind, Document, Node, TagNode, TagOption, TagType, Visitor};
 pub use error::{ParseError, RenderError};
-pub use parser::{Parser, ParserConfig};
-pub use renderer::{escape_html, CustomTagHandler, RenderConfig, RenderContext, Renderer};
+pub use parser::{ParseEvent, ParseEventSink, Parser, ParserConfig, TagEnd};
+pub use renderer::{
+    escape_html, escape_html_text, AttachmentInfo, AttachmentResolver, CustomTagHandler,
+    OutputMode, PostUrlResolver, RenderConfig, RenderContext, Renderer, SizeMode, SpoilerMode,
+    StrayContent, TagDecision, TagFilter, UrlRewriter, UserLinkResolver,
+};
 pub use tags::{CustomTagDef, ResolvedTag, TagDef, TagRegistry, STANDARD_TAGS};
-pub use tokenizer::{tokenize, Token};
+pub use tokenizer::{tokenize, tokenize_spanned, SpannedToken, Token, Tokenizer};
+
+/// The map type used in the API, such as [`RenderConfig::smilies`].
+///
+/// This is `HashMap`, or `BTreeMap` when the `std` feature is disabled.
+#[cfg(feature = "std")]
+pub type Map<K, V> = std::collections::HashMap<K, V>;
+/// The map type used in the API, such as [`RenderConfig::smilies`].
+///
+/// This is `HashMap`, or `BTreeMap` when the `std` feature is disabled.
+#[cfg(not(feature = "std"))]
+pub type Map<K, V> = alloc::collections::BTreeMap<K, V>;
+
+/// The set type used in the API, such as [`ParserConfig::disabled_tags`].
+///
+/// This is `HashSet`, or `BTreeSet` when the `std` feature is disabled.
+#[cfg(feature = "std")]
+pub type Set<T> = std::collections::HashSet<T>;
+/// The set type used in the API, such as [`ParserConfig::disabled_tags`].
+///
+/// This is `HashSet`, or `BTreeSet` when the `std` feature is disabled.
+#[cfg(not(feature = "std"))]
+pub type Set<T> = alloc::collections::BTreeSet<T>;
 
 /// Parses BBCode and renders it to HTML with default settings.
 ///
@@ -196,7 +234,7 @@ mod tests {
     fn test_html_escaping() {
         assert_eq!(
             parse("<script>alert('xss')</script>"),
-            "&lt;script&gt;alert(&#x27;xss&#x27;)&lt;/script&gt;"
+            "&lt;script&gt;alert('xss')&lt;/script&gt;"
         );
     }
 
@@ -621,6 +659,95 @@ fn main() {
         let _result = renderer.render(&doc);
     }
 
+    #[test]
+    fn test_block_inside_inline() {
+        let result = parse("[b]x[quote]y[/quote]z[/b]");
+        assert!(!result.contains("<strong>x<blockquote"));
+        assert!(result.starts_with("<strong>x</strong><blockquote"));
+        assert!(result.ends_with("</blockquote><strong>z</strong>"));
+    }
+
+    #[test]
+    fn test_hr_inside_inline() {
+        assert_eq!(
+            parse("[b]a[hr]b[/b]"),
+            "<strong>a</strong><hr /><strong>b</strong>"
+        );
+    }
+
+    #[test]
+    fn test_max_depth_truncates_to_text() {
+        let parser_config = ParserConfig {
+            max_depth: 2,
+            ..Default::default()
+        };
+
+        let result = parse_with_config(
+            "[b][b][b]x[/b][/b][/b]",
+            &parser_config,
+            &RenderConfig::default(),
+        );
+        assert_eq!(result.matches("<strong>").count(), 2);
+        assert_eq!(result, "<strong><strong>[b]x[/b]</strong></strong>");
+    }
+
+    #[test]
+    fn test_max_depth_unclosed_tag_keeps_later_close_tags() {
+        let parser_config = ParserConfig {
+            max_depth: 2,
+            ..Default::default()
+        };
+
+        let result = parse_with_config(
+            "[i][u][b]x[/u][/i]z[b]w[/b]",
+            &parser_config,
+            &RenderConfig::default(),
+        );
+        assert_eq!(result, "<em><u>[b]x</u></em>z<strong>w</strong>");
+
+        let result = parse_with_config(
+            "[quote][quote][b]x[/quote][/quote] tail [b]y[/b]",
+            &parser_config,
+            &RenderConfig::default(),
+        );
+        assert!(result.contains("[b]x"));
+        assert!(result.ends_with(" tail <strong>y</strong>"));
+    }
+
+    #[test]
+    fn test_newline_list_items() {
+        let parser_config = ParserConfig {
+            newline_list_items: true,
+            ..Default::default()
+        };
+
+        let result = parse_with_config(
+            "[list]\nApple\nBanana\n[/list]",
+            &parser_config,
+            &RenderConfig::default(),
+        );
+        assert_eq!(
+            result,
+            "<ul class=\"bbcode-list\"><li>Apple</li><li>Banana</li></ul>"
+        );
+    }
+
+    #[test]
+    fn test_deep_nesting_is_iterative() {
+        let depth = 10_000;
+        let input = "[b]".repeat(depth) + "x" + &"[/b]".repeat(depth);
+        let parser_config = ParserConfig {
+            max_depth: depth,
+            ..Default::default()
+        };
+
+        // Test threads have small stacks, so recursion per level would overflow
+        let result = parse_with_config(&input, &parser_config, &RenderConfig::default());
+        assert_eq!(result.matches("<strong>").count(), depth);
+        assert_eq!(result.matches("</strong>").count(), depth);
+        assert!(result.contains("<strong>x</strong>"));
+    }
+
     // ============================================================================
     // Renderer Config Tests
     // ============================================================================
@@ -656,6 +783,7 @@ fn main() {
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_nofollow_disabled() {
         let render_config = RenderConfig {
             nofollow_links: false,
@@ -669,4 +797,54 @@ fn main() {
 
         assert!(!result.contains("rel=\"nofollow\""));
     }
+
+    #[test]
+    fn test_icode_verbatim_by_default() {
+        let result = parse("[icode][b]x[/b][/icode]");
+        assert!(result.contains("<code class=\"bbcode-icode\">[b]x[/b]</code>"));
+    }
+
+    #[test]
+    fn test_icode_parses_bbcode_when_not_verbatim() {
+        let parser_config = ParserConfig {
+            icode_verbatim: false,
+            ..Default::default()
+        };
+        let render_config = RenderConfig::default();
+
+        let result = parse_with_config("[icode][b]x[/b][/icode]", &parser_config, &render_config);
+        assert_eq!(
+            result,
+            "<code class=\"bbcode-icode\"><strong>x</strong></code>"
+        );
+
+        // [code] stays verbatim regardless
+        let result = parse_with_config("[code][b]x[/b][/code]", &parser_config, &render_config);
+        assert!(result.contains("[b]x[/b]"));
+
+        // Plain inline code is still escaped text
+        let result = parse_with_config("[icode]a < b :)[/icode]", &parser_config, &render_config);
+        assert_eq!(result, "<code class=\"bbcode-icode\">a &lt; b :)</code>");
+    }
+
+    #[test]
+    fn test_allowed_schemes() {
+        let parser_config = ParserConfig::default();
+
+        let https_only = RenderConfig::with_schemes(&["https"]);
+        let result = parse_with_config("[url]http://x.com[/url]", &parser_config, &https_only);
+        assert!(!result.contains("href="));
+        let result = parse_with_config("[url]https://x.com[/url]", &parser_config, &https_only);
+        assert!(result.contains("href=\"https://x.com\""));
+
+        let with_ftp = RenderConfig::with_schemes(&["http", "https", "FTP"]);
+        let result = parse_with_config("[url]ftp://x.com/file[/url]", &parser_config, &with_ftp);
+        assert!(result.contains("href=\"ftp://x.com/file\""));
+        let result = parse_with_config(
+            "[url]ftp://x.com/file[/url]",
+            &parser_config,
+            &RenderConfig::default(),
+        );
+        assert!(!result.contains("href="));
+    }
 }
@@ -3,12 +3,18 @@
 //! This module contains the parser that takes tokenized BBCode and
 //! builds a tree structure representing the document.
 
-use std::borrow::Cow;
-use std::collections::HashMap;
-
-use crate::ast::{Document, Node, TagNode, TagOption};
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::ast::{AutoLinkKind, Document, Node, TagNode, TagOption, TagType};
+use crate::error::ParseError;
 use crate::tags::{CustomTagDef, ResolvedTag, TagRegistry};
-use crate::tokenizer::{tokenize, tokenize_until_close, Token};
+use crate::tokenizer::{tokenize_spanned, tokenize_until_close, SpannedToken, Token};
+use crate::Set;
+use alloc::collections::BTreeMap;
 
 /// Maximum nesting depth to prevent stack overflow.
 const MAX_NESTING_DEPTH: usize = 50;
@@ -22,11 +28,66 @@ pub struct ParserConfig {
     /// Whether to auto-detect URLs in text.
     pub auto_link: bool,
 
+    /// Whether bare `www.` hosts are auto-linked too (with an inferred
+    /// `https://` scheme). Requires `auto_link`.
+    pub auto_link_www: bool,
+
+    /// Whether bare email addresses are auto-linked to `mailto:`. Requires
+    /// `auto_link`.
+    pub auto_link_emails: bool,
+
+    /// Whether `@name` mentions in text are parsed as `[user]name[/user]`.
+    /// Mentions are not detected where auto-linking is suppressed (e.g.
+    /// inside `[url]`) or when the `user` tag is disabled.
+    pub parse_mentions: bool,
+
+    /// Whether each non-empty line directly inside a `[list]` starts a list
+    /// item, for lists written without `[*]` markers. An item started this
+    /// way ends at the next line break; explicit `[*]` items are unaffected.
+    pub newline_list_items: bool,
+
     /// Whether to convert line breaks to <br>.
     pub convert_linebreaks: bool,
 
     /// Whether unknown tags should be treated as text.
     pub allow_unknown_tags: bool,
+
+    /// Whether to apply typographic replacements (curly quotes, em-dashes,
+    /// ellipses) to text. Verbatim content and URLs are left untouched.
+    pub typographic: bool,
+
+    /// Whether `[icode]` content is captured verbatim. When false, BBCode
+    /// inside inline code is parsed like any other inline tag, and the
+    /// renderer renders it inside the `<code>` element.
+    pub icode_verbatim: bool,
+
+    /// If set, only these tags (by name or alias, lowercase) are parsed;
+    /// all others are kept as literal text.
+    pub allowed_tags: Option<Set<String>>,
+
+    /// Tags (by name or alias, lowercase) that are always kept as literal
+    /// text. Takes precedence over `allowed_tags`.
+    pub disabled_tags: Set<String>,
+
+    /// Whether malformed markup (unclosed tags, unmatched or mismatched
+    /// close tags) is reported to [`ParseEventSink::error`] while parsing.
+    /// Parsing itself stays lenient. [`Parser::parse_strict`] always reports.
+    pub strict: bool,
+
+    /// Whether close tags auto-balance the markup (XenForo behavior): a
+    /// close tag for an outer tag closes it even while inner tags are open,
+    /// and tags left open at end of input are closed there.
+    ///
+    /// When false, a close tag only matches the innermost open tag (list
+    /// items excepted) and is kept as text otherwise. At end of input the
+    /// outermost open tag is still closed, as when the input stops
+    /// mid-post, but any tags left open inside it are kept as text.
+    pub auto_close: bool,
+
+    /// Maximum input length in bytes (`None` = unbounded). Longer input is
+    /// truncated at the last character boundary within the limit before
+    /// tokenizing, so a huge payload can't tie up the parser.
+    pub max_input_len: Option<usize>,
 }
 
 impl Default for ParserConfig {
@@ -34,10 +95,293 @@ impl Default for ParserConfig {
         Self {
             max_depth: MAX_NESTING_DEPTH,
             auto_link: true,
+            auto_link_www: false,
+            auto_link_emails: false,
+            parse_mentions: false,
+            newline_list_items: false,
             convert_linebreaks: true,
             allow_unknown_tags: true,
+            typographic: false,
+            icode_verbatim: true,
+            allowed_tags: None,
+            disabled_tags: Set::new(),
+            strict: false,
+            auto_close: true,
+            max_input_len: None,
+        }
+    }
+}
+
+/// An event reported by [`Parser::parse_events`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseEvent<'a> {
+    /// A tag was opened. The node has no children; its content follows as
+    /// further events up to the matching [`ParseEvent::EndTag`].
+    StartTag(TagNode<'a>),
+    /// The innermost open tag ended.
+    EndTag(TagEnd<'a>),
+    /// Plain text.
+    Text(Cow<'a, str>),
+    /// An auto-detected URL (or, if enabled, a bare `www.` host or email
+    /// address), as written in the input.
+    AutoUrl(Cow<'a, str>, AutoLinkKind),
+    /// A line break.
+    LineBreak,
+}
+
+/// How a tag ended, reported with [`ParseEvent::EndTag`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TagEnd<'a> {
+    /// The raw closing tag text (empty if not explicitly closed).
+    pub raw_close: Cow<'a, str>,
+    /// Whether the tag was explicitly closed (or is self-closing).
+    pub closed: bool,
+    /// Whether the tag was left open inside a tag that closed around it.
+    /// Broken tags are rendered as text.
+    pub broken: bool,
+    /// Byte offset in the input just past the end of the tag.
+    pub end: usize,
+}
+
+/// Receiver for [`Parser::parse_events`].
+pub trait ParseEventSink<'a> {
+    /// Handles a single parse event.
+    fn event(&mut self, event: ParseEvent<'a>);
+
+    /// Handles a diagnostic about malformed markup, reported only in strict
+    /// mode (see [`ParserConfig::strict`]). The events are unaffected: the
+    /// offending tags are still auto-closed or kept as text. Ignored by
+    /// default.
+    fn error(&mut self, _error: ParseError) {}
+}
+
+/// Event sink that assembles events into a [`Document`].
+///
+/// Inline tags left open when a block tag starts are closed before it and
+/// reopened inside and after it, as browsers do, so `[b]x[quote]y[/quote]z[/b]`
+/// never puts a `<blockquote>` inside `<strong>`. To keep the tree from
+/// growing much larger than the input, a block is left inside the inline
+/// tags instead when more than [`MAX_REOPENED_TAGS`] are open, or once
+/// [`REOPENED_MARKUP_BUDGET`] is used up.
+struct TreeBuilder<'r, 'a> {
+    registry: &'r TagRegistry,
+    doc: Document<'a>,
+    stack: Vec<OpenTag<'a>>,
+    errors: Vec<ParseError>,
+    /// Approximate bytes of markup added by reopening tags so far.
+    reopened_markup: usize,
+}
+
+/// A tag being built by [`TreeBuilder`].
+struct OpenTag<'a> {
+    tag: TagNode<'a>,
+    /// Reopened inside a block; the parser never sends its end event.
+    synthetic: bool,
+    /// One part of an inline tag split around a block; dropped if empty.
+    split: bool,
+    /// Inline tags closed before this block, reopened after it together
+    /// with whether they were synthetic.
+    carried: Vec<(TagNode<'a>, bool)>,
+}
+
+impl<'r, 'a> TreeBuilder<'r, 'a> {
+    /// Creates a builder that appends to `doc`.
+    fn new(registry: &'r TagRegistry, doc: Document<'a>) -> Self {
+        Self {
+            registry,
+            doc,
+            stack: Vec::new(),
+            errors: Vec::new(),
+            reopened_markup: 0,
+        }
+    }
+
+    /// Pushes a node to the current context (stack top or document root).
+    fn push(&mut self, node: Node<'a>) {
+        if let Some(parent) = self.stack.last_mut() {
+            parent.tag.children.push(node);
+        } else {
+            self.doc.push(node);
+        }
+    }
+
+    /// Adds a finished tag to its parent, dropping empty split parts.
+    fn push_tag(&mut self, open: OpenTag<'a>) {
+        if !(open.split && open.tag.children.is_empty() && !open.tag.broken) {
+            self.push(Node::Tag(open.tag));
+        }
+    }
+
+    /// Closes the open tag on top of the stack without an end event.
+    fn auto_close(&mut self, end: usize) -> Option<TagNode<'a>> {
+        let mut open = self.stack.pop()?;
+        let template = reopened_tag(&open.tag, end);
+        open.split = true;
+        open.tag.closed = true;
+        open.tag.span.end = end;
+        self.push_tag(open);
+        Some(template)
+    }
+
+    fn tag_type(&self, name: &str) -> Option<TagType> {
+        self.registry.resolve(name).map(|tag| tag.tag_type())
+    }
+
+    fn start_tag(&mut self, tag: TagNode<'a>) {
+        // Code blocks and `[hr]` render as block elements too
+        let block = self.registry.is_block_level(&tag.name);
+
+        // Lists and tables only hold items, rows and cells directly
+        let reopen_inside = self.tag_type(&tag.name) == Some(TagType::Block)
+            && !self.registry.restricts_children(&tag.name);
+
+        let mut carried = Vec::new();
+        if block && self.reserve_reopened(reopen_inside) {
+            let start = tag.span.start;
+            while let Some(top) = self.stack.last() {
+                if !self.is_splittable(top) {
+                    break;
+                }
+                let synthetic = top.synthetic;
+                let template = self.auto_close(start).expect("checked non-empty");
+                carried.push((template, synthetic));
+            }
+            carried.reverse();
+        }
+
+        let inside: Vec<_> = if reopen_inside {
+            carried
+                .iter()
+                .map(|(template, _)| template.clone())
+                .collect()
+        } else {
+            Vec::new()
+        };
+        self.stack.push(OpenTag {
+            tag,
+            synthetic: false,
+            split: false,
+            carried,
+        });
+        for tag in inside {
+            self.stack.push(OpenTag {
+                tag,
+                synthetic: true,
+                split: true,
+                carried: Vec::new(),
+            });
+        }
+    }
+
+    /// Returns true if `open` is an inline tag that a block closes and
+    /// reopens.
+    fn is_splittable(&self, open: &OpenTag<'a>) -> bool {
+        !open.tag.broken && self.tag_type(&open.tag.name) == Some(TagType::Inline)
+    }
+
+    /// Accounts for reopening the inline tags open at a block (once after
+    /// it, and once inside it too with `inside`), returning false if that
+    /// exceeds the limits.
+    fn reserve_reopened(&mut self, inside: bool) -> bool {
+        let copies = if inside { 2 } else { 1 };
+        let mut count = 0;
+        let mut markup = 0;
+        for open in self.stack.iter().rev() {
+            if !self.is_splittable(open) {
+                break;
+            }
+            let option = open.tag.raw_option.as_ref().map_or(0, |o| o.len());
+            // [name=option] and [/name]
+            markup += (2 * open.tag.name.len() + option + 6) * copies;
+            count += 1;
+        }
+        if count > MAX_REOPENED_TAGS || self.reopened_markup + markup > REOPENED_MARKUP_BUDGET {
+            return false;
+        }
+        self.reopened_markup += markup;
+        true
+    }
+
+    fn end_tag(&mut self, end: TagEnd<'a>) {
+        while self.stack.last().is_some_and(|top| top.synthetic) {
+            self.auto_close(end.end);
+        }
+        let Some(mut open) = self.stack.pop() else {
+            return;
+        };
+        open.tag.raw_close = end.raw_close;
+        open.tag.closed = end.closed;
+        open.tag.broken = end.broken;
+        open.tag.span.end = end.end;
+        let carried = core::mem::take(&mut open.carried);
+        self.push_tag(open);
+
+        for (mut tag, synthetic) in carried {
+            tag.span = end.end..end.end;
+            self.stack.push(OpenTag {
+                tag,
+                synthetic,
+                split: true,
+                carried: Vec::new(),
+            });
+        }
+    }
+
+    fn finish(mut self) -> Document<'a> {
+        while let Some(open) = self.stack.pop() {
+            self.push_tag(open);
+        }
+        core::mem::take(&mut self.doc)
+    }
+}
+
+/// Builds the error for a close tag of `tag` that skips over the still-open
+/// tags `above` it, naming the innermost one that isn't a list item.
+fn mismatched_close(tag: &str, above: &[String], span: Range<usize>) -> ParseError {
+    let open = above
+        .iter()
+        .rfind(|t| *t != "*")
+        .unwrap_or(&above[above.len() - 1]);
+    ParseError::MismatchedClosingTag {
+        tag: tag.to_string(),
+        open: open.clone(),
+        span,
+    }
+}
+
+/// Most inline tags a block tag closes and reopens; with more open, the
+/// block stays inside them.
+const MAX_REOPENED_TAGS: usize = 8;
+
+/// Most markup, in bytes, that reopening inline tags may add to a document.
+const REOPENED_MARKUP_BUDGET: usize = 64 * 1024;
+
+/// Returns an empty copy of `tag` to reopen it at byte offset `at`.
+fn reopened_tag<'a>(tag: &TagNode<'a>, at: usize) -> TagNode<'a> {
+    TagNode {
+        name: tag.name.clone(),
+        raw_name: tag.raw_name.clone(),
+        option: tag.option.clone(),
+        raw_option: tag.raw_option.clone(),
+        span: at..at,
+        ..TagNode::default()
+    }
+}
+
+impl<'a> ParseEventSink<'a> for TreeBuilder<'_, 'a> {
+    fn event(&mut self, event: ParseEvent<'a>) {
+        match event {
+            ParseEvent::StartTag(tag) => self.start_tag(tag),
+            ParseEvent::EndTag(end) => self.end_tag(end),
+            ParseEvent::Text(text) => self.push(Node::Text(text)),
+            ParseEvent::AutoUrl(url, kind) => self.push(Node::AutoUrl(url, kind)),
+            ParseEvent::LineBreak => self.push(Node::LineBreak),
         }
     }
+
+    fn error(&mut self, error: ParseError) {
+        self.errors.push(error);
+    }
 }
 
 /// The BBCode parser.
@@ -107,50 +451,224 @@ impl Parser {
 
     /// Parses BBCode input into a document AST.
     pub fn parse<'a>(&self, input: &'a str) -> Document<'a> {
-        let tokens = tokenize(input);
-        self.parse_tokens(&tokens, input, 0)
+        let mut doc = Document::new();
+        self.parse_into(input, &mut doc);
+        doc
+    }
+
+    /// Parses BBCode input into an existing document, replacing its nodes.
+    ///
+    /// The document's node storage is kept, so parsing many short posts into
+    /// one reused `Document` avoids reallocating it for every post. As with
+    /// [`parse`](Self::parse), text in the result borrows from `input`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bbcode::{Document, Parser};
+    ///
+    /// let parser = Parser::new();
+    /// let mut doc = Document::new();
+    /// for post in ["[b]one[/b]", "[i]two[/i]"] {
+    ///     parser.parse_into(post, &mut doc);
+    ///     assert_eq!(doc.len(), 1);
+    /// }
+    /// ```
+    pub fn parse_into<'a>(&self, input: &'a str, doc: &mut Document<'a>) {
+        doc.clear();
+        let mut builder = TreeBuilder::new(&self.registry, core::mem::take(doc));
+        self.parse_events(input, &mut builder);
+        *doc = builder.finish();
+    }
+
+    /// Parses a short inline snippet, such as a quote author or a title.
+    ///
+    /// Tags are parsed as usual, but line breaks are kept as literal text
+    /// instead of becoming [`Node::LineBreak`], and bare URLs are not
+    /// auto-linked. Unlike `[plain]`, inline tags like `[b]` still apply.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bbcode::{Node, Parser};
+    ///
+    /// let doc = Parser::new().parse_fragment("[b]a[/b]\nhttps://x.com");
+    /// assert!(!doc.iter().any(|n| matches!(n, Node::LineBreak | Node::AutoUrl(..))));
+    /// ```
+    pub fn parse_fragment<'a>(&self, input: &'a str) -> Document<'a> {
+        let mut builder = TreeBuilder::new(&self.registry, Document::new());
+        let input = self.truncate_input(input);
+        let tokens = tokenize_spanned(input);
+        self.parse_tokens(&tokens, input, 0, self.config.strict, true, &mut builder);
+        builder.finish()
+    }
+
+    /// Parses BBCode input, failing with every diagnostic found if the
+    /// markup is malformed.
+    ///
+    /// Unclosed tags, close tags without an open tag and close tags that
+    /// skip over a still-open tag are reported with their byte spans, for
+    /// warning users before their post is saved. Well-formed input yields
+    /// the same document as [`parse`](Self::parse).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bbcode::{ParseError, Parser};
+    ///
+    /// let errors = Parser::new().parse_strict("[b][i]x[/b]").unwrap_err();
+    /// assert_eq!(
+    ///     errors,
+    ///     vec![ParseError::MismatchedClosingTag {
+    ///         tag: "b".into(),
+    ///         open: "i".into(),
+    ///         span: 7..11,
+    ///     }]
+    /// );
+    /// ```
+    pub fn parse_strict<'a>(&self, input: &'a str) -> Result<Document<'a>, Vec<ParseError>> {
+        let mut builder = TreeBuilder::new(&self.registry, Document::new());
+        let input = self.truncate_input(input);
+        let tokens = tokenize_spanned(input);
+        self.parse_tokens(&tokens, input, 0, true, false, &mut builder);
+        let errors = core::mem::take(&mut builder.errors);
+        if errors.is_empty() {
+            Ok(builder.finish())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Parses BBCode input, reporting each node to `sink` as it is parsed.
+    ///
+    /// This is the streaming counterpart of [`parse`](Self::parse), which is
+    /// itself built on these events. Every [`ParseEvent::StartTag`] is matched
+    /// by exactly one [`ParseEvent::EndTag`]; the end event says whether the
+    /// tag was closed explicitly, left open at end of input, or turned out to
+    /// be broken (an unclosed tag inside a tag that was closed around it).
+    /// Events follow the source nesting; the tree built by `parse`
+    /// additionally moves block tags out of open inline tags.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bbcode::{ParseEvent, ParseEventSink, Parser};
+    ///
+    /// struct TagCounter(usize);
+    ///
+    /// impl<'a> ParseEventSink<'a> for TagCounter {
+    ///     fn event(&mut self, event: ParseEvent<'a>) {
+    ///         if let ParseEvent::StartTag(_) = event {
+    ///             self.0 += 1;
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut counter = TagCounter(0);
+    /// Parser::new().parse_events("[b]Hello[/b] [i]world[/i]", &mut counter);
+    /// assert_eq!(counter.0, 2);
+    /// ```
+    pub fn parse_events<'a>(&self, input: &'a str, sink: &mut dyn ParseEventSink<'a>) {
+        let input = self.truncate_input(input);
+        let tokens = tokenize_spanned(input);
+        self.parse_tokens(&tokens, input, 0, self.config.strict, false, sink);
     }
 
-    /// Parses tokens into a document, tracking depth.
+    /// Parses tokens into events, tracking depth. With `strict`, malformed
+    /// markup is also reported to [`ParseEventSink::error`]. With `fragment`,
+    /// line breaks are kept as text and URLs are not auto-linked.
     fn parse_tokens<'a>(
         &self,
-        tokens: &[Token<'a>],
+        tokens: &[SpannedToken<'a>],
         original_input: &'a str,
         depth: usize,
-    ) -> Document<'a> {
-        let mut doc = Document::new();
-        let mut stack: Vec<TagNode<'a>> = Vec::new();
+        strict: bool,
+        fragment: bool,
+        sink: &mut dyn ParseEventSink<'a>,
+    ) {
+        // Names of the currently open tags
+        let mut stack: Vec<String> = Vec::new();
+        // Spans of the open tags' opening tags, parallel to `stack`
+        let mut opened: Vec<Range<usize>> = Vec::new();
+        // Names of tags rejected for exceeding max_depth, so their matching
+        // close tags are also kept as text. Each is keyed by the stack depth
+        // and opening tag position of the tag enclosing it, so only a close
+        // tag inside that same tag matches it.
+        let mut too_deep: Vec<(String, usize, Option<usize>)> = Vec::new();
+        // How text inside each open tag is treated, parallel to `stack`
+        let mut modes: Vec<TextMode> = Vec::new();
+        // Last character of text emitted so far, for typographic quotes
+        let mut prev_char: Option<char> = None;
+        // Stack positions of list items opened by `newline_list_items`
+        let mut line_items: Vec<usize> = Vec::new();
         let mut i = 0;
 
         while i < tokens.len() {
-            let token = &tokens[i];
+            let SpannedToken { token, span } = &tokens[i];
+
+            if self.starts_line_item(token, &stack, depth) {
+                let start = span.start;
+                let mut item = TagNode::new("*");
+                item.span = start..start;
+                line_items.push(stack.len());
+                stack.push("*".to_string());
+                opened.push(start..start);
+                modes.push(TextMode::Normal);
+                sink.event(ParseEvent::StartTag(item));
+            }
 
             match token {
                 Token::Text(text) => {
-                    let node = Node::Text(Cow::Borrowed(*text));
-                    self.push_to_stack_or_doc(&mut stack, &mut doc, node);
+                    let offset = span.start;
+                    self.emit_text(text, offset, &modes, fragment, &mut prev_char, sink);
                 }
 
-                Token::LineBreak(_raw) => {
-                    let node = Node::LineBreak;
-                    self.push_to_stack_or_doc(&mut stack, &mut doc, node);
+                Token::LineBreak(raw) => {
+                    prev_char = Some('\n');
+                    if line_items
+                        .last()
+                        .is_some_and(|&item| item + 1 == stack.len())
+                    {
+                        // The line break ends the item instead of rendering
+                        line_items.pop();
+                        stack.pop();
+                        opened.pop();
+                        modes.pop();
+                        sink.event(ParseEvent::EndTag(TagEnd {
+                            raw_close: Cow::Borrowed(""),
+                            closed: true,
+                            broken: false,
+                            end: span.start,
+                        }));
+                    } else if fragment {
+                        sink.event(ParseEvent::Text(Cow::Borrowed(*raw)));
+                    } else if !(self.config.newline_list_items
+                        && stack.last().is_some_and(|t| is_list_tag(t)))
+                    {
+                        sink.event(ParseEvent::LineBreak);
+                    }
                 }
 
                 Token::Url(url) => {
-                    let node = Node::AutoUrl(Cow::Borrowed(*url));
-                    self.push_to_stack_or_doc(&mut stack, &mut doc, node);
+                    prev_char = url.chars().next_back();
+                    if self.auto_link_allowed(&modes, fragment) {
+                        sink.event(ParseEvent::AutoUrl(Cow::Borrowed(*url), AutoLinkKind::Url));
+                    } else {
+                        sink.event(ParseEvent::Text(Cow::Borrowed(*url)));
+                    }
                 }
 
                 Token::OpenTag { raw, name, arg } => {
                     let lower_name = name.to_ascii_lowercase();
 
                     // Look up the tag definition (static or custom)
-                    if let Some(resolved) = self.registry.resolve(&lower_name) {
+                    if let Some(resolved) = self.resolve_tag(&lower_name) {
                         // Check nesting depth
                         if depth + stack.len() >= self.config.max_depth {
                             // Too deep, treat as text
-                            let node = Node::Text(Cow::Borrowed(*raw));
-                            self.push_to_stack_or_doc(&mut stack, &mut doc, node);
+                            let enclosing = opened.last().map(|open| open.start);
+                            too_deep.push((lower_name, stack.len(), enclosing));
+                            sink.event(ParseEvent::Text(Cow::Borrowed(*raw)));
                             i += 1;
                             continue;
                         }
@@ -158,8 +676,7 @@ impl Parser {
                         // Check forbidden ancestors
                         if !self.check_ancestors_resolved(&stack, &resolved) {
                             // Invalid nesting, treat as text
-                            let node = Node::Text(Cow::Borrowed(*raw));
-                            self.push_to_stack_or_doc(&mut stack, &mut doc, node);
+                            sink.event(ParseEvent::Text(Cow::Borrowed(*raw)));
                             i += 1;
                             continue;
                         }
@@ -167,8 +684,7 @@ impl Parser {
                         // Check required parents
                         if !self.check_required_parents_resolved(&stack, &resolved) {
                             // Missing required parent, treat as text
-                            let node = Node::Text(Cow::Borrowed(*raw));
-                            self.push_to_stack_or_doc(&mut stack, &mut doc, node);
+                            sink.event(ParseEvent::Text(Cow::Borrowed(*raw)));
                             i += 1;
                             continue;
                         }
@@ -179,8 +695,7 @@ impl Parser {
                         // Check if option is required but missing
                         if resolved.option_required() && option.is_none() {
                             // Missing required option, treat as text
-                            let node = Node::Text(Cow::Borrowed(*raw));
-                            self.push_to_stack_or_doc(&mut stack, &mut doc, node);
+                            sink.event(ParseEvent::Text(Cow::Borrowed(*raw)));
                             i += 1;
                             continue;
                         }
@@ -191,147 +706,163 @@ impl Parser {
                             name: Cow::Owned(lower_name),
                             raw_name: Cow::Borrowed(*name),
                             option,
+                            raw_option: arg.and(raw_option_text(raw, name)).map(Cow::Borrowed),
                             children: Vec::new(),
                             closed: false,
                             raw_open: Cow::Borrowed(*raw),
                             raw_close: Cow::Borrowed(""),
                             broken: false,
+                            span: span.clone(),
                         };
 
                         // Handle self-closing tags
                         if resolved.is_self_closing() {
-                            // List items are special - they need content until next [*] or [/list]
+                            // List items stay open until the next [*] or [/list]
+                            // of the same list
                             if resolved.name() == "*" {
-                                // Collect content until next [*] or [/list]
-                                i += 1;
-                                while i < tokens.len() {
-                                    match &tokens[i] {
-                                        Token::OpenTag { name, .. }
-                                            if name.eq_ignore_ascii_case("*") =>
-                                        {
-                                            break;
-                                        }
-                                        Token::CloseTag { name, .. }
-                                            if name.eq_ignore_ascii_case("list") =>
-                                        {
-                                            break;
-                                        }
-                                        Token::Text(t) => {
-                                            tag_node.children.push(Node::Text(Cow::Borrowed(*t)));
-                                        }
-                                        Token::LineBreak(_) => {
-                                            tag_node.children.push(Node::LineBreak);
-                                        }
-                                        Token::Url(u) => {
-                                            tag_node
-                                                .children
-                                                .push(Node::AutoUrl(Cow::Borrowed(*u)));
-                                        }
-                                        _ => {
-                                            // Recursively parse nested content
-                                            let remaining = &tokens[i..];
-                                            if let Some((node, consumed)) = self.parse_single_tag(
-                                                remaining,
-                                                original_input,
-                                                depth + stack.len(),
-                                            ) {
-                                                tag_node.children.push(node);
-                                                i += consumed;
-                                                continue;
-                                            }
-                                        }
-                                    }
-                                    i += 1;
-                                }
-                                tag_node.mark_closed();
-                                let node = Node::Tag(tag_node);
-                                self.push_to_stack_or_doc(&mut stack, &mut doc, node);
-                                continue;
+                                let span = span.clone();
+                                self.close_list_item(
+                                    &mut stack,
+                                    &mut opened,
+                                    &mut modes,
+                                    span.start,
+                                    strict,
+                                    sink,
+                                );
+                                line_items.retain(|&item| item < stack.len());
+                                // Aliases such as [li] are tracked as "*"
+                                stack.push(resolved.name().to_string());
+                                opened.push(span);
+                                modes.push(TextMode::Normal);
+                                sink.event(ParseEvent::StartTag(tag_node));
                             } else {
                                 // Regular self-closing tag
                                 tag_node.mark_closed();
-                                let node = Node::Tag(tag_node);
-                                self.push_to_stack_or_doc(&mut stack, &mut doc, node);
+                                self.emit_node(Node::Tag(tag_node), sink);
                             }
                         }
                         // Handle verbatim tags (content not parsed)
-                        else if resolved.is_verbatim() {
+                        else if self.is_verbatim(&resolved) {
                             // Find the closing tag in the remaining input
-                            let remaining_start = self.find_token_end(original_input, *raw);
-                            if let Some(start_pos) = remaining_start {
-                                let remaining = &original_input[start_pos..];
-                                let (content, close_tag, _rest) =
-                                    tokenize_until_close(remaining, &tag_name_for_close);
-
-                                if !close_tag.is_empty() {
-                                    tag_node.children.push(Node::Text(Cow::Borrowed(content)));
-                                    tag_node.raw_close = Cow::Borrowed(close_tag);
-                                    tag_node.mark_closed();
-
-                                    // Skip tokens until after the close tag
-                                    let close_end = start_pos + content.len() + close_tag.len();
-                                    i = self.skip_tokens_until_pos(
-                                        &tokens,
-                                        i + 1,
-                                        original_input,
-                                        close_end,
-                                    );
-
-                                    let node = Node::Tag(tag_node);
-                                    self.push_to_stack_or_doc(&mut stack, &mut doc, node);
-                                    continue;
-                                }
+                            let remaining = &original_input[span.end..];
+                            let (content, close_tag, _rest) =
+                                tokenize_until_close(remaining, &tag_name_for_close);
+
+                            if !close_tag.is_empty() {
+                                tag_node.children.push(Node::Text(Cow::Borrowed(content)));
+                                tag_node.raw_close = Cow::Borrowed(close_tag);
+                                tag_node.mark_closed();
+
+                                // Skip tokens until after the close tag
+                                let close_end = span.end + content.len() + close_tag.len();
+                                tag_node.span.end = close_end;
+                                i += 1 + tokens[i + 1..]
+                                    .iter()
+                                    .take_while(|t| t.span.start < close_end)
+                                    .count();
+
+                                self.emit_node(Node::Tag(tag_node), sink);
+                                continue;
                             }
 
-                            // No close tag found, push to stack like normal
-                            stack.push(tag_node);
+                            // No close tag found, open it like a normal tag
+                            stack.push(tag_name_for_close);
+                            opened.push(span.clone());
+                            modes.push(text_mode(&resolved, &tag_node.option));
+                            sink.event(ParseEvent::StartTag(tag_node));
                         }
                         // Regular tag with content
                         else {
-                            stack.push(tag_node);
+                            stack.push(tag_name_for_close);
+                            opened.push(span.clone());
+                            modes.push(text_mode(&resolved, &tag_node.option));
+                            sink.event(ParseEvent::StartTag(tag_node));
                         }
-                    } else if self.config.allow_unknown_tags {
-                        // Unknown tag, treat as text
-                        let node = Node::Text(Cow::Borrowed(*raw));
-                        self.push_to_stack_or_doc(&mut stack, &mut doc, node);
                     } else {
                         // Unknown tag, treat as text
-                        let node = Node::Text(Cow::Borrowed(*raw));
-                        self.push_to_stack_or_doc(&mut stack, &mut doc, node);
+                        sink.event(ParseEvent::Text(Cow::Borrowed(*raw)));
                     }
                 }
 
                 Token::CloseTag { raw, name } => {
                     let lower_name = name.to_ascii_lowercase();
 
-                    // Find matching open tag in stack
-                    if let Some(pos) = self.find_matching_open_tag(&stack, &lower_name) {
-                        // Close all tags from pos to end
-                        let mut closed = stack.split_off(pos);
-
-                        if let Some(mut tag_node) = closed.pop() {
-                            // The matching tag
-                            tag_node.raw_close = Cow::Borrowed(*raw);
-                            tag_node.mark_closed();
-
-                            // Add any intervening unclosed tags as children (auto-close)
-                            for mut unclosed in closed.into_iter().rev() {
-                                // Move children to parent
-                                let children = std::mem::take(&mut unclosed.children);
-                                for child in children {
-                                    tag_node.children.push(child);
-                                }
-                                // The unclosed tag itself becomes broken/text
-                                unclosed.mark_broken();
-                            }
+                    // The close tag of a too-deep tag stays text as well
+                    let enclosing = opened.last().map(|open| open.start);
+                    if let Some(pos) = too_deep.iter().rposition(|(n, len, start)| {
+                        *n == lower_name && *len == stack.len() && *start == enclosing
+                    }) {
+                        too_deep.remove(pos);
+                        sink.event(ParseEvent::Text(Cow::Borrowed(*raw)));
+                        i += 1;
+                        continue;
+                    }
+
+                    // Find matching open tag in stack. Without auto_close, only
+                    // list items may be left open above it.
+                    let found = self.find_matching_open_tag(&stack, &lower_name);
+                    let matched = found.filter(|&pos| {
+                        self.config.auto_close || stack[pos + 1..].iter().all(|t| t == "*")
+                    });
+                    if let Some(pos) = matched {
+                        let span = span.clone();
+                        if is_list_tag(&lower_name) {
+                            self.close_list_item(
+                                &mut stack,
+                                &mut opened,
+                                &mut modes,
+                                span.start,
+                                strict,
+                                sink,
+                            );
+                        }
+
+                        if strict && stack.len() > pos + 1 {
+                            sink.error(mismatched_close(
+                                &lower_name,
+                                &stack[pos + 1..],
+                                span.clone(),
+                            ));
+                        }
 
-                            let node = Node::Tag(tag_node);
-                            self.push_to_stack_or_doc(&mut stack, &mut doc, node);
+                        // Any tags opened after the match were never closed
+                        // and become broken (rendered as text)
+                        while stack.len() > pos + 1 {
+                            stack.pop();
+                            opened.pop();
+                            modes.pop();
+                            sink.event(ParseEvent::EndTag(TagEnd {
+                                raw_close: Cow::Borrowed(""),
+                                closed: false,
+                                broken: true,
+                                end: span.start,
+                            }));
                         }
+
+                        stack.pop();
+                        opened.pop();
+                        modes.pop();
+                        line_items.retain(|&item| item < stack.len());
+                        too_deep.retain(|&(_, len, _)| len <= stack.len());
+                        sink.event(ParseEvent::EndTag(TagEnd {
+                            raw_close: Cow::Borrowed(*raw),
+                            closed: true,
+                            broken: false,
+                            end: span.end,
+                        }));
                     } else {
                         // No matching open tag, treat close tag as text
-                        let node = Node::Text(Cow::Borrowed(*raw));
-                        self.push_to_stack_or_doc(&mut stack, &mut doc, node);
+                        if strict {
+                            let span = span.clone();
+                            sink.error(match found {
+                                Some(pos) => mismatched_close(&lower_name, &stack[pos + 1..], span),
+                                None => ParseError::UnmatchedClosingTag {
+                                    tag: lower_name,
+                                    span,
+                                },
+                            });
+                        }
+                        sink.event(ParseEvent::Text(Cow::Borrowed(*raw)));
                     }
                 }
             }
@@ -339,108 +870,280 @@ impl Parser {
             i += 1;
         }
 
-        // Close any remaining open tags
-        while let Some(tag_node) = stack.pop() {
-            // Unclosed tag - mark as closed but missing close tag
-            let node = Node::Tag(tag_node);
-            if let Some(parent) = stack.last_mut() {
-                parent.children.push(node);
-            } else {
-                doc.push(node);
+        // Close any remaining open tags (unclosed, but still rendered)
+        if strict {
+            for (name, span) in stack.iter().zip(&opened) {
+                if name != "*" {
+                    sink.error(ParseError::UnclosedTag {
+                        tag: name.clone(),
+                        span: span.clone(),
+                    });
+                }
             }
         }
+        while let Some(name) = stack.pop() {
+            // Without auto_close, only the outermost tag (and list items) is
+            // closed; tags open inside it stay text
+            let broken = !self.config.auto_close && !stack.is_empty() && name != "*";
+            sink.event(ParseEvent::EndTag(TagEnd {
+                raw_close: Cow::Borrowed(""),
+                closed: false,
+                broken,
+                end: original_input.len(),
+            }));
+        }
+    }
 
-        doc
+    /// Returns true if `token` should open an implicit list item under
+    /// [`ParserConfig::newline_list_items`].
+    fn starts_line_item(&self, token: &Token<'_>, stack: &[String], depth: usize) -> bool {
+        if !self.config.newline_list_items
+            || !stack.last().is_some_and(|t| is_list_tag(t))
+            || depth + stack.len() >= self.config.max_depth
+            || self.resolve_tag("*").is_none()
+        {
+            return false;
+        }
+        match token {
+            Token::Text(text) => !text.trim().is_empty(),
+            Token::Url(_) => true,
+            Token::OpenTag { name, .. } => {
+                let name = name.to_ascii_lowercase();
+                self.resolve_tag(&name).is_some_and(|tag| tag.name() != "*")
+            }
+            Token::LineBreak(_) | Token::CloseTag { .. } => false,
+        }
     }
 
-    /// Parses a single tag and returns the node and number of tokens consumed.
-    fn parse_single_tag<'a>(
+    /// Closes the open item of the innermost list, if any, together with any
+    /// tags still open inside it.
+    fn close_list_item<'a>(
         &self,
-        tokens: &[Token<'a>],
-        _original_input: &'a str,
-        _depth: usize,
-    ) -> Option<(Node<'a>, usize)> {
-        if tokens.is_empty() {
-            return None;
+        stack: &mut Vec<String>,
+        opened: &mut Vec<Range<usize>>,
+        modes: &mut Vec<TextMode>,
+        end: usize,
+        strict: bool,
+        sink: &mut dyn ParseEventSink<'a>,
+    ) {
+        let Some(item) = stack.iter().rposition(|t| t == "*") else {
+            return;
+        };
+        if stack[item..].iter().any(|t| is_list_tag(t)) {
+            // The item belongs to an outer list
+            return;
         }
 
-        match &tokens[0] {
-            Token::OpenTag { raw, name, arg } => {
-                let lower_name = name.to_ascii_lowercase();
-                let resolved = self.registry.resolve(&lower_name)?;
-
-                let option = self.parse_option_resolved(*arg, &resolved);
-
-                let mut tag_node = TagNode {
-                    name: Cow::Owned(lower_name.clone()),
-                    raw_name: Cow::Borrowed(*name),
-                    option,
-                    children: Vec::new(),
-                    closed: false,
-                    raw_open: Cow::Borrowed(*raw),
-                    raw_close: Cow::Borrowed(""),
-                    broken: false,
-                };
-
-                // Find matching close tag
-                let mut consumed = 1;
-                let mut nesting = 1;
-
-                while consumed < tokens.len() && nesting > 0 {
-                    match &tokens[consumed] {
-                        Token::OpenTag { name: n, .. } if n.eq_ignore_ascii_case(&lower_name) => {
-                            nesting += 1;
-                        }
-                        Token::CloseTag {
-                            name: n,
-                            raw: close_raw,
-                        } if n.eq_ignore_ascii_case(&lower_name) => {
-                            nesting -= 1;
-                            if nesting == 0 {
-                                tag_node.raw_close = Cow::Borrowed(*close_raw);
-                                tag_node.mark_closed();
-                                consumed += 1;
-                                break;
-                            }
-                        }
-                        Token::Text(t) => {
-                            tag_node.children.push(Node::Text(Cow::Borrowed(*t)));
-                        }
-                        Token::LineBreak(_) => {
-                            tag_node.children.push(Node::LineBreak);
-                        }
-                        Token::Url(u) => {
-                            tag_node.children.push(Node::AutoUrl(Cow::Borrowed(*u)));
-                        }
-                        _ => {}
-                    }
-                    consumed += 1;
+        if strict {
+            for (name, span) in stack[item + 1..].iter().zip(&opened[item + 1..]) {
+                sink.error(ParseError::UnclosedTag {
+                    tag: name.clone(),
+                    span: span.clone(),
+                });
+            }
+        }
+
+        while stack.len() > item {
+            stack.pop();
+            opened.pop();
+            modes.pop();
+            sink.event(ParseEvent::EndTag(TagEnd {
+                raw_close: Cow::Borrowed(""),
+                closed: stack.len() == item,
+                broken: false,
+                end,
+            }));
+        }
+    }
+
+    /// Reports an already-built node (and its subtree) as events.
+    fn emit_node<'a>(&self, node: Node<'a>, sink: &mut dyn ParseEventSink<'a>) {
+        enum Piece<'a> {
+            Node(Node<'a>),
+            End(TagEnd<'a>),
+        }
+
+        // Walk with an explicit stack so deep subtrees can't overflow
+        let mut stack = vec![Piece::Node(node)];
+        while let Some(piece) = stack.pop() {
+            let node = match piece {
+                Piece::Node(node) => node,
+                Piece::End(end) => {
+                    sink.event(ParseEvent::EndTag(end));
+                    continue;
+                }
+            };
+            match node {
+                Node::Text(text) => sink.event(ParseEvent::Text(text)),
+                Node::LineBreak => sink.event(ParseEvent::LineBreak),
+                Node::AutoUrl(url, kind) => sink.event(ParseEvent::AutoUrl(url, kind)),
+                Node::Tag(mut tag) => {
+                    let children = core::mem::take(&mut tag.children);
+                    stack.push(Piece::End(TagEnd {
+                        raw_close: core::mem::take(&mut tag.raw_close),
+                        closed: tag.closed,
+                        broken: tag.broken,
+                        end: tag.span.end,
+                    }));
+                    sink.event(ParseEvent::StartTag(tag));
+                    stack.extend(children.into_iter().rev().map(Piece::Node));
                 }
+            }
+        }
+    }
+
+    /// Emits text, splitting out bare `www.` hosts and email addresses as
+    /// auto-links and `@name` mentions as `[user]` tags when enabled.
+    /// `offset` is the position of `text` in the input.
+    fn emit_text<'a>(
+        &self,
+        mut text: &'a str,
+        mut offset: usize,
+        modes: &[TextMode],
+        fragment: bool,
+        prev_char: &mut Option<char>,
+        sink: &mut dyn ParseEventSink<'a>,
+    ) {
+        let link = self.auto_link_allowed(modes, fragment);
+        let typographic = self.config.typographic && !modes.contains(&TextMode::Url);
+        let www = link && self.config.auto_link_www;
+        let emails = link && self.config.auto_link_emails;
+        let mentions = self.config.parse_mentions
+            && modes.iter().all(|&mode| mode == TextMode::Normal)
+            && self.resolve_tag("user").is_some();
+
+        // Mentions are found with no link kind
+        let find = |text: &str| {
+            [
+                www.then(|| find_www_link(text).map(|r| (r, Some(AutoLinkKind::Www))))
+                    .flatten(),
+                emails
+                    .then(|| find_email(text).map(|r| (r, Some(AutoLinkKind::Email))))
+                    .flatten(),
+                mentions
+                    .then(|| find_mention(text).map(|r| (r, None)))
+                    .flatten(),
+            ]
+            .into_iter()
+            .flatten()
+            .min_by_key(|(range, _)| range.start)
+        };
 
-                Some((Node::Tag(tag_node), consumed))
+        while let Some((range, kind)) = find(text) {
+            if range.start > 0 {
+                let node = self.text_node(&text[..range.start], typographic, prev_char);
+                self.emit_node(node, sink);
+            }
+            let found = &text[range.clone()];
+            *prev_char = found.chars().next_back();
+            if let Some(kind) = kind {
+                sink.event(ParseEvent::AutoUrl(Cow::Borrowed(found), kind));
+            } else {
+                let span = offset + range.start..offset + range.end;
+                self.emit_mention(&found[..1], &found[1..], span, sink);
             }
-            _ => None,
+            text = &text[range.end..];
+            offset += range.end;
+        }
+
+        if !text.is_empty() {
+            let node = self.text_node(text, typographic, prev_char);
+            self.emit_node(node, sink);
         }
     }
 
-    /// Pushes a node to the current context (stack top or document root).
-    fn push_to_stack_or_doc<'a>(
+    /// Emits an `@name` mention as a closed `[user]` tag.
+    fn emit_mention<'a>(
         &self,
-        stack: &mut Vec<TagNode<'a>>,
-        doc: &mut Document<'a>,
-        node: Node<'a>,
+        at: &'a str,
+        name: &'a str,
+        span: Range<usize>,
+        sink: &mut dyn ParseEventSink<'a>,
     ) {
-        if let Some(parent) = stack.last_mut() {
-            parent.children.push(node);
+        let end = span.end;
+        let tag = TagNode {
+            closed: true,
+            raw_open: Cow::Borrowed(at),
+            span,
+            ..TagNode::with_name(Cow::Borrowed("user"), Cow::Borrowed("user"))
+        };
+        sink.event(ParseEvent::StartTag(tag));
+        sink.event(ParseEvent::Text(Cow::Borrowed(name)));
+        sink.event(ParseEvent::EndTag(TagEnd {
+            raw_close: Cow::Borrowed(""),
+            closed: true,
+            broken: false,
+            end,
+        }));
+    }
+
+    /// Creates a text node, applying typographic replacements if
+    /// `typographic`. `prev_char` is the character before `text` and is
+    /// updated to its last character.
+    fn text_node<'a>(
+        &self,
+        text: &'a str,
+        typographic: bool,
+        prev_char: &mut Option<char>,
+    ) -> Node<'a> {
+        if typographic {
+            Node::Text(smarten(text, prev_char))
         } else {
-            doc.push(node);
+            *prev_char = text.chars().next_back().or(*prev_char);
+            Node::Text(Cow::Borrowed(text))
         }
     }
 
+    /// Cuts `input` down to `max_input_len` bytes, backing off to a
+    /// character boundary.
+    fn truncate_input<'a>(&self, input: &'a str) -> &'a str {
+        match self.config.max_input_len {
+            Some(max) if input.len() > max => {
+                let mut end = max;
+                while !input.is_char_boundary(end) {
+                    end -= 1;
+                }
+                &input[..end]
+            }
+            _ => input,
+        }
+    }
+
+    /// Returns true if bare URLs should be linked given the open tags.
+    fn auto_link_allowed(&self, modes: &[TextMode], fragment: bool) -> bool {
+        self.config.auto_link && !fragment && modes.iter().all(|&mode| mode == TextMode::Normal)
+    }
+
+    /// Resolves a tag name, honoring the allowed and disabled tag sets.
+    fn resolve_tag(&self, name: &str) -> Option<ResolvedTag> {
+        let resolved = self.registry.resolve(name)?;
+        let matches = |set: &Set<String>| {
+            set.contains(name) || set.contains(&resolved.name().to_ascii_lowercase())
+        };
+
+        if matches(&self.config.disabled_tags) {
+            return None;
+        }
+        if let Some(allowed) = &self.config.allowed_tags {
+            if !matches(allowed) {
+                return None;
+            }
+        }
+        Some(resolved)
+    }
+
+    /// Returns true if the tag's content should be captured verbatim.
+    fn is_verbatim(&self, resolved: &ResolvedTag) -> bool {
+        resolved.is_verbatim() && (self.config.icode_verbatim || resolved.name() != "icode")
+    }
+
     /// Checks if the tag is allowed based on forbidden ancestors (for resolved tags).
-    fn check_ancestors_resolved(&self, stack: &[TagNode], resolved: &ResolvedTag) -> bool {
+    fn check_ancestors_resolved(&self, stack: &[String], resolved: &ResolvedTag) -> bool {
+        if !resolved.has_forbidden_ancestors() {
+            return true;
+        }
         for ancestor in stack {
-            if resolved.is_ancestor_forbidden(&ancestor.name) {
+            if resolved.is_ancestor_forbidden(ancestor) {
                 return false;
             }
         }
@@ -448,28 +1151,33 @@ impl Parser {
     }
 
     /// Checks if required parent tags are present (for resolved tags).
-    fn check_required_parents_resolved(&self, stack: &[TagNode], resolved: &ResolvedTag) -> bool {
-        let names: Vec<String> = stack.iter().map(|t| t.name.to_string()).collect();
-        resolved.has_required_parent(&names)
+    fn check_required_parents_resolved(&self, stack: &[String], resolved: &ResolvedTag) -> bool {
+        resolved.has_required_parent(stack)
     }
 
     /// Finds the position of a matching open tag in the stack.
-    fn find_matching_open_tag(&self, stack: &[TagNode], name: &str) -> Option<usize> {
-        stack
-            .iter()
-            .rposition(|t| t.name.eq_ignore_ascii_case(name))
+    fn find_matching_open_tag(&self, stack: &[String], name: &str) -> Option<usize> {
+        stack.iter().rposition(|t| t.eq_ignore_ascii_case(name))
     }
 
     /// Parses a tag option string into a TagOption (for resolved tags).
     fn parse_option_resolved<'a>(
         &self,
         arg: Option<&'a str>,
-        _resolved: &ResolvedTag,
+        resolved: &ResolvedTag,
     ) -> TagOption<'a> {
         match arg {
-            None => TagOption::None,
-            Some(s) if s.is_empty() => TagOption::None,
+            None | Some("") => TagOption::None,
             Some(s) => {
+                // Tolerate paste artifacts like [color=red, blue] by keeping only
+                // the first value. Functional colors such as rgb(1, 2, 3) are
+                // left intact.
+                if matches!(resolved.name(), "color" | "size") && !s.contains('(') {
+                    if let Some((first, _)) = s.split_once(',') {
+                        return TagOption::Scalar(Cow::Borrowed(first.trim()));
+                    }
+                }
+
                 // Try to parse as key-value pairs if it looks like key=value format.
                 // Key-value format starts with an identifier (alphabetic) followed by =
                 // This distinguishes [attach width=100] from [url=http://example.com?foo=bar]
@@ -503,8 +1211,8 @@ impl Parser {
     fn parse_keyed_options<'a>(
         &self,
         input: &'a str,
-    ) -> Option<HashMap<Cow<'a, str>, Cow<'a, str>>> {
-        let mut map = HashMap::new();
+    ) -> Option<BTreeMap<Cow<'a, str>, Cow<'a, str>>> {
+        let mut map = BTreeMap::new();
         let mut remaining = input.trim();
 
         while !remaining.is_empty() {
@@ -514,15 +1222,13 @@ impl Parser {
             remaining = remaining[eq_pos + 1..].trim_start();
 
             // Find value
-            let (value, rest) = if remaining.starts_with('"') {
+            let (value, rest) = if let Some(quoted) = remaining.strip_prefix('"') {
                 // Quoted value
-                let end = remaining[1..].find('"')?;
-                let val = &remaining[1..=end];
-                (val, remaining[end + 2..].trim_start())
-            } else if remaining.starts_with('\'') {
-                let end = remaining[1..].find('\'')?;
-                let val = &remaining[1..=end];
-                (val, remaining[end + 2..].trim_start())
+                let end = quoted.find('"')?;
+                (&quoted[..end], quoted[end + 1..].trim_start())
+            } else if let Some(quoted) = remaining.strip_prefix('\'') {
+                let end = quoted.find('\'')?;
+                (&quoted[..end], quoted[end + 1..].trim_start())
             } else {
                 // Unquoted value - until space
                 let end = remaining.find(' ').unwrap_or(remaining.len());
@@ -539,41 +1245,184 @@ impl Parser {
             Some(map)
         }
     }
+}
 
-    /// Finds the end position of a token's raw text in the original input.
-    fn find_token_end(&self, input: &str, raw: &str) -> Option<usize> {
-        let raw_start = raw.as_ptr() as usize;
-        let input_start = input.as_ptr() as usize;
-        if raw_start >= input_start {
-            let offset = raw_start - input_start;
-            Some(offset + raw.len())
-        } else {
-            None
+/// Finds the first bare `www.` host (with optional path) in `text`.
+///
+/// Trailing punctuation is left out of the link.
+fn find_www_link(text: &str) -> Option<Range<usize>> {
+    let bytes = text.as_bytes();
+
+    for pos in 0..bytes.len().saturating_sub(3) {
+        if !bytes[pos..pos + 4].eq_ignore_ascii_case(b"www.") {
+            continue;
+        }
+        // Must start a word, not continue a host, path or email
+        if pos > 0 && (bytes[pos - 1].is_ascii_alphanumeric() || b".-@/:".contains(&bytes[pos - 1]))
+        {
+            continue;
+        }
+
+        let rest = &text[pos..];
+        let len = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '[' | ']' | '<' | '>' | '"' | '\''))
+            .unwrap_or(rest.len());
+        let link = rest[..len].trim_end_matches(['.', ',', ')', '!', '?', ':', ';']);
+
+        let host_end = link.find(['/', '?', '#', ':']).unwrap_or(link.len());
+        let host = &link[4..host_end];
+        if host.contains('.')
+            && !host.starts_with('.')
+            && !host.ends_with('.')
+            && host
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'-')
+        {
+            return Some(pos..pos + link.len());
         }
     }
+    None
+}
 
-    /// Skips tokens until we reach a position past the given offset.
-    fn skip_tokens_until_pos(
-        &self,
-        tokens: &[Token],
-        start: usize,
-        input: &str,
-        target_pos: usize,
-    ) -> usize {
-        let input_start = input.as_ptr() as usize;
-        let mut i = start;
+/// Finds the first bare email address in `text`.
+fn find_email(text: &str) -> Option<Range<usize>> {
+    let bytes = text.as_bytes();
+    let is_local = |b: u8| b.is_ascii_alphanumeric() || b"._%+-".contains(&b);
+    let is_domain = |b: u8| b.is_ascii_alphanumeric() || b == b'.' || b == b'-';
 
-        while i < tokens.len() {
-            let raw = tokens[i].as_raw();
-            let raw_start = raw.as_ptr() as usize - input_start;
-            if raw_start >= target_pos {
-                return i;
-            }
-            i += 1;
+    for (at, _) in text.match_indices('@') {
+        let mut start = at;
+        while start > 0 && is_local(bytes[start - 1]) {
+            start -= 1;
+        }
+        // A local part can't start or end with a dot
+        while start < at && bytes[start] == b'.' {
+            start += 1;
+        }
+        if start == at || bytes[at - 1] == b'.' {
+            continue;
+        }
+
+        let mut end = at + 1;
+        while end < bytes.len() && is_domain(bytes[end]) {
+            end += 1;
+        }
+        let domain = text[at + 1..end].trim_end_matches(['.', '-']);
+        let tld = domain.rsplit('.').next().unwrap_or("");
+        if domain.contains('.')
+            && !domain.starts_with(['.', '-'])
+            && !domain.contains("..")
+            && tld.len() >= 2
+            && tld.bytes().all(|b| b.is_ascii_alphabetic())
+        {
+            return Some(start..at + 1 + domain.len());
+        }
+    }
+    None
+}
+
+/// How text inside an open tag is treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextMode {
+    /// Ordinary content.
+    Normal,
+    /// Content that is not auto-linked, e.g. inside `[code]`.
+    NoLink,
+    /// A URL or address given as content (`[img]`, or `[url]`/`[email]`
+    /// without an option); not auto-linked or made typographic either.
+    Url,
+}
+
+/// Returns how text inside the tag `resolved` with `option` is treated.
+fn text_mode(resolved: &ResolvedTag, option: &TagOption<'_>) -> TextMode {
+    match resolved.name() {
+        "img" => TextMode::Url,
+        "url" | "email" if option.is_none() => TextMode::Url,
+        _ if resolved.stop_auto_link() => TextMode::NoLink,
+        _ => TextMode::Normal,
+    }
+}
+
+/// Returns true if `name` is a list container (`[list]`, `[ol]` or `[ul]`).
+fn is_list_tag(name: &str) -> bool {
+    matches!(name, "list" | "ol" | "ul")
+}
+
+/// Returns the option text of the open tag `raw` named `name`, as written.
+fn raw_option_text<'a>(raw: &'a str, name: &str) -> Option<&'a str> {
+    let rest = raw.get(1 + name.len()..)?;
+    let rest = rest.strip_suffix(']').unwrap_or(rest);
+    rest.strip_prefix('=')
+        .or_else(|| rest.strip_prefix(' '))
+        .filter(|option| !option.is_empty())
+}
+
+/// Finds the first `@name` mention in `text`.
+///
+/// The `@` must not follow a word character (so email addresses don't
+/// match), and the name is 1 to 50 letters, digits, `_`, `.` or `-`, not
+/// ending in `.` or `-`.
+fn find_mention(text: &str) -> Option<Range<usize>> {
+    let bytes = text.as_bytes();
+    let is_name = |b: u8| b.is_ascii_alphanumeric() || b"_.-".contains(&b);
+
+    for (at, _) in text.match_indices('@') {
+        if at > 0 && (is_name(bytes[at - 1]) || bytes[at - 1] == b'@') {
+            continue;
+        }
+        let len = bytes[at + 1..].iter().take_while(|&&b| is_name(b)).count();
+        let name = text[at + 1..at + 1 + len].trim_end_matches(['.', '-']);
+        if !name.is_empty() && name.len() <= 50 && !name.starts_with(['.', '-']) {
+            return Some(at..at + 1 + name.len());
+        }
+    }
+    None
+}
+
+/// Applies conservative SmartyPants-style replacements to a text fragment.
+///
+/// Straight quotes become curly quotes (opening after whitespace, opening
+/// punctuation or at the start of the text, closing otherwise), `--`
+/// becomes an em-dash and `...` becomes an ellipsis. `prev` is the character
+/// before the fragment, carried across tags, and is updated to the last
+/// character of the fragment.
+fn smarten<'t>(text: &'t str, prev: &mut Option<char>) -> Cow<'t, str> {
+    if !text.contains(['"', '\'']) && !text.contains("--") && !text.contains("...") {
+        *prev = text.chars().next_back().or(*prev);
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::with_capacity(text.len() + 8);
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        if let Some(r) = rest.strip_prefix("...") {
+            out.push('\u{2026}');
+            *prev = Some('\u{2026}');
+            rest = r;
+            continue;
+        }
+        if let Some(r) = rest.strip_prefix("--") {
+            out.push('\u{2014}');
+            *prev = Some('\u{2014}');
+            rest = r;
+            continue;
         }
 
-        tokens.len()
+        let opening = prev.is_none_or(|p| p.is_whitespace() || "([{\u{2014}\u{2013}".contains(p));
+        let replaced = match c {
+            '"' if opening => '\u{201C}',
+            '"' => '\u{201D}',
+            '\'' if opening => '\u{2018}',
+            '\'' => '\u{2019}',
+            other => other,
+        };
+        out.push(replaced);
+        *prev = Some(c);
+        rest = &rest[c.len_utf8()..];
     }
+
+    Cow::Owned(out)
 }
 
 impl Default for Parser {
@@ -722,7 +1571,58 @@ mod tests {
         assert_eq!(items.len(), 2);
     }
 
-    // ==================== Verbatim Tag Tests ====================
+    #[test]
+    fn parse_list_items_alias() {
+        let doc = Parser::new().parse("[list][li]a[li]b[/list]");
+        let list = doc.nodes[0].as_tag().unwrap();
+        let items: Vec<_> = list
+            .children
+            .iter()
+            .filter_map(|n| n.as_tag())
+            .map(|t| (&*t.name, t.inner_text().into_owned()))
+            .collect();
+        assert_eq!(items, [("li", "a".to_string()), ("li", "b".to_string())]);
+
+        // [li] ends an implicit newline item like [*] does
+        let parser = Parser::with_config(ParserConfig {
+            newline_list_items: true,
+            ..Default::default()
+        });
+        let doc = parser.parse("[list]\nApple\n[li]Banana[/list]");
+        let list = doc.nodes[0].as_tag().unwrap();
+        assert_eq!(list.children.iter().filter(|n| n.is_tag()).count(), 2);
+    }
+
+    #[test]
+    fn parse_newline_list_items() {
+        let parser = Parser::with_config(ParserConfig {
+            newline_list_items: true,
+            ..Default::default()
+        });
+        let items = |input: &str| -> Vec<String> {
+            let doc = parser.parse(input);
+            let list = doc.nodes[0].as_tag().unwrap();
+            list.children
+                .iter()
+                .filter_map(|n| n.as_tag())
+                .filter(|t| t.name == "*")
+                .map(|t| t.inner_text().into_owned())
+                .collect()
+        };
+
+        assert_eq!(items("[list]\nApple\nBanana\n[/list]"), ["Apple", "Banana"]);
+        assert_eq!(
+            items("[list]\nApple\n\n[*]Cherry\nstill cherry[*]Date[/list]"),
+            ["Apple", "Cherry\nstill cherry", "Date"]
+        );
+
+        // Off by default
+        let doc = Parser::new().parse("[list]\nApple\nBanana\n[/list]");
+        let list = doc.nodes[0].as_tag().unwrap();
+        assert!(list.children.iter().all(|n| !n.is_tag()));
+    }
+
+    // ==================== Verbatim Tag Tests ====================
 
     #[test]
     fn parse_code_verbatim() {
@@ -751,6 +1651,30 @@ mod tests {
         assert_eq!(&*content, "[i]Not italic[/i]");
     }
 
+    #[test]
+    fn parse_verbatim_followed_by_tag() {
+        let parser = Parser::new();
+        for (input, verbatim, close, next) in [
+            ("[code]x[/code][b]y[/b]", "code", "[/code]", "b"),
+            ("[icode]x[/icode][i]y[/i]", "icode", "[/icode]", "i"),
+            ("[icode]a[/icode][b]b[/b]", "icode", "[/icode]", "b"),
+            ("[CODE]x[/Code][b]y[/b]", "code", "[/Code]", "b"),
+        ] {
+            let doc = parser.parse(input);
+            assert_eq!(doc.nodes.len(), 2, "{input}");
+
+            let first = doc.nodes[0].as_tag().unwrap();
+            assert_eq!(&*first.name, verbatim);
+            assert_eq!(first.raw_close, close);
+            assert_eq!(first.inner_text().len(), 1);
+
+            let second = doc.nodes[1].as_tag().unwrap();
+            assert_eq!(&*second.name, next);
+            assert!(second.closed);
+            assert_eq!(second.inner_text().len(), 1);
+        }
+    }
+
     // ==================== Case Insensitivity Tests ====================
 
     #[test]
@@ -802,6 +1726,119 @@ mod tests {
         assert!(!doc.is_empty());
     }
 
+    // ==================== Auto Close Tests ====================
+
+    #[test]
+    fn parse_without_auto_close() {
+        let parser = Parser::with_config(ParserConfig {
+            auto_close: false,
+            ..Default::default()
+        });
+        let renderer = crate::Renderer::new();
+        let render = |input| renderer.render(&parser.parse(input));
+
+        assert_eq!(
+            renderer.render(&Parser::new().parse("[b][i]x[/b]")),
+            "<strong>[i]x</strong>"
+        );
+        assert_eq!(render("[b][i]x[/b]"), "<strong>[i]x[/b]</strong>");
+        assert_eq!(render("[b]x[/i]y[/b]"), "<strong>x[/i]y</strong>");
+        assert_eq!(render("[b][i]x[/i][/b]"), "<strong><em>x</em></strong>");
+        assert_eq!(
+            render("[list][*]a[*]b[/list]"),
+            "<ul class=\"bbcode-list\"><li>a</li><li>b</li></ul>"
+        );
+
+        let errors = parser.parse_strict("[b][i]x[/b]").unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                ParseError::MismatchedClosingTag {
+                    tag: "b".into(),
+                    open: "i".into(),
+                    span: 7..11,
+                },
+                ParseError::UnclosedTag {
+                    tag: "b".into(),
+                    span: 0..3,
+                },
+                ParseError::UnclosedTag {
+                    tag: "i".into(),
+                    span: 3..6,
+                },
+            ]
+        );
+    }
+
+    // ==================== Strict Mode Tests ====================
+
+    #[test]
+    fn parse_strict_mismatched_close() {
+        let errors = Parser::new().parse_strict("[b][i]x[/b]").unwrap_err();
+        assert_eq!(
+            errors,
+            vec![ParseError::MismatchedClosingTag {
+                tag: "b".to_string(),
+                open: "i".to_string(),
+                span: 7..11,
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_strict_unclosed_and_unmatched() {
+        let errors = Parser::new()
+            .parse_strict("x[/u] [list][*][b]a[*]b[/list] [quote]q")
+            .unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                ParseError::UnmatchedClosingTag {
+                    tag: "u".to_string(),
+                    span: 1..5,
+                },
+                ParseError::UnclosedTag {
+                    tag: "b".to_string(),
+                    span: 15..18,
+                },
+                ParseError::UnclosedTag {
+                    tag: "quote".to_string(),
+                    span: 31..38,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_strict_well_formed() {
+        let parser = Parser::new();
+        let input = "[list][*]a[*][b]b[/b][/list][code][/i][/code]";
+        let doc = parser.parse_strict(input).unwrap();
+        assert_eq!(doc, parser.parse(input));
+    }
+
+    #[test]
+    fn parse_events_strict_reports_errors() {
+        struct Errors(Vec<ParseError>);
+        impl<'a> ParseEventSink<'a> for Errors {
+            fn event(&mut self, _event: ParseEvent<'a>) {}
+            fn error(&mut self, error: ParseError) {
+                self.0.push(error);
+            }
+        }
+
+        let mut sink = Errors(Vec::new());
+        Parser::new().parse_events("[b]x", &mut sink);
+        assert!(sink.0.is_empty());
+
+        let parser = Parser::with_config(ParserConfig {
+            strict: true,
+            ..Default::default()
+        });
+        parser.parse_events("[b]x", &mut sink);
+        assert_eq!(sink.0.len(), 1);
+    }
+
     // ==================== Invalid Tag Tests ====================
 
     #[test]
@@ -834,6 +1871,111 @@ mod tests {
         assert_eq!(&*outer.name, "url");
     }
 
+    // ==================== Block In Inline Tests ====================
+
+    #[test]
+    fn parse_block_splits_inline_tags() {
+        let doc = Parser::new().parse("[b]x[quote]y[/quote]z[/b]");
+        let names: Vec<_> = doc
+            .nodes
+            .iter()
+            .map(|n| n.as_tag().unwrap().name.as_ref())
+            .collect();
+        assert_eq!(names, ["b", "quote", "b"]);
+
+        let quote = doc.nodes[1].as_tag().unwrap();
+        let inner = quote.children[0].as_tag().unwrap();
+        assert_eq!(&*inner.name, "b");
+        assert_eq!(inner.inner_text(), "y");
+        assert_eq!(doc.nodes[2].as_tag().unwrap().raw_close, "[/b]");
+
+        // Re-parsing the reconstructed source gives the same tree
+        let source = doc.to_bbcode();
+        assert_eq!(source, "[b]x[/b][quote][b]y[/b][/quote][b]z[/b]");
+        assert_eq!(Parser::new().parse(&source).to_bbcode(), source);
+    }
+
+    #[test]
+    fn parse_block_splits_nested_inline_tags() {
+        let doc = Parser::new().parse("[i][b]a[center]b[/center][/b]c[/i]");
+        assert_eq!(
+            doc.to_bbcode(),
+            "[i][b]a[/b][/i][center][i][b]b[/b][/i][/center][i]c[/i]"
+        );
+
+        // Lists and code blocks are not given copies of the inline tags
+        let doc = Parser::new().parse("[b][list][*]a[/list][code]x[/code][/b]");
+        assert_eq!(doc.to_bbcode(), "[list][*]a[/list][code]x[/code]");
+    }
+
+    #[test]
+    fn parse_block_reopens_limited_inline_tags() {
+        // Too many open inline tags: the block stays inside them
+        let input = format!("{}a[quote]b[/quote]c", "[b]".repeat(MAX_REOPENED_TAGS + 1));
+        let doc = Parser::new().parse(&input);
+        assert_eq!(doc.to_bbcode().matches("[quote]").count(), 1);
+        assert_eq!(
+            doc.to_bbcode().matches("[b]").count(),
+            MAX_REOPENED_TAGS + 1
+        );
+
+        // Many blocks: reopening stops once the budget is used up
+        let input = format!(
+            "[b][i][u][s][color=red][size=12][font=Arial][sub]x{}",
+            "[quote]y[/quote]".repeat(10_000)
+        );
+        let doc = Parser::new().parse(&input);
+        let output = doc.to_bbcode();
+        assert!(output.len() < input.len() + REOPENED_MARKUP_BUDGET + 1024);
+        assert!(output.starts_with("[b][i][u]"));
+        assert_eq!(output.matches("[quote]").count(), 10_000);
+    }
+
+    #[test]
+    fn parse_hr_splits_inline_tags() {
+        let doc = Parser::new().parse("[i][b]a[hr]b[/b][/i][b]c[br]d[/b]");
+        assert_eq!(
+            doc.to_bbcode(),
+            "[i][b]a[/b][/i][hr][i][b]b[/b][/i][b]c[br]d[/b]"
+        );
+    }
+
+    #[test]
+    fn parse_block_splits_by_registry() {
+        let mut registry = TagRegistry::new();
+        registry.register_alias("blockquote", "quote");
+        registry.register(CustomTagDef {
+            name: "sql".into(),
+            tag_type: TagType::Verbatim,
+            html_tag: Some("pre".into()),
+            ..Default::default()
+        });
+        registry.register(CustomTagDef {
+            name: "steps".into(),
+            tag_type: TagType::Block,
+            ..Default::default()
+        });
+        registry.register(CustomTagDef {
+            name: "step".into(),
+            tag_type: TagType::Block,
+            required_parents: vec!["steps".into()],
+            ..Default::default()
+        });
+        let parser = Parser::with_registry(registry);
+
+        let doc = parser.parse("[b]x[blockquote]y[/blockquote]z[/b]");
+        assert_eq!(
+            doc.to_bbcode(),
+            "[b]x[/b][blockquote][b]y[/b][/blockquote][b]z[/b]"
+        );
+        let doc = parser.parse("[b]x[sql]y[/sql]z[/b]");
+        assert_eq!(doc.to_bbcode(), "[b]x[/b][sql]y[/sql][b]z[/b]");
+
+        // Containers of required children get no copies of the inline tags
+        let doc = parser.parse("[b][steps][step]a[/step][/steps][/b]");
+        assert_eq!(doc.to_bbcode(), "[steps][step]a[/step][/steps]");
+    }
+
     // ==================== Required Parent Tests ====================
 
     #[test]
@@ -877,6 +2019,29 @@ mod tests {
         assert!(tag.children.iter().any(|n| n.is_linebreak()));
     }
 
+    // ==================== Fragment Tests ====================
+
+    #[test]
+    fn parse_fragment_keeps_newlines_and_skips_auto_links() {
+        let parser = Parser::new();
+        let doc = parser.parse_fragment("a\nb https://x.com");
+        assert_eq!(
+            doc.nodes,
+            vec![
+                Node::Text(Cow::Borrowed("a")),
+                Node::Text(Cow::Borrowed("\n")),
+                Node::Text(Cow::Borrowed("b ")),
+                Node::Text(Cow::Borrowed("https://x.com")),
+            ]
+        );
+        let html = crate::Renderer::new().render(&doc);
+        assert_eq!(html, "a\nb https://x.com");
+
+        let doc = parser.parse_fragment("[b]Bob[/b]\r\n");
+        assert_eq!(doc.nodes[0].as_tag().unwrap().name, "b");
+        assert_eq!(doc.nodes[1], Node::Text(Cow::Borrowed("\r\n")));
+    }
+
     // ==================== URL Auto-Detection Tests ====================
 
     #[test]
@@ -885,10 +2050,123 @@ mod tests {
         let doc = parser.parse("Visit https://example.com today!");
 
         // Should have auto-detected URL
-        let has_url = doc.nodes.iter().any(|n| matches!(n, Node::AutoUrl(_)));
+        let has_url = doc.nodes.iter().any(|n| matches!(n, Node::AutoUrl(..)));
         assert!(has_url);
     }
 
+    #[test]
+    fn parse_auto_url_trailing_punctuation() {
+        let doc = Parser::new().parse("(see https://example.com/a).");
+        assert_eq!(
+            doc.nodes[1],
+            Node::AutoUrl(Cow::Borrowed("https://example.com/a"), AutoLinkKind::Url)
+        );
+        assert_eq!(doc.nodes[2], Node::Text(Cow::Borrowed(").")));
+    }
+
+    #[test]
+    fn parse_auto_link_www_and_emails() {
+        let parser = Parser::with_config(ParserConfig {
+            auto_link_www: true,
+            auto_link_emails: true,
+            ..Default::default()
+        });
+
+        let doc = parser.parse("visit www.example.com.");
+        assert_eq!(
+            doc.nodes,
+            vec![
+                Node::Text(Cow::Borrowed("visit ")),
+                Node::AutoUrl(Cow::Borrowed("www.example.com"), AutoLinkKind::Www),
+                Node::Text(Cow::Borrowed(".")),
+            ]
+        );
+
+        let doc = parser.parse("mail me@x.com.");
+        assert_eq!(
+            doc.nodes[1],
+            Node::AutoUrl(Cow::Borrowed("me@x.com"), AutoLinkKind::Email)
+        );
+        assert_eq!(doc.nodes[2], Node::Text(Cow::Borrowed(".")));
+
+        // Off by default
+        let doc = Parser::new().parse("visit www.example.com or me@x.com");
+        assert_eq!(doc.nodes.len(), 1);
+    }
+
+    #[test]
+    fn find_www_link_test() {
+        assert_eq!(find_www_link("www.example.com"), Some(0..15));
+        assert_eq!(find_www_link("(www.x.org/a?b=1)"), Some(1..16));
+        assert_eq!(find_www_link("WWW.X.ORG"), Some(0..9));
+        assert_eq!(find_www_link("www.example"), None);
+        assert_eq!(find_www_link("awww.x.com"), None);
+        assert_eq!(find_www_link("me@www.x.com"), None);
+        assert_eq!(find_www_link("www..com"), None);
+    }
+
+    #[test]
+    fn parse_mentions() {
+        let parser = Parser::with_config(ParserConfig {
+            parse_mentions: true,
+            ..Default::default()
+        });
+        let input = "Hi @alice, mail bob@x.com or [url=https://x.com]@carol[/url] @dave.";
+        let doc = parser.parse(input);
+
+        let mentions: Vec<_> = doc
+            .nodes
+            .iter()
+            .filter_map(Node::as_tag)
+            .filter(|tag| tag.name == "user")
+            .collect();
+        assert_eq!(mentions.len(), 2);
+        assert_eq!(mentions[0].inner_text(), "alice");
+        assert_eq!(&input[mentions[0].span.clone()], "@alice");
+        assert!(mentions[0].closed && !mentions[0].broken);
+        assert_eq!(mentions[1].inner_text(), "dave");
+        assert_eq!(&input[mentions[1].span.clone()], "@dave");
+
+        // Off by default and when [user] is disabled
+        assert!(Parser::new()
+            .parse(input)
+            .nodes
+            .iter()
+            .all(|n| n.as_tag().is_none_or(|t| t.name != "user")));
+        let parser = Parser::with_config(ParserConfig {
+            parse_mentions: true,
+            disabled_tags: ["user".to_string()].into(),
+            ..Default::default()
+        });
+        assert_eq!(
+            parser.parse("@alice").nodes,
+            vec![Node::Text("@alice".into())]
+        );
+    }
+
+    #[test]
+    fn find_mention_test() {
+        assert_eq!(find_mention("@bob"), Some(0..4));
+        assert_eq!(find_mention("(@first.last)"), Some(1..12));
+        assert_eq!(find_mention("hi @bob-."), Some(3..7));
+        assert_eq!(find_mention("me@x.com"), None);
+        assert_eq!(find_mention("@ @@x"), None);
+        assert_eq!(find_mention("@.x"), None);
+    }
+
+    #[test]
+    fn find_email_test() {
+        assert_eq!(find_email("me@x.com"), Some(0..8));
+        assert_eq!(
+            find_email("Mail first.last+tag@mail.example.org!"),
+            Some(5..36)
+        );
+        assert_eq!(find_email("a @ b"), None);
+        assert_eq!(find_email("me@localhost"), None);
+        assert_eq!(find_email("me@x.c0m"), None);
+        assert_eq!(find_email("@x.com"), None);
+    }
+
     // ==================== Complex Document Tests ====================
 
     #[test]
@@ -971,6 +2249,301 @@ Check out https://example.com for more info.
         assert_eq!(&*tag.inner_text(), "私は猫です");
     }
 
+    // ==================== Event Tests ====================
+
+    /// Streams HTML for a small subset of tags straight from parse events.
+    #[derive(Default)]
+    struct HtmlSink {
+        html: String,
+        open: Vec<&'static str>,
+    }
+
+    impl<'a> ParseEventSink<'a> for HtmlSink {
+        fn event(&mut self, event: ParseEvent<'a>) {
+            match event {
+                ParseEvent::StartTag(tag) => {
+                    let element = match &*tag.name {
+                        "b" => "strong",
+                        "i" => "em",
+                        _ => "span",
+                    };
+                    self.html.push_str(&format!("<{}>", element));
+                    self.open.push(element);
+                }
+                ParseEvent::EndTag(_) => {
+                    let element = self.open.pop().unwrap();
+                    self.html.push_str(&format!("</{}>", element));
+                }
+                ParseEvent::Text(text) | ParseEvent::AutoUrl(text, _) => {
+                    self.html.push_str(&crate::escape_html(&text))
+                }
+                ParseEvent::LineBreak => self.html.push_str("<br />"),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_events_stream_matches_render() {
+        let input = "Hello [b]bold [i]both[/i][/b] & <done>\n[i]unclosed";
+        let mut sink = HtmlSink::default();
+        Parser::new().parse_events(input, &mut sink);
+
+        let expected = crate::Renderer::new().render(&Parser::new().parse(input));
+        assert_eq!(sink.html, expected);
+    }
+
+    #[test]
+    fn parse_events_balanced() {
+        struct Depth(i32, Vec<TagEnd<'static>>);
+        impl<'a> ParseEventSink<'a> for Depth {
+            fn event(&mut self, event: ParseEvent<'a>) {
+                match event {
+                    ParseEvent::StartTag(_) => self.0 += 1,
+                    ParseEvent::EndTag(end) => {
+                        self.0 -= 1;
+                        self.1.push(TagEnd {
+                            raw_close: Cow::Owned(end.raw_close.into_owned()),
+                            ..end
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut sink = Depth(0, Vec::new());
+        Parser::new().parse_events("[b][i]x[/b][u]open", &mut sink);
+        assert_eq!(sink.0, 0);
+        assert!(sink.1[0].broken);
+        assert!(sink.1[1].closed);
+        assert!(!sink.1[2].closed && !sink.1[2].broken);
+    }
+
+    #[test]
+    fn emit_node_deep_tree() {
+        struct Count(usize, usize);
+        impl<'a> ParseEventSink<'a> for Count {
+            fn event(&mut self, event: ParseEvent<'a>) {
+                match event {
+                    ParseEvent::StartTag(_) => self.0 += 1,
+                    ParseEvent::EndTag(_) => self.1 += 1,
+                    _ => {}
+                }
+            }
+        }
+
+        let depth = 50_000;
+        let mut node = Node::text("x");
+        for _ in 0..depth {
+            node = Node::Tag(TagNode {
+                name: Cow::Borrowed("b"),
+                children: vec![node],
+                ..Default::default()
+            });
+        }
+        let mut sink = Count(0, 0);
+        Parser::new().emit_node(node, &mut sink);
+        assert_eq!((sink.0, sink.1), (depth, depth));
+    }
+
+    #[test]
+    fn parse_misnested_tags_become_broken() {
+        let doc = Parser::new().parse("[b]a[i]x[u]z[/b]y");
+        let bold = doc.nodes[0].as_tag().unwrap();
+        assert!(bold.closed);
+        let italic = bold.children[1].as_tag().unwrap();
+        assert!(italic.broken);
+        assert!(italic.children[1].as_tag().unwrap().broken);
+        assert_eq!(doc.nodes[1].as_text().map(|t| t.as_ref()), Some("y"));
+    }
+
+    // ==================== Span Tests ====================
+
+    #[test]
+    fn parse_tag_spans() {
+        let input = "ab[b]c[i]d[/i][/b] [u]open";
+        let doc = Parser::new().parse(input);
+        let bold = doc.nodes[1].as_tag().unwrap();
+        assert_eq!(bold.span, 2..18);
+        assert_eq!(&input[bold.span.clone()], "[b]c[i]d[/i][/b]");
+        assert_eq!(bold.children[1].as_tag().unwrap().span, 6..14);
+        assert_eq!(doc.nodes[3].as_tag().unwrap().span, 19..input.len());
+    }
+
+    #[test]
+    fn parse_special_tag_spans() {
+        let input = "[code]x[/b][/code][list][*]one [b]two[/b][*]three[/list][b][i]x[/b]";
+        let doc = Parser::new().parse(input);
+
+        let code = doc.nodes[0].as_tag().unwrap();
+        assert_eq!(&input[code.span.clone()], "[code]x[/b][/code]");
+
+        let list = doc.nodes[1].as_tag().unwrap();
+        let item = list.children[0].as_tag().unwrap();
+        assert_eq!(&input[item.span.clone()], "[*]one [b]two[/b]");
+        let nested = item.children[1].as_tag().unwrap();
+        assert_eq!(&input[nested.span.clone()], "[b]two[/b]");
+
+        let broken = doc.nodes[2].as_tag().unwrap().children[0].as_tag().unwrap();
+        assert!(broken.broken);
+        assert_eq!(&input[broken.span.clone()], "[i]x");
+    }
+
+    // ==================== Nested Verbatim Tests ====================
+
+    fn code_content<'a>(node: &'a Node<'a>) -> &'a str {
+        let code = node.as_tag().unwrap();
+        assert_eq!(code.name, "code");
+        assert!(code.closed);
+        code.children[0].as_text().unwrap()
+    }
+
+    #[test]
+    fn parse_code_in_quote_keeps_tags_literal() {
+        let doc = Parser::new().parse("[quote][code][b]x[/b][/code][/quote]");
+        let quote = doc.nodes[0].as_tag().unwrap();
+        assert_eq!(code_content(&quote.children[0]), "[b]x[/b]");
+    }
+
+    #[test]
+    fn parse_code_in_quote_ignores_outer_close() {
+        let doc = Parser::new().parse("[quote][code]a [/quote] b[/code][/quote]after");
+        assert_eq!(doc.len(), 2);
+        let quote = doc.nodes[0].as_tag().unwrap();
+        assert!(quote.closed);
+        assert_eq!(code_content(&quote.children[0]), "a [/quote] b");
+    }
+
+    #[test]
+    fn parse_code_deeply_nested() {
+        let doc =
+            Parser::new().parse("[quote][quote][b][code][/b][/quote][/code][/b][/quote][/quote]");
+        let outer = doc.nodes[0].as_tag().unwrap();
+        let inner = outer.children[0].as_tag().unwrap();
+        // The code block is lifted out of the (now empty) bold tag
+        assert_eq!(code_content(&inner.children[0]), "[/b][/quote]");
+    }
+
+    #[test]
+    fn parse_code_in_list_item_ignores_list_close() {
+        let doc = Parser::new().parse("[list][*][code][/list][b][/code][*]two[/list]");
+        let list = doc.nodes[0].as_tag().unwrap();
+        assert_eq!(list.children.len(), 2);
+        let item = list.children[0].as_tag().unwrap();
+        assert_eq!(code_content(&item.children[0]), "[/list][b]");
+    }
+
+    // ==================== Tag Filter Tests ====================
+
+    #[test]
+    fn parse_allowed_tags_only() {
+        let parser = Parser::with_config(ParserConfig {
+            allowed_tags: Some(["b".to_string(), "i".to_string()].into()),
+            ..Default::default()
+        });
+        let doc = parser.parse("[b]x[/b][quote]y[/quote][img]https://a.example/p.png[/img]");
+        assert!(doc.nodes[0].is_tag());
+        assert!(doc.nodes[1..].iter().all(|n| !n.is_tag()));
+    }
+
+    #[test]
+    fn parse_disabled_tags_win() {
+        let parser = Parser::with_config(ParserConfig {
+            allowed_tags: Some(["b".to_string(), "quote".to_string()].into()),
+            disabled_tags: ["quote".to_string()].into(),
+            ..Default::default()
+        });
+        let doc = parser.parse("[quote][b]x[/b][/quote]");
+        assert_eq!(doc.nodes[0].as_text().map(|t| t.as_ref()), Some("[quote]"));
+        assert!(doc.nodes[1].is_tag());
+        assert_eq!(doc.nodes[2].as_text().map(|t| t.as_ref()), Some("[/quote]"));
+    }
+
+    #[test]
+    fn parse_disabled_tag_alias() {
+        let parser = Parser::with_config(ParserConfig {
+            disabled_tags: ["img".to_string()].into(),
+            ..Default::default()
+        });
+        let doc = parser.parse("[image]https://a.example/p.png[/image]");
+        assert!(doc.nodes.iter().all(|n| !n.is_tag()));
+        let html = crate::Renderer::new().render(&doc);
+        assert!(html.starts_with("[image]") && html.ends_with("[/image]"));
+    }
+
+    // ==================== Typographic Tests ====================
+
+    fn typographic_parser() -> Parser {
+        Parser::with_config(ParserConfig {
+            typographic: true,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn parse_typographic_quotes() {
+        let doc = typographic_parser().parse("\"hi\" and it's 'fine'");
+        assert_eq!(
+            doc.nodes[0].as_text().map(|t| t.as_ref()),
+            Some("\u{201C}hi\u{201D} and it\u{2019}s \u{2018}fine\u{2019}")
+        );
+    }
+
+    #[test]
+    fn parse_typographic_dashes_and_ellipsis() {
+        let doc = typographic_parser().parse("a--b...");
+        assert_eq!(
+            doc.nodes[0].as_text().map(|t| t.as_ref()),
+            Some("a\u{2014}b\u{2026}")
+        );
+    }
+
+    #[test]
+    fn parse_typographic_skips_verbatim() {
+        let doc = typographic_parser().parse("[code]\"x\" -- y...[/code]");
+        let tag = doc.nodes[0].as_tag().unwrap();
+        assert_eq!(
+            tag.children[0].as_text().map(|t| t.as_ref()),
+            Some("\"x\" -- y...")
+        );
+    }
+
+    #[test]
+    fn parse_typographic_across_tags_and_urls() {
+        let doc = typographic_parser().parse("[b]Bob[/b]'s \"[i]x[/i]\"");
+        let texts: Vec<_> = doc.nodes[1..]
+            .iter()
+            .filter_map(|n| n.as_text().map(|t| t.as_ref()))
+            .collect();
+        assert_eq!(texts, ["\u{2019}s \u{201C}", "\u{201D}"]);
+
+        let doc = typographic_parser().parse(
+            "[url]https://x.com/a--b...[/url] [img]https://x.com/it's.png[/img] \
+             [email]o'brien@x.com[/email] [url=https://x.com/a--b]a--b[/url]",
+        );
+        let inner: Vec<_> = doc
+            .nodes
+            .iter()
+            .filter_map(|n| n.as_tag())
+            .map(|t| t.inner_text().into_owned())
+            .collect();
+        assert_eq!(
+            inner,
+            [
+                "https://x.com/a--b...",
+                "https://x.com/it's.png",
+                "o'brien@x.com",
+                "a\u{2014}b"
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_typographic_disabled_by_default() {
+        let doc = Parser::new().parse("\"hi\"");
+        assert_eq!(doc.nodes[0].as_text().map(|t| t.as_ref()), Some("\"hi\""));
+    }
+
     #[test]
     fn parse_emoji() {
         let parser = Parser::new();
@@ -980,6 +2553,37 @@ Check out https://example.com for more info.
         assert_eq!(&*tag.inner_text(), "🔥🎉");
     }
 
+    // ==================== Input Limit Tests ====================
+
+    #[test]
+    fn parse_max_input_len_truncates() {
+        let parser = Parser::with_config(ParserConfig {
+            max_input_len: Some(7),
+            ..Default::default()
+        });
+        let doc = parser.parse("[b]bold[/b] and more");
+        let tag = doc.nodes[0].as_tag().unwrap();
+        assert_eq!(tag.name, "b");
+        assert!(!tag.closed);
+        assert_eq!(tag.children, vec![Node::Text(Cow::Borrowed("bold"))]);
+        assert_eq!(doc.len(), 1);
+
+        // Never split a multi-byte character
+        for max in 0..12 {
+            let parser = Parser::with_config(ParserConfig {
+                max_input_len: Some(max),
+                ..Default::default()
+            });
+            let doc = parser.parse("héllo wörld");
+            assert!(doc.to_bbcode().len() <= max);
+            assert!("héllo wörld".starts_with(&doc.to_bbcode()));
+        }
+        assert_eq!(
+            Parser::new().parse("héllo wörld").to_bbcode(),
+            "héllo wörld"
+        );
+    }
+
     // ==================== Parser Reuse Tests ====================
 
     #[test]
@@ -996,6 +2600,24 @@ Check out https://example.com for more info.
         assert_eq!(&*doc2.nodes[0].as_tag().unwrap().name, "i");
     }
 
+    #[test]
+    fn parse_into_reuses_document() {
+        let parser = Parser::new();
+        let second = String::from("[u]Second[/u]");
+        let mut doc = Document::new();
+
+        parser.parse_into("a [b]b[/b] c [i]d[/i] e\nf", &mut doc);
+        assert_eq!(doc.len(), 7);
+        let capacity = doc.nodes.capacity();
+
+        parser.parse_into(&second, &mut doc);
+        assert_eq!(doc.nodes.capacity(), capacity);
+        assert_eq!(doc, parser.parse(&second));
+        let tag = doc.nodes[0].as_tag().unwrap();
+        assert_eq!(&*tag.name, "u");
+        assert_eq!(tag.inner_text(), "Second");
+    }
+
     // ==================== Raw Preservation Tests ====================
 
     #[test]
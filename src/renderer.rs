@@ -26,13 +26,19 @@
 //! }
 //! ```
 
-use std::borrow::Cow;
-use std::collections::HashMap;
-use std::fmt::Write;
-use std::sync::Arc;
-
-use crate::ast::{Document, Node, TagNode};
-use crate::tags::TagRegistry;
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+use crate::ast::{AutoLinkKind, Document, Node, TagNode, TagOption, TagType};
+use crate::error::RenderError;
+use crate::tags::{ResolvedTag, TagRegistry};
+use crate::Map;
+use alloc::collections::{BTreeMap, BTreeSet};
 
 /// Context provided to custom tag handlers for rendering.
 #[derive(Debug, Clone)]
@@ -47,6 +53,18 @@ pub struct RenderContext<'a> {
     pub sanitize: bool,
     /// Allowed URL schemes.
     pub allowed_schemes: &'a [String],
+    /// Number of `[spoiler]` blocks enclosing the tag.
+    pub spoiler_depth: usize,
+    /// Per-render data passed to [`Renderer::render_with_context`], such as
+    /// the current user, locale or base URL.
+    pub data: &'a Map<String, String>,
+}
+
+impl RenderContext<'_> {
+    /// Returns the context value for `key`, if set.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.data.get(key).map(String::as_str)
+    }
 }
 
 /// Trait for custom tag handlers that extend the renderer.
@@ -95,6 +113,22 @@ pub trait CustomTagHandler: Send + Sync {
     /// * `output` - The output string to write HTML to
     fn render(&self, tag: &TagNode, ctx: &RenderContext, output: &mut String) -> bool;
 
+    /// Renders the tag to HTML, reporting failures.
+    ///
+    /// The renderer always calls this method; the default delegates to
+    /// [`render`](Self::render). Override it for handlers that can fail (for
+    /// example when a referenced attachment is missing). An error aborts
+    /// [`Renderer::try_render`], while the infallible render methods output
+    /// the failed tag as raw text instead.
+    fn try_render(
+        &self,
+        tag: &TagNode,
+        ctx: &RenderContext,
+        output: &mut String,
+    ) -> Result<bool, RenderError> {
+        Ok(self.render(tag, ctx, output))
+    }
+
     /// Optional: Called before rendering to collect data from all tags of this type.
     ///
     /// This is useful for batch operations like pre-fetching attachments from a database.
@@ -111,45 +145,917 @@ pub trait CustomTagHandler: Send + Sync {
     }
 }
 
+/// Output sink for a single render pass.
+///
+/// Wraps the caller's writer and latches the first write error, so the tag
+/// renderers can write unconditionally and the error is reported once at the
+/// end of [`Renderer::render_to`].
+///
+/// Rendering is iterative: a tag renderer never descends into its children
+/// but [defers](Self::defer) them, and [`Renderer::render_nodes`] renders
+/// them from an explicit work stack. Output a tag writes after deferring
+/// children is buffered until they have been rendered.
+struct RenderState<'w, 'd> {
+    out: &'w mut dyn fmt::Write,
+    result: fmt::Result,
+    /// Whether the node being rendered has deferred any children yet.
+    deferring: bool,
+    /// Output written by the current node since it first deferred children.
+    buffer: String,
+    /// Work produced by the current node, in output order.
+    pending: Vec<Work<'d>>,
+    /// Whether a custom handler error aborts rendering (see
+    /// [`Renderer::try_render`]) instead of falling back to raw text.
+    strict: bool,
+    /// The first custom handler error, when `strict`.
+    handler_error: Option<RenderError>,
+    /// Heading ids already emitted, for deduplication.
+    heading_ids: BTreeSet<String>,
+    /// Smilies replaced so far, for `max_smilies`.
+    smilie_count: usize,
+    /// Bytes that may still be written, for `max_output_len`. Markup that
+    /// closes elements already written is reserved up front.
+    remaining: Option<usize>,
+    /// Whether output stopped at `max_output_len`.
+    truncated: bool,
+    /// Output written by the current node before it deferred children, held
+    /// back until the whole node is known to fit `max_output_len`.
+    chunk: String,
+    /// Consecutive line breaks seen, for `collapse_blank_lines`.
+    line_break_run: usize,
+    /// Number of `[spoiler]` blocks currently open.
+    spoiler_depth: usize,
+    /// Data for [`RenderContext::data`].
+    context: Cow<'w, Map<String, String>>,
+}
+
+impl<'w, 'd> RenderState<'w, 'd> {
+    fn new(out: &'w mut dyn fmt::Write) -> Self {
+        Self {
+            out,
+            result: Ok(()),
+            deferring: false,
+            buffer: String::new(),
+            pending: Vec::new(),
+            strict: false,
+            handler_error: None,
+            heading_ids: BTreeSet::new(),
+            smilie_count: 0,
+            remaining: None,
+            truncated: false,
+            chunk: String::new(),
+            line_break_run: 0,
+            spoiler_depth: 0,
+            context: Cow::Owned(Map::new()),
+        }
+    }
+
+    /// Writes straight to the sink, bypassing the output limit.
+    fn write_out(&mut self, s: &str) {
+        if self.result.is_ok() {
+            self.result = self.out.write_str(s);
+        }
+    }
+
+    /// Records a custom handler error, stopping all further output.
+    fn fail(&mut self, error: RenderError) {
+        if self.handler_error.is_none() {
+            self.handler_error = Some(error);
+        }
+        self.result = Err(fmt::Error);
+    }
+
+    fn push_str(&mut self, s: &str) {
+        if self.deferring {
+            self.buffer.push_str(s);
+        } else if self.remaining.is_some() {
+            self.chunk.push_str(s);
+        } else if self.result.is_ok() {
+            self.result = self.out.write_str(s);
+        }
+    }
+
+    fn push(&mut self, c: char) {
+        if self.deferring {
+            self.buffer.push(c);
+        } else if self.remaining.is_some() {
+            self.chunk.push(c);
+        } else if self.result.is_ok() {
+            self.result = self.out.write_char(c);
+        }
+    }
+
+    /// Schedules `nodes` to be rendered at this point of the output.
+    fn defer(&mut self, nodes: &'d [Node<'d>]) {
+        if nodes.is_empty() {
+            return;
+        }
+        if !self.buffer.is_empty() {
+            self.pending
+                .push(Work::Html(core::mem::take(&mut self.buffer)));
+        }
+        self.pending.push(Work::Nodes(nodes));
+        self.deferring = true;
+    }
+
+    /// Leaves a `[spoiler]` once the children deferred so far are rendered.
+    fn leave_spoiler(&mut self) {
+        if self.deferring {
+            if !self.buffer.is_empty() {
+                self.pending
+                    .push(Work::Html(core::mem::take(&mut self.buffer)));
+            }
+            self.pending.push(Work::LeaveSpoiler);
+        } else {
+            self.spoiler_depth -= 1;
+        }
+    }
+
+    /// Ends the current node, returning the work it produced in output order.
+    ///
+    /// Under `max_output_len` the node is written only if it fits together
+    /// with the markup closing it; otherwise output stops here and the node
+    /// produces no work.
+    fn take_pending(&mut self) -> impl Iterator<Item = Work<'d>> + '_ {
+        if !self.buffer.is_empty() {
+            self.pending
+                .push(Work::Html(core::mem::take(&mut self.buffer)));
+        }
+        self.deferring = false;
+        if let Some(remaining) = self.remaining {
+            let closing: usize = self
+                .pending
+                .iter()
+                .map(|work| match work {
+                    Work::Html(html) => html.len(),
+                    _ => 0,
+                })
+                .sum();
+            let len = self.chunk.len() + closing;
+            if self.truncated || len > remaining {
+                self.truncated = true;
+                self.pending.clear();
+            } else {
+                self.remaining = Some(remaining - len);
+                if self.result.is_ok() {
+                    self.result = self.out.write_str(&self.chunk);
+                }
+            }
+            self.chunk.clear();
+        }
+        self.pending.drain(..)
+    }
+
+    fn finish(self) -> fmt::Result {
+        self.result
+    }
+}
+
+impl fmt::Write for RenderState<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+/// An entry on the render work stack.
+enum Work<'d> {
+    /// Nodes still to be rendered.
+    Nodes(&'d [Node<'d>]),
+    /// Closing markup of a tag whose children have been rendered.
+    Html(String),
+    /// End of a `[spoiler]` whose children have been rendered.
+    LeaveSpoiler,
+}
+
+/// An entry on the work stack of [`Renderer::render_text_only`].
+enum PlainWork<'d> {
+    /// A node still to be rendered.
+    Node(&'d Node<'d>),
+    /// Text to append as is.
+    Text(&'d str),
+    /// End of a block-level tag, which is followed by a newline.
+    EndBlock,
+}
+
+/// An entry on the work stack of [`Renderer::render_markdown`].
+enum MarkdownWork<'d> {
+    /// A node still to be rendered.
+    Node(&'d Node<'d>),
+    /// A list item with its marker (`- ` or `1. `).
+    Item(&'d TagNode<'d>, &'static str),
+    /// Markdown syntax to append as is, such as the end of a link.
+    Markup(Cow<'d, str>),
+    /// A closing emphasis marker (`**` or `*`).
+    CloseEmphasis(&'static str),
+    /// Text to append with Markdown syntax escaped.
+    Text(&'d str),
+    /// End of a block, followed by this many line breaks.
+    EndBlock(usize),
+    /// End of a quote or list item; the line prefix is cut back to this length.
+    EndPrefix(usize),
+}
+
+/// Markdown output with a line prefix (`> ` inside quotes, indentation
+/// inside list items) that is written at the start of every line.
+struct MarkdownWriter {
+    out: String,
+    prefix: String,
+    /// Line breaks still to be written before the next output.
+    breaks: usize,
+    /// Whether the current line is still empty.
+    line_start: bool,
+    /// Whether only a list item marker has been written on this line.
+    item_start: bool,
+    /// Emphasis markers opened but not written yet; they go after any
+    /// leading whitespace of the following text.
+    emphasis: String,
+}
+
+impl MarkdownWriter {
+    fn new() -> Self {
+        Self {
+            out: String::new(),
+            prefix: String::new(),
+            breaks: 0,
+            line_start: true,
+            item_start: false,
+            emphasis: String::new(),
+        }
+    }
+
+    /// Requests a line break before the next output.
+    fn line_break(&mut self) {
+        if !self.out.is_empty() {
+            self.breaks += 1;
+        }
+    }
+
+    /// Requests at least `breaks` line breaks (2 for a blank line) before
+    /// the next output.
+    fn block(&mut self, breaks: usize) {
+        if !self.out.is_empty() {
+            self.breaks = self.breaks.max(breaks);
+        }
+    }
+
+    fn newline(&mut self) {
+        if self.line_start {
+            self.out.push_str(self.prefix.trim_end());
+        }
+        self.out.push('\n');
+        self.line_start = true;
+    }
+
+    /// Writes the requested line breaks.
+    fn flush_breaks(&mut self) {
+        if self.breaks > 0 && !self.line_start {
+            // Trailing spaces could turn into a hard line break
+            let len = self.out.trim_end_matches([' ', '\t']).len();
+            self.out.truncate(len);
+        }
+        // More than one blank line means nothing more in Markdown
+        for _ in 0..core::mem::take(&mut self.breaks).min(2) {
+            self.newline();
+        }
+    }
+
+    /// Writes `markup` as is, prefixing each line.
+    fn push_str(&mut self, markup: &str) {
+        if !self.emphasis.is_empty() {
+            let emphasis = core::mem::take(&mut self.emphasis);
+            self.write(&emphasis);
+        }
+        self.write(markup);
+    }
+
+    fn write(&mut self, markup: &str) {
+        self.item_start = false;
+        self.flush_breaks();
+        for (i, line) in markup.split('\n').enumerate() {
+            if i > 0 {
+                self.newline();
+            }
+            if !line.is_empty() {
+                if self.line_start {
+                    self.out.push_str(&self.prefix);
+                    self.line_start = false;
+                }
+                self.out.push_str(line);
+            }
+        }
+    }
+
+    /// Writes text with Markdown syntax escaped, including list, heading
+    /// and rule markers at the start of a line. Leading whitespace on a new
+    /// line is dropped, as it could start an indented code block.
+    fn push_text(&mut self, text: &str) {
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.write("\n");
+            }
+            let line_start = self.line_start || self.breaks > 0;
+            let line = if line_start { line.trim_start() } else { line };
+            if line.is_empty() {
+                continue;
+            }
+
+            // Pending emphasis goes after leading whitespace, which can't
+            // follow an opening marker
+            let trimmed = line.trim_start();
+            let whitespace = &line[..line.len() - trimmed.len()];
+            if trimmed.is_empty() {
+                self.write(whitespace);
+                continue;
+            }
+            let mut escaped = escape_markdown(trimmed);
+            if (line_start || self.item_start) && self.emphasis.is_empty() {
+                escaped = Cow::Owned(escape_line_start(&escaped).into_owned());
+            }
+            self.write(whitespace);
+            self.push_str(&escaped);
+        }
+    }
+
+    /// Opens emphasis with `marker`, written before the next text.
+    fn open_emphasis(&mut self, marker: &str) {
+        self.emphasis.push_str(marker);
+    }
+
+    /// Closes emphasis with `marker`, before any trailing whitespace.
+    fn close_emphasis(&mut self, marker: &str) {
+        if let Some(open) = self.emphasis.strip_suffix(marker) {
+            // Nothing was emphasized
+            self.emphasis.truncate(open.len());
+            return;
+        }
+        if self.line_start {
+            return self.push_str(marker);
+        }
+        let len = self.out.trim_end_matches([' ', '\t']).len();
+        let trailing = self.out.split_off(len);
+        self.out.push_str(marker);
+        self.out.push_str(&trailing);
+    }
+}
+
 /// Configuration for the HTML renderer.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct RenderConfig {
     /// CSS class prefix for BBCode elements.
     pub class_prefix: Cow<'static, str>,
 
-    /// Whether to add rel="nofollow" to links.
+    /// Deprecated switch for the `nofollow` rel token.
+    ///
+    /// Only consulted while [`link_rel`](Self::link_rel) is at its default
+    /// (`["nofollow"]`): setting it to false then emits no `rel` tokens. A
+    /// customized `link_rel` is used as is.
+    #[deprecated(note = "use `link_rel` instead")]
     pub nofollow_links: bool,
 
+    /// Tokens emitted in the `rel` attribute of links (e.g. `nofollow`,
+    /// `noreferrer`). `noopener` is added automatically when links open in a
+    /// new tab.
+    pub link_rel: Vec<String>,
+
     /// Whether to add target="_blank" to links.
     pub open_links_in_new_tab: bool,
 
+    /// The site's own host (e.g. `forum.example.com`). When set, links to
+    /// this host (compared case-insensitively, whatever the scheme) stay in
+    /// the same tab even with `open_links_in_new_tab`, as relative links
+    /// always do.
+    pub internal_host: Option<String>,
+
+    /// Template for an `aria-label` on links whose text is the URL itself
+    /// (bare `[url]` and auto-links), so screen readers announce a summary
+    /// instead of spelling out the URL. `{host}` is replaced with the link's
+    /// host, e.g. `"Link to {host}"`. `None` (the default) adds no label.
+    pub url_aria_label_format: Option<Cow<'static, str>>,
+
+    /// Maximum length in characters of a URL shown as link text (bare
+    /// `[url]` and auto-links). Longer URLs are shortened with an ellipsis
+    /// (`https://example.com/very/lo…`); the `href` keeps the full URL.
+    pub max_display_url_len: Option<usize>,
+
     /// Whether to sanitize text content (escape HTML).
     pub sanitize: bool,
 
     /// Whether to convert line breaks to <br>.
     pub convert_linebreaks: bool,
 
-    /// Custom smilies/emoji mapping.
-    pub smilies: HashMap<String, String>,
+    /// Maximum number of consecutive line breaks rendered (`None` =
+    /// unlimited), so that e.g. `Some(2)` turns runs of blank lines into a
+    /// single blank line. Whitespace between the breaks doesn't end a run.
+    pub collapse_blank_lines: Option<usize>,
+
+    /// Custom smilies/emoji mapping from code (e.g. `:)`) to an image URL
+    /// or replacement text.
+    pub smilies: Map<String, String>,
+
+    /// Maximum number of smilies replaced per render (`None` = unlimited).
+    /// Codes past the limit are left as literal text.
+    pub max_smilies: Option<usize>,
 
     /// Allowed URL schemes for links and images.
     pub allowed_schemes: Vec<String>,
+
+    /// Whether user-supplied links and images may be relative (`/threads/5`,
+    /// `?page=2`) or protocol-relative (`//cdn.example.com/x.png`), e.g. on
+    /// an intranet forum. URLs from smilies and resolvers are always allowed
+    /// to be relative.
+    ///
+    /// Links that start with a host name (`[url=www.example.com]`,
+    /// `[url]example.com/a[/url]`) are treated as `http://` links either
+    /// way. Other scheme-less links such as `[url=page.html]` render as text
+    /// unless this is enabled.
+    pub allow_relative_urls: bool,
+
+    /// Query parameters kept on `[email]` links, e.g. `?subject=Help`.
+    /// Values are percent-encoded; other parameters are dropped.
+    pub mailto_params: Vec<String>,
+
+    /// Whether email addresses in `mailto:` links and their display text
+    /// are written as numeric character references (`&#109;&#101;...`),
+    /// which browsers decode but naive address scrapers miss.
+    pub obfuscate_emails: bool,
+
+    /// How content between `[list]` and its first `[*]` is rendered.
+    pub list_stray_content: StrayContent,
+
+    /// Maximum rendered image width in pixels (`None` = no limit).
+    pub max_image_width: Option<u32>,
+
+    /// Maximum rendered image height in pixels (`None` = no limit).
+    pub max_image_height: Option<u32>,
+
+    /// `alt` text for images that don't supply one with `[img alt=...]`.
+    /// Empty (the default) omits the attribute. An explicit `[img alt=""]`
+    /// is always rendered as `alt=""`.
+    pub default_img_alt: Cow<'static, str>,
+
+    /// Whether to emit inline `style` attributes for color, size, alignment
+    /// and indentation. When false, class names (e.g. `bbcode-color-red`) are
+    /// emitted instead, with a `data-*` attribute for values that can't map to
+    /// a class. Useful under a strict Content-Security-Policy.
+    pub use_inline_styles: bool,
+
+    /// Whether to merge directly nested `[color]`, `[size]` and `[font]` tags
+    /// into a single `<span>` with a combined `style` attribute. Only applies
+    /// when each tag's sole child is the next style tag.
+    pub merge_style_spans: bool,
+
+    /// HTML heading level used for `[heading=1]`. Higher option levels count
+    /// up from here, clamped to `<h6>`. Defaults to 2 (XenForo's mapping).
+    pub heading_base_level: u8,
+
+    /// Template for the quote attribution header, inserted as HTML. `{author}`
+    /// is replaced with the author name, escaped so it is safe in text and in
+    /// quoted attribute values.
+    pub quote_attribution_format: Cow<'static, str>,
+
+    /// Whether a quote author that is a valid URL (e.g. imported posts) is
+    /// rendered as a link labeled `quote_source_label` instead of an
+    /// attribution.
+    pub quote_author_as_link: bool,
+
+    /// Text of the link rendered by `quote_author_as_link`, e.g. for
+    /// localized forums.
+    pub quote_source_label: Cow<'static, str>,
+
+    /// Censored words as `(pattern, replacement)` pairs, matched against the
+    /// visible text before escaping; where several match, the first listed
+    /// wins. Code, plain blocks, URLs and attribute values are never
+    /// filtered. The replacement is escaped like the surrounding text.
+    pub word_filters: Vec<(String, String)>,
+
+    /// Whether word filters only match whole words.
+    pub word_filter_whole_word: bool,
+
+    /// Whether word filters match ASCII letters case-insensitively.
+    pub word_filter_case_insensitive: bool,
+
+    /// Whether each line of `[code]` content is wrapped in
+    /// `<span class="{prefix}-code-line">` so CSS counters can number lines.
+    pub code_line_numbers: bool,
+
+    /// Whether `[code]` blocks include a
+    /// `<button class="{prefix}-code-copy" type="button">` inside the `<pre>`.
+    /// The button has no behaviour of its own; wire it up with a script.
+    pub code_copy_button: bool,
+
+    /// Text of the `code_copy_button` button, e.g. for localized forums.
+    pub code_copy_label: Cow<'static, str>,
+
+    /// Template for the `class` of a `[code=lang]` block's `<code>`. `{lang}`
+    /// is replaced with the escaped language. Defaults to
+    /// `"language-{lang}"` (Prism); use `"hljs {lang}"` or
+    /// `"hljs language-{lang}"` for highlight.js.
+    pub code_class_format: Cow<'static, str>,
+
+    /// Optional hook that rewrites image URLs (e.g. to route them through an
+    /// image proxy). Runs only after the URL has passed scheme validation, and
+    /// its result is escaped before output.
+    pub image_url_rewriter: Option<UrlRewriter>,
+
+    /// Maximum length in bytes of a color value; longer values are rejected.
+    pub max_color_length: usize,
+
+    /// Whether headings get an `id` derived from their title, for permalinks.
+    /// Repeated titles are deduplicated with `-1`, `-2`, ... suffixes.
+    pub heading_anchors: bool,
+
+    /// Prefix for heading ids, keeping them from clashing with ids used by
+    /// the surrounding page.
+    pub heading_id_prefix: Cow<'static, str>,
+
+    /// Whether headings with an id also get a `#` link pointing at it.
+    pub heading_anchor_links: bool,
+
+    /// Whether the brackets of tags rendered back as text (broken or
+    /// rejected tags) are emitted as `&#91;`/`&#93;`, so the HTML can be fed
+    /// through a BBCode parser again without re-interpreting them.
+    pub escape_rejected_brackets: bool,
+
+    /// How bare numbers in `[size=N]` are interpreted. Values with an
+    /// explicit `px` or `%` unit are accepted in every mode.
+    pub size_mode: SizeMode,
+
+    /// Smallest font size `[size]` may produce, in pixels. Computed sizes
+    /// are raised to it; when above the default of 8, percentages are
+    /// raised proportionally too (relative to a 16px base).
+    pub min_font_px: u32,
+
+    /// Largest font size `[size]` may produce, in pixels. Computed sizes
+    /// and percentages (relative to a 16px base) are lowered to it. Sizes
+    /// beyond the built-in 8-36px range are still rejected.
+    pub max_font_px: u32,
+
+    /// Margin added per `[indent]` level, in pixels.
+    pub indent_step_px: u32,
+
+    /// Deepest `[indent]` level; larger levels are lowered to it. Levels
+    /// below one are raised to one.
+    pub max_indent_level: u32,
+
+    /// Whether whitespace and line breaks between table rows and cells are
+    /// rendered (as `<br />`, which is invalid inside `<table>`/`<tr>`). Line
+    /// breaks inside cell content are always kept.
+    pub line_break_in_tables: bool,
+
+    /// Summary text of a `[spoiler]` without a title, e.g. for localized
+    /// forums.
+    pub default_spoiler_label: Cow<'static, str>,
+
+    /// Whether `[spoiler]` blocks render expanded, with the `open` attribute
+    /// on `<details>` (e.g. for print or email views).
+    pub spoilers_open_by_default: bool,
+
+    /// How inline `[ispoiler]` tags are revealed.
+    pub spoiler_mode: SpoilerMode,
+
+    /// Whether void elements are written XHTML-style (`<br />`) or as
+    /// HTML5 (`<br>`).
+    pub output_mode: OutputMode,
+
+    /// Maximum length in bytes of the rendered HTML (`None` = unlimited).
+    /// Rendering stops before the first node that would not fit, and the
+    /// elements already opened are closed, so truncated output stays
+    /// well-formed.
+    pub max_output_len: Option<usize>,
+
+    /// Maps a `[user]` id (or, for mentions without an id, the user name)
+    /// to a profile URL. Returning `None` or an invalid URL leaves the
+    /// mention unlinked.
+    pub user_link_resolver: Option<UserLinkResolver>,
+
+    /// Maps the post id of a XenForo-style quote (`[quote="Name, post: 123,
+    /// member: 45"]`) to the quoted post's URL, which links the quote's
+    /// attribution. Returning `None` or an invalid URL leaves it unlinked.
+    pub post_url_resolver: Option<PostUrlResolver>,
+
+    /// Consulted before each tag is rendered, e.g. to disable `[img]` at
+    /// runtime or to drop everything inside `[spoiler]`. Also useful to
+    /// observe which tags a post uses. The tag's name is as written, so a
+    /// filter on `img` should also match `IMG` and the `[image]` alias.
+    pub tag_filter: Option<TagFilter>,
+
+    /// Maps an `[attach]` id to the uploaded file. Image attachments render
+    /// as `<img>`, others as a download link; without a resolver, or when it
+    /// returns `None` or an invalid URL, the tag is left as text.
+    pub attachment_resolver: Option<AttachmentResolver>,
+}
+
+/// Callback that maps a validated URL to the URL actually emitted.
+pub type UrlRewriter = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+/// Callback that maps a user id or name to a profile URL.
+pub type UserLinkResolver = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// Callback that maps a quoted post id to the post's URL.
+pub type PostUrlResolver = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// Callback that maps an attachment id to the attached file.
+pub type AttachmentResolver = Arc<dyn Fn(&str) -> Option<AttachmentInfo> + Send + Sync>;
+
+/// An uploaded file referenced by `[attach]`, as returned by
+/// [`RenderConfig::attachment_resolver`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AttachmentInfo {
+    /// URL of the file.
+    pub url: String,
+    /// Alt text of an image, or the text of a download link. Empty uses the
+    /// attachment id.
+    pub alt: String,
+    /// MIME type; `image/*` attachments are embedded as images.
+    pub mime_type: String,
+}
+
+/// Callback that decides how a tag is rendered.
+pub type TagFilter = Arc<dyn Fn(&TagNode) -> TagDecision + Send + Sync>;
+
+/// What [`RenderConfig::tag_filter`] does with a tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TagDecision {
+    /// Render the tag as usual.
+    #[default]
+    Render,
+    /// Render the tag's markup as literal text; its content is still
+    /// rendered.
+    AsText,
+    /// Drop the tag together with its content.
+    Skip,
+}
+
+impl fmt::Debug for RenderConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Destructure so new fields can't be forgotten here
+        #[allow(deprecated)]
+        let Self {
+            class_prefix,
+            nofollow_links,
+            link_rel,
+            open_links_in_new_tab,
+            internal_host,
+            url_aria_label_format,
+            max_display_url_len,
+            sanitize,
+            convert_linebreaks,
+            collapse_blank_lines,
+            smilies,
+            max_smilies,
+            allowed_schemes,
+            allow_relative_urls,
+            mailto_params,
+            obfuscate_emails,
+            list_stray_content,
+            max_image_width,
+            max_image_height,
+            default_img_alt,
+            use_inline_styles,
+            merge_style_spans,
+            heading_base_level,
+            quote_attribution_format,
+            quote_author_as_link,
+            quote_source_label,
+            word_filters,
+            word_filter_whole_word,
+            word_filter_case_insensitive,
+            code_line_numbers,
+            code_copy_button,
+            code_copy_label,
+            code_class_format,
+            image_url_rewriter,
+            max_color_length,
+            heading_anchors,
+            heading_id_prefix,
+            heading_anchor_links,
+            escape_rejected_brackets,
+            size_mode,
+            min_font_px,
+            max_font_px,
+            indent_step_px,
+            max_indent_level,
+            line_break_in_tables,
+            default_spoiler_label,
+            spoilers_open_by_default,
+            spoiler_mode,
+            output_mode,
+            max_output_len,
+            user_link_resolver,
+            post_url_resolver,
+            tag_filter,
+            attachment_resolver,
+        } = self;
+
+        f.debug_struct("RenderConfig")
+            .field("class_prefix", class_prefix)
+            .field("nofollow_links", nofollow_links)
+            .field("link_rel", link_rel)
+            .field("open_links_in_new_tab", open_links_in_new_tab)
+            .field("internal_host", internal_host)
+            .field("url_aria_label_format", url_aria_label_format)
+            .field("max_display_url_len", max_display_url_len)
+            .field("sanitize", sanitize)
+            .field("convert_linebreaks", convert_linebreaks)
+            .field("collapse_blank_lines", collapse_blank_lines)
+            .field("smilies", smilies)
+            .field("max_smilies", max_smilies)
+            .field("allowed_schemes", allowed_schemes)
+            .field("allow_relative_urls", allow_relative_urls)
+            .field("mailto_params", mailto_params)
+            .field("obfuscate_emails", obfuscate_emails)
+            .field("list_stray_content", list_stray_content)
+            .field("max_image_width", max_image_width)
+            .field("max_image_height", max_image_height)
+            .field("default_img_alt", default_img_alt)
+            .field("use_inline_styles", use_inline_styles)
+            .field("merge_style_spans", merge_style_spans)
+            .field("heading_base_level", heading_base_level)
+            .field("quote_attribution_format", quote_attribution_format)
+            .field("quote_author_as_link", quote_author_as_link)
+            .field("quote_source_label", quote_source_label)
+            .field("word_filters", word_filters)
+            .field("word_filter_whole_word", word_filter_whole_word)
+            .field("word_filter_case_insensitive", word_filter_case_insensitive)
+            .field("code_line_numbers", code_line_numbers)
+            .field("code_copy_button", code_copy_button)
+            .field("code_copy_label", code_copy_label)
+            .field("code_class_format", code_class_format)
+            .field(
+                "image_url_rewriter",
+                &image_url_rewriter.as_ref().map(|_| ".."),
+            )
+            .field("max_color_length", max_color_length)
+            .field("heading_anchors", heading_anchors)
+            .field("heading_id_prefix", heading_id_prefix)
+            .field("heading_anchor_links", heading_anchor_links)
+            .field("escape_rejected_brackets", escape_rejected_brackets)
+            .field("size_mode", size_mode)
+            .field("min_font_px", min_font_px)
+            .field("max_font_px", max_font_px)
+            .field("indent_step_px", indent_step_px)
+            .field("max_indent_level", max_indent_level)
+            .field("line_break_in_tables", line_break_in_tables)
+            .field("default_spoiler_label", default_spoiler_label)
+            .field("spoilers_open_by_default", spoilers_open_by_default)
+            .field("spoiler_mode", spoiler_mode)
+            .field("output_mode", output_mode)
+            .field("max_output_len", max_output_len)
+            .field(
+                "user_link_resolver",
+                &user_link_resolver.as_ref().map(|_| ".."),
+            )
+            .field(
+                "post_url_resolver",
+                &post_url_resolver.as_ref().map(|_| ".."),
+            )
+            .field("tag_filter", &tag_filter.as_ref().map(|_| ".."))
+            .field(
+                "attachment_resolver",
+                &attachment_resolver.as_ref().map(|_| ".."),
+            )
+            .finish()
+    }
+}
+
+/// Handling of content that appears inside a `[list]` before its first `[*]`.
+///
+/// Such content would otherwise end up as a direct child of `<ul>`/`<ol>`,
+/// which is invalid HTML. Whitespace-only stray content is always dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StrayContent {
+    /// Discard the stray content.
+    Drop,
+    /// Wrap the stray content in its own `<li>`.
+    #[default]
+    WrapInItem,
+    /// Render the stray content before the opening list element.
+    KeepBefore,
+}
+
+/// Interpretation of bare numeric `[size]` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SizeMode {
+    /// Guess from the value: 1-7 is the XenForo scale, 8-100 a percentage
+    /// and 101-200 pixels (clamped to 36px).
+    #[default]
+    Auto,
+    /// 1-7 is the XenForo scale; other bare numbers are rejected.
+    XenForoScale,
+    /// Bare numbers are phpBB percentages between 50 and 200.
+    PhpbbPercent,
+    /// Bare numbers are pixel sizes between 8 and 36.
+    Pixels,
+}
+
+impl RenderConfig {
+    /// Creates a default configuration that only allows the given URL
+    /// schemes for links and images. Schemes match case-insensitively.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bbcode::{parse_with_config, ParserConfig, RenderConfig};
+    ///
+    /// let config = RenderConfig::with_schemes(&["https"]);
+    /// let html = parse_with_config("[url]http://x.com[/url]", &ParserConfig::default(), &config);
+    /// assert!(!html.contains("href="));
+    /// ```
+    pub fn with_schemes(schemes: &[&str]) -> Self {
+        Self {
+            allowed_schemes: schemes.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// How inline spoilers (`[ispoiler]`) are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpoilerMode {
+    /// A `<span>` toggled by an inline `onclick` handler. Blocked by a
+    /// strict Content Security Policy.
+    #[default]
+    Script,
+    /// A plain `<span>` without inline JavaScript; revealing is left to the
+    /// embedder's stylesheet (e.g. `:hover`) or an external script.
+    CssOnly,
+    /// A `<details>` element, like block `[spoiler]`s.
+    Details,
+}
+
+/// HTML dialect used for void elements (`<br>`, `<hr>`, `<img>`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    /// HTML5: void elements have no self-closing slash (`<br>`).
+    Html5,
+    /// XHTML: void elements are self-closed (`<br />`).
+    #[default]
+    Xhtml,
 }
 
 impl Default for RenderConfig {
+    #[allow(deprecated)]
     fn default() -> Self {
         Self {
             class_prefix: Cow::Borrowed("bbcode"),
             nofollow_links: true,
+            link_rel: vec!["nofollow".to_string()],
             open_links_in_new_tab: false,
+            internal_host: None,
+            url_aria_label_format: None,
+            max_display_url_len: None,
             sanitize: true,
             convert_linebreaks: true,
-            smilies: HashMap::new(),
+            collapse_blank_lines: None,
+            smilies: Map::new(),
+            max_smilies: None,
             allowed_schemes: vec![
                 "http".to_string(),
                 "https".to_string(),
                 "mailto".to_string(),
             ],
+            allow_relative_urls: false,
+            mailto_params: ["subject", "body", "cc", "bcc"].map(String::from).to_vec(),
+            obfuscate_emails: false,
+            list_stray_content: StrayContent::default(),
+            max_image_width: None,
+            max_image_height: None,
+            default_img_alt: Cow::Borrowed(""),
+            use_inline_styles: true,
+            merge_style_spans: false,
+            heading_base_level: 2,
+            quote_attribution_format: Cow::Borrowed("{author} wrote:"),
+            quote_author_as_link: false,
+            quote_source_label: Cow::Borrowed("Source"),
+            word_filters: Vec::new(),
+            word_filter_whole_word: true,
+            word_filter_case_insensitive: true,
+            code_line_numbers: false,
+            code_copy_button: false,
+            code_copy_label: Cow::Borrowed("Copy"),
+            code_class_format: Cow::Borrowed("language-{lang}"),
+            image_url_rewriter: None,
+            max_color_length: 32,
+            heading_anchors: false,
+            heading_id_prefix: Cow::Borrowed("heading-"),
+            heading_anchor_links: false,
+            escape_rejected_brackets: false,
+            size_mode: SizeMode::default(),
+            min_font_px: 8,
+            max_font_px: 36,
+            indent_step_px: 20,
+            max_indent_level: 5,
+            line_break_in_tables: false,
+            default_spoiler_label: Cow::Borrowed("Spoiler"),
+            spoilers_open_by_default: false,
+            spoiler_mode: SpoilerMode::default(),
+            output_mode: OutputMode::default(),
+            max_output_len: None,
+            user_link_resolver: None,
+            post_url_resolver: None,
+            tag_filter: None,
+            attachment_resolver: None,
         }
     }
 }
@@ -158,25 +1064,90 @@ impl Default for RenderConfig {
 pub struct Renderer {
     config: RenderConfig,
     registry: TagRegistry,
-    custom_handlers: HashMap<String, Arc<dyn CustomTagHandler>>,
+    custom_handlers: BTreeMap<String, Arc<dyn CustomTagHandler>>,
+    /// The configured smilie codes, for finding them in one pass.
+    smilie_trie: SmilieTrie,
+}
+
+/// Smilie codes stored as a byte trie, so text is scanned for all of them
+/// in a single left-to-right pass.
+struct SmilieTrie {
+    nodes: Vec<SmilieTrieNode>,
+}
+
+#[derive(Default)]
+struct SmilieTrieNode {
+    /// Child nodes by next byte.
+    next: Vec<(u8, usize)>,
+    /// Replacement of the code ending at this node.
+    replacement: Option<String>,
+}
+
+impl SmilieTrie {
+    fn new(smilies: &Map<String, String>) -> Self {
+        let mut nodes = vec![SmilieTrieNode::default()];
+        for (code, replacement) in smilies {
+            if code.is_empty() {
+                continue;
+            }
+            let mut node = 0;
+            for &byte in code.as_bytes() {
+                node = match nodes[node].next.iter().find(|(b, _)| *b == byte) {
+                    Some(&(_, child)) => child,
+                    None => {
+                        nodes.push(SmilieTrieNode::default());
+                        let child = nodes.len() - 1;
+                        nodes[node].next.push((byte, child));
+                        child
+                    }
+                };
+            }
+            nodes[node].replacement = Some(replacement.clone());
+        }
+        Self { nodes }
+    }
+
+    /// Returns the length and replacement of the longest code at the start
+    /// of `text`.
+    fn longest_match(&self, text: &[u8]) -> Option<(usize, &str)> {
+        let mut node = 0;
+        let mut found = None;
+        for (i, byte) in text.iter().enumerate() {
+            match self.nodes[node].next.iter().find(|(b, _)| b == byte) {
+                Some(&(_, child)) => node = child,
+                None => break,
+            }
+            if let Some(replacement) = &self.nodes[node].replacement {
+                found = Some((i + 1, replacement.as_str()));
+            }
+        }
+        found
+    }
 }
 
 impl Renderer {
     /// Creates a new renderer with default settings.
     pub fn new() -> Self {
-        Self {
-            config: RenderConfig::default(),
-            registry: TagRegistry::new(),
-            custom_handlers: HashMap::new(),
-        }
+        Self::with_config(RenderConfig::default())
     }
 
     /// Creates a new renderer with custom configuration.
     pub fn with_config(config: RenderConfig) -> Self {
+        Self::with_config_and_registry(config, TagRegistry::new())
+    }
+
+    /// Creates a new renderer with custom configuration and registry.
+    ///
+    /// Built-in tags missing from the registry are rendered as text, and
+    /// aliases added with [`TagRegistry::register_alias`] render like their
+    /// canonical tag. Pass the same registry to
+    /// [`Parser::with_registry`](crate::Parser::with_registry).
+    pub fn with_config_and_registry(config: RenderConfig, registry: TagRegistry) -> Self {
         Self {
+            smilie_trie: SmilieTrie::new(&config.smilies),
             config,
-            registry: TagRegistry::new(),
-            custom_handlers: HashMap::new(),
+            registry,
+            custom_handlers: BTreeMap::new(),
         }
     }
 
@@ -203,8 +1174,16 @@ impl Renderer {
     ///
     /// Call this before rendering if your custom handlers need to batch-fetch data.
     pub fn collect_from_document(&self, doc: &Document) {
-        for node in doc.iter() {
-            self.collect_from_node(node);
+        // Visit tags in document order without recursing
+        let mut stack: Vec<&Node> = doc.nodes.iter().rev().collect();
+        while let Some(node) = stack.pop() {
+            if let Node::Tag(tag) = node {
+                let name_lower = tag.name.to_ascii_lowercase();
+                if let Some(handler) = self.custom_handlers.get(&name_lower) {
+                    handler.collect(tag);
+                }
+                stack.extend(tag.children.iter().rev());
+            }
         }
         // Call prepare on all handlers after collection
         for handler in self.custom_handlers.values() {
@@ -212,119 +1191,619 @@ impl Renderer {
         }
     }
 
-    fn collect_from_node(&self, node: &Node) {
-        if let Node::Tag(tag) = node {
-            let name_lower = tag.name.to_ascii_lowercase();
-            if let Some(handler) = self.custom_handlers.get(&name_lower) {
-                handler.collect(tag);
-            }
-            for child in &tag.children {
-                self.collect_from_node(child);
-            }
-        }
-    }
-
     /// Creates a render context from the current configuration.
-    fn render_context(&self) -> RenderContext<'_> {
+    fn render_context<'s>(&'s self, output: &'s RenderState<'_, '_>) -> RenderContext<'s> {
         RenderContext {
             class_prefix: &self.config.class_prefix,
-            nofollow_links: self.config.nofollow_links,
+            nofollow_links: self.rel_tokens(false).contains(&"nofollow"),
             open_links_in_new_tab: self.config.open_links_in_new_tab,
             sanitize: self.config.sanitize,
             allowed_schemes: &self.config.allowed_schemes,
+            spoiler_depth: output.spoiler_depth,
+            data: &output.context,
         }
     }
 
     /// Renders a document to HTML.
     pub fn render(&self, doc: &Document) -> String {
         let mut output = String::new();
-        for node in doc.iter() {
-            self.render_node(node, &mut output);
-        }
+        // Writing to a String cannot fail
+        let _ = self.render_to(doc, &mut output);
         output
     }
 
-    /// Renders a single node to HTML.
-    fn render_node(&self, node: &Node, output: &mut String) {
-        match node {
-            Node::Text(text) => {
-                self.render_text(text, output);
-            }
-            Node::LineBreak => {
-                if self.config.convert_linebreaks {
-                    output.push_str("<br />");
-                } else {
-                    output.push('\n');
-                }
-            }
-            Node::AutoUrl(url) => {
-                self.render_auto_url(url, output);
-            }
-            Node::Tag(tag) => {
-                self.render_tag(tag, output);
-            }
-        }
+    /// Renders a document to HTML with per-render data that custom tags
+    /// read from [`RenderContext::data`], such as the current user.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use std::sync::Arc;
+    /// use bbcode::{
+    ///     escape_html, CustomTagDef, CustomTagHandler, Parser, RenderContext, Renderer, TagNode,
+    /// };
+    ///
+    /// struct Me;
+    ///
+    /// impl CustomTagHandler for Me {
+    ///     fn tag_name(&self) -> &str {
+    ///         "me"
+    ///     }
+    ///
+    ///     fn render(&self, _tag: &TagNode, ctx: &RenderContext, output: &mut String) -> bool {
+    ///         output.push_str(&escape_html(ctx.get("user").unwrap_or("Guest")));
+    ///         true
+    ///     }
+    /// }
+    ///
+    /// let mut parser = Parser::new();
+    /// parser.register_custom_tag(CustomTagDef::new("me"));
+    /// let mut renderer = Renderer::new();
+    /// renderer.register_handler(Arc::new(Me));
+    /// let doc = parser.parse("[me][/me]");
+    /// let context = HashMap::from([("user".to_string(), "Alice".to_string())]);
+    /// assert_eq!(renderer.render_with_context(&doc, &context), "Alice");
+    /// assert_eq!(renderer.render(&doc), "Guest");
+    /// ```
+    pub fn render_with_context(&self, doc: &Document, context: &Map<String, String>) -> String {
+        let mut output = String::new();
+        let mut state = RenderState::new(&mut output);
+        state.context = Cow::Borrowed(context);
+        state.remaining = self.config.max_output_len;
+        self.render_nodes(&doc.nodes, &mut state);
+        output
     }
 
-    /// Renders text content with HTML escaping.
-    fn render_text(&self, text: &str, output: &mut String) {
-        if self.config.sanitize {
-            output.push_str(&escape_html(text));
-        } else {
-            output.push_str(text);
+    /// Renders a document to HTML, propagating custom handler errors.
+    ///
+    /// Built-in tags never fail; an error is returned only when a
+    /// [`CustomTagHandler::try_render`] implementation reports one. By
+    /// contrast, [`render`](Self::render) outputs such tags as raw text.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::borrow::Cow;
+    /// use std::sync::Arc;
+    /// use bbcode::{CustomTagHandler, Parser, RenderContext, RenderError, Renderer, TagNode};
+    ///
+    /// struct UserLookup;
+    ///
+    /// impl CustomTagHandler for UserLookup {
+    ///     fn tag_name(&self) -> &str { "user" }
+    ///
+    ///     fn render(&self, _: &TagNode, _: &RenderContext, _: &mut String) -> bool {
+    ///         false
+    ///     }
+    ///
+    ///     fn try_render(
+    ///         &self,
+    ///         _tag: &TagNode,
+    ///         _ctx: &RenderContext,
+    ///         _output: &mut String,
+    ///     ) -> Result<bool, RenderError> {
+    ///         Err(RenderError::Generic { message: Cow::Borrowed("unknown user") })
+    ///     }
+    /// }
+    ///
+    /// let mut renderer = Renderer::new();
+    /// renderer.register_handler(Arc::new(UserLookup));
+    /// let doc = Parser::new().parse("[user=7]Bob[/user]");
+    /// assert!(renderer.try_render(&doc).is_err());
+    /// assert_eq!(renderer.render(&doc), "[user=7]Bob[/user]");
+    /// ```
+    pub fn try_render(&self, doc: &Document) -> Result<String, RenderError> {
+        let mut output = String::new();
+        let mut state = RenderState::new(&mut output);
+        state.strict = true;
+        state.remaining = self.config.max_output_len;
+        self.render_nodes(&doc.nodes, &mut state);
+        if let Some(error) = state.handler_error.take() {
+            return Err(error);
         }
+        // Writing to a String cannot fail
+        let _ = state.finish();
+        Ok(output)
     }
 
-    /// Renders an auto-detected URL.
-    fn render_auto_url(&self, url: &str, output: &mut String) {
-        let safe_url = escape_html(url);
-        write!(
-            output,
-            "<a class=\"{}-url\" href=\"{}\"{}{}>{}</a>",
-            self.config.class_prefix,
-            safe_url,
-            if self.config.nofollow_links {
-                " rel=\"nofollow\""
-            } else {
-                ""
-            },
-            if self.config.open_links_in_new_tab {
-                " target=\"_blank\""
-            } else {
-                ""
-            },
-            safe_url
-        )
-        .unwrap();
+    /// Renders a document to HTML, appending to any [`fmt::Write`] sink.
+    ///
+    /// This lets callers reuse one buffer across many documents or stream
+    /// straight into a template engine's writer.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bbcode::{Parser, Renderer};
+    ///
+    /// let parser = Parser::new();
+    /// let renderer = Renderer::new();
+    /// let mut html = String::new();
+    /// renderer.render_to(&parser.parse("[b]a[/b]"), &mut html).unwrap();
+    /// renderer.render_to(&parser.parse("[i]b[/i]"), &mut html).unwrap();
+    /// assert_eq!(html, "<strong>a</strong><em>b</em>");
+    /// ```
+    pub fn render_to<W: fmt::Write>(&self, doc: &Document, out: &mut W) -> fmt::Result {
+        let mut state = RenderState::new(out);
+        state.remaining = self.config.max_output_len;
+        self.render_nodes(&doc.nodes, &mut state);
+        state.finish()
     }
 
-    /// Renders a tag node.
-    fn render_tag(&self, tag: &TagNode, output: &mut String) {
-        // If broken, render as raw text
-        if tag.broken {
-            self.render_text(&tag.raw_open, output);
-            for child in &tag.children {
-                self.render_node(child, output);
-            }
-            if !tag.raw_close.is_empty() {
-                self.render_text(&tag.raw_close, output);
-            }
-            return;
-        }
+    /// Returns the canonical names of the built-in tags this renderer can
+    /// render, sorted alphabetically.
+    ///
+    /// Useful for building editor toolbars. Tags handled only by custom
+    /// handlers are not included.
+    pub fn supported_tags(&self) -> Vec<&'static str> {
+        let mut tags: Vec<&'static str> = RENDERED_TAGS
+            .iter()
+            .copied()
+            .filter(|name| self.registry.is_known(name))
+            .collect();
+        tags.sort_unstable();
+        tags
+    }
 
-        // Check custom handlers first
-        let name_lower = tag.name.to_ascii_lowercase();
-        if let Some(handler) = self.custom_handlers.get(&name_lower) {
-            let ctx = self.render_context();
-            if handler.render(tag, &ctx, output) {
-                return; // Custom handler processed the tag
-            }
+    /// Returns true if rendering `doc` produces no visible content.
+    ///
+    /// Content is visible when the HTML contains non-whitespace text or an
+    /// embedded media element such as `<img>`. Tags whose handlers render
+    /// nothing (for example suppressed media) therefore count as empty, which
+    /// lets a forum reject "post has no visible content" edits.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bbcode::{Parser, Renderer};
+    ///
+    /// let renderer = Renderer::new();
+    /// assert!(renderer.is_empty_render(&Parser::new().parse("[b] [/b]\n")));
+    /// assert!(!renderer.is_empty_render(&Parser::new().parse("[b]x[/b]")));
+    /// ```
+    pub fn is_empty_render(&self, doc: &Document) -> bool {
+        !has_visible_content(&self.render(doc))
+    }
+
+    /// Renders the start of a document, e.g. for a thread preview.
+    ///
+    /// Rendering stops once about `max_chars` characters of visible text
+    /// (including `[code]` content, but not image URLs) have been emitted.
+    /// Open tags are closed as usual, and `…` is appended after them when
+    /// anything was cut off. Links whose text is their URL are never cut;
+    /// one that doesn't fit is left out.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bbcode::{Parser, Renderer};
+    ///
+    /// let doc = Parser::new().parse("[b]Hello world[/b] and more");
+    /// assert_eq!(
+    ///     Renderer::new().render_excerpt(&doc, 5),
+    ///     "<strong>Hello</strong>\u{2026}"
+    /// );
+    /// ```
+    pub fn render_excerpt(&self, doc: &Document, max_chars: usize) -> String {
+        let (excerpt, truncated) = truncate_document(doc, max_chars, &self.registry);
+        let mut html = self.render(&excerpt);
+        if truncated {
+            html.push('\u{2026}');
         }
+        html
+    }
 
-        // Look up tag definition
-        let _tag_def = self.registry.get(&tag.name);
+    /// Renders a document as plain text with all formatting stripped.
+    ///
+    /// Useful for notification emails and search indexing. Text is not
+    /// HTML-escaped, line breaks become `\n`, block-level tags are separated
+    /// by newlines, code blocks are included verbatim and images contribute
+    /// their alt text (if any).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bbcode::{Parser, Renderer};
+    ///
+    /// let doc = Parser::new().parse("[b]Hi[/b] [url=https://x.com]link[/url]");
+    /// assert_eq!(Renderer::new().render_text_only(&doc), "Hi link");
+    /// ```
+    pub fn render_text_only(&self, doc: &Document) -> String {
+        let mut output = String::new();
+        let mut work: Vec<PlainWork> = doc.nodes.iter().rev().map(PlainWork::Node).collect();
+        while let Some(item) = work.pop() {
+            match item {
+                PlainWork::Node(node) => self.render_plaintext_node(node, &mut output, &mut work),
+                PlainWork::Text(text) => output.push_str(text),
+                PlainWork::EndBlock => {
+                    if !output.ends_with('\n') {
+                        output.push('\n');
+                    }
+                }
+            }
+        }
+        let trimmed_len = output.trim_end().len();
+        output.truncate(trimmed_len);
+        output
+    }
 
-        match &*tag.name {
+    /// Renders a document as Markdown, for migrating content to
+    /// Markdown-based systems.
+    ///
+    /// Bold, italic, links, images, code, quotes and lists map to their
+    /// CommonMark equivalents; other tags contribute only their content.
+    /// Markdown syntax in text is backslash-escaped.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bbcode::{Parser, Renderer};
+    ///
+    /// let doc = Parser::new().parse("[b]Hi[/b] [url=https://x.com]link[/url]");
+    /// assert_eq!(
+    ///     Renderer::new().render_markdown(&doc),
+    ///     "**Hi** [link](https://x.com)"
+    /// );
+    /// ```
+    pub fn render_markdown(&self, doc: &Document) -> String {
+        let mut output = MarkdownWriter::new();
+        let mut work: Vec<MarkdownWork> = doc.nodes.iter().rev().map(MarkdownWork::Node).collect();
+        while let Some(item) = work.pop() {
+            match item {
+                MarkdownWork::Node(node) => self.render_markdown_node(node, &mut output, &mut work),
+                MarkdownWork::Item(tag, marker) => {
+                    output.block(1);
+                    output.push_str(marker);
+                    output.item_start = true;
+                    work.push(MarkdownWork::EndPrefix(output.prefix.len()));
+                    output.prefix.push_str(&" ".repeat(marker.len()));
+                    work.extend(tag.children.iter().rev().map(MarkdownWork::Node));
+                }
+                MarkdownWork::Markup(markup) => output.push_str(&markup),
+                MarkdownWork::CloseEmphasis(marker) => output.close_emphasis(marker),
+                MarkdownWork::Text(text) => output.push_text(text),
+                MarkdownWork::EndBlock(breaks) => output.block(breaks),
+                MarkdownWork::EndPrefix(len) => output.prefix.truncate(len),
+            }
+        }
+        output.out
+    }
+
+    /// Renders nodes to HTML using an explicit work stack, so nesting depth is
+    /// bounded by the heap rather than the call stack.
+    fn render_nodes<'d>(&self, nodes: &'d [Node<'d>], output: &mut RenderState<'_, 'd>) {
+        let mut work = vec![Work::Nodes(nodes)];
+        while let Some(item) = work.pop() {
+            if output.result.is_err() {
+                break;
+            }
+            let nodes = match item {
+                // Already reserved against `max_output_len`, so elements
+                // opened before truncation are still closed
+                Work::Html(html) => {
+                    output.write_out(&html);
+                    continue;
+                }
+                Work::LeaveSpoiler => {
+                    output.spoiler_depth -= 1;
+                    continue;
+                }
+                Work::Nodes(_) if output.truncated => continue,
+                Work::Nodes(nodes) => nodes,
+            };
+            let Some((node, rest)) = nodes.split_first() else {
+                continue;
+            };
+
+            work.push(Work::Nodes(rest));
+            self.render_node(node, output);
+            let start = work.len();
+            work.extend(output.take_pending());
+            work[start..].reverse();
+        }
+    }
+
+    /// Renders a single node to HTML, deferring its children.
+    fn render_node<'d>(&self, node: &'d Node, output: &mut RenderState<'_, 'd>) {
+        match node {
+            Node::LineBreak => output.line_break_run += 1,
+            Node::Text(text) if text.trim().is_empty() => {}
+            _ => output.line_break_run = 0,
+        }
+
+        match node {
+            Node::Text(text) if !self.config.smilies.is_empty() => {
+                self.render_smilie_text(text, output);
+            }
+            Node::Text(text) => {
+                self.render_visible_text(text, output);
+            }
+            Node::LineBreak
+                if self
+                    .config
+                    .collapse_blank_lines
+                    .is_some_and(|max| output.line_break_run > max) => {}
+            Node::LineBreak => {
+                if self.config.convert_linebreaks {
+                    output.push_str("<br");
+                    output.push_str(self.void_end());
+                } else {
+                    output.push('\n');
+                }
+            }
+            Node::AutoUrl(url, kind) => {
+                self.render_auto_url(url, *kind, output);
+            }
+            Node::Tag(tag) => {
+                self.render_tag(tag, output);
+            }
+        }
+    }
+
+    /// Renders text content with HTML escaping.
+    fn render_text(&self, text: &str, output: &mut RenderState<'_, '_>) {
+        if self.config.sanitize {
+            output.push_str(&escape_html_text(text));
+        } else {
+            output.push_str(text);
+        }
+    }
+
+    /// Renders visible text, applying word filters when any are configured.
+    fn render_visible_text(&self, text: &str, output: &mut RenderState<'_, '_>) {
+        if self.config.word_filters.is_empty() {
+            self.render_text(text, output);
+        } else {
+            self.render_filtered_text(text, output);
+        }
+    }
+
+    /// Renders visible text with smilie codes replaced, up to `max_smilies`.
+    /// At each position the longest code wins; once the limit is reached
+    /// the rest is plain text.
+    fn render_smilie_text(&self, text: &str, output: &mut RenderState<'_, '_>) {
+        let bytes = text.as_bytes();
+        // Start of the text not written yet
+        let mut plain = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            if self
+                .config
+                .max_smilies
+                .is_some_and(|max| output.smilie_count >= max)
+            {
+                break;
+            }
+            // Codes start on a character boundary, so matches do too
+            match self.smilie_trie.longest_match(&bytes[i..]) {
+                Some((len, replacement)) => {
+                    self.render_visible_text(&text[plain..i], output);
+                    output.smilie_count += 1;
+                    self.render_smilie(&text[i..i + len], replacement, output);
+                    i += len;
+                    plain = i;
+                }
+                None => i += 1,
+            }
+        }
+        self.render_visible_text(&text[plain..], output);
+    }
+
+    /// Renders a single smilie: an image when the replacement is a valid URL,
+    /// otherwise the replacement as text (e.g. an emoji).
+    fn render_smilie(&self, code: &str, replacement: &str, output: &mut RenderState<'_, '_>) {
+        if replacement.contains('/')
+            && is_valid_url(replacement, &self.config.allowed_schemes, true)
+        {
+            let code = escape_html(code);
+            write!(
+                output,
+                "<img class=\"{}-smilie\" src=\"{}\" alt=\"{}\" title=\"{}\"{}",
+                self.config.class_prefix,
+                escape_html(replacement),
+                code,
+                code,
+                self.void_end()
+            )
+            .unwrap();
+        } else {
+            self.render_text(replacement, output);
+        }
+    }
+
+    /// Renders visible text with the configured word filters applied.
+    fn render_filtered_text(&self, text: &str, output: &mut RenderState<'_, '_>) {
+        // Start of the text not written yet
+        let mut plain = 0;
+        let mut pos = 0;
+        while pos < text.len() {
+            let found = self.config.word_filters.iter().find(|(pattern, _)| {
+                word_at(
+                    text,
+                    pos,
+                    pattern,
+                    self.config.word_filter_whole_word,
+                    self.config.word_filter_case_insensitive,
+                )
+            });
+            match found {
+                Some((pattern, replacement)) => {
+                    self.render_text(&text[plain..pos], output);
+                    self.render_text(replacement, output);
+                    pos += pattern.len();
+                    plain = pos;
+                }
+                None => pos += 1,
+            }
+        }
+        self.render_text(&text[plain..], output);
+    }
+
+    /// Renders an auto-detected URL.
+    fn render_auto_url(&self, url: &str, kind: AutoLinkKind, output: &mut RenderState<'_, '_>) {
+        let safe_url = escape_html(url);
+        let prefix = &self.config.class_prefix;
+
+        // Bare emails and www hosts (if the parser detects them) are
+        // displayed as written, with the scheme inferred for the href
+        let inferred = match kind {
+            AutoLinkKind::Url => "",
+            AutoLinkKind::Www => "https://",
+            AutoLinkKind::Email => {
+                write!(output, "<a class=\"{}-email\" href=\"", prefix).unwrap();
+                self.render_email_text("mailto:", output);
+                self.render_email_text(url, output);
+                output.push_str("\">");
+                self.render_email_text(url, output);
+                output.push_str("</a>");
+                return;
+            }
+        };
+
+        // Links with a scheme that isn't allowed stay text
+        let href = format!("{}{}", inferred, url);
+        if !self.is_allowed_url(&href) {
+            self.render_visible_text(url, output);
+            return;
+        }
+
+        write!(
+            output,
+            "<a class=\"{}-url\" href=\"{}{}\"",
+            prefix, inferred, safe_url
+        )
+        .unwrap();
+        self.render_url_aria_label(&href, output);
+        self.render_link_attrs(&href, output);
+        write!(output, ">{}</a>", escape_html(&self.display_url(url))).unwrap();
+    }
+
+    /// Shortens a URL shown as link text to `max_display_url_len`.
+    fn display_url<'u>(&self, url: &'u str) -> Cow<'u, str> {
+        match self.config.max_display_url_len {
+            Some(max) if url.chars().count() > max => {
+                let mut short: String = url.chars().take(max.saturating_sub(1)).collect();
+                short.push('\u{2026}');
+                Cow::Owned(short)
+            }
+            _ => Cow::Borrowed(url),
+        }
+    }
+
+    /// Writes an `aria-label` from `url_aria_label_format` for a link whose
+    /// text is `url` itself.
+    fn render_url_aria_label(&self, url: &str, output: &mut RenderState<'_, '_>) {
+        let Some(format) = &self.config.url_aria_label_format else {
+            return;
+        };
+        let Some(host) = url_host(url) else {
+            return;
+        };
+        let label = format.replace("{host}", host);
+        write!(output, " aria-label=\"{}\"", escape_html(&label)).unwrap();
+    }
+
+    /// Returns the effective `rel` tokens for links, opening in a new tab
+    /// if `new_tab`.
+    #[allow(deprecated)]
+    fn rel_tokens(&self, new_tab: bool) -> Vec<&str> {
+        // The deprecated switch only applies to the default `link_rel`
+        let default_rel = self.config.link_rel == ["nofollow"];
+        let mut tokens: Vec<&str> = Vec::with_capacity(self.config.link_rel.len() + 1);
+        for token in &self.config.link_rel {
+            let token = token.as_str();
+            if !token.is_empty()
+                && !tokens.contains(&token)
+                && (self.config.nofollow_links || !default_rel)
+            {
+                tokens.push(token);
+            }
+        }
+        if new_tab && !tokens.contains(&"noopener") {
+            tokens.push("noopener");
+        }
+        tokens
+    }
+
+    /// Returns true if a user-supplied URL may be linked or embedded.
+    fn is_allowed_url(&self, url: &str) -> bool {
+        is_valid_url(
+            url,
+            &self.config.allowed_schemes,
+            self.config.allow_relative_urls,
+        )
+    }
+
+    /// Writes the `rel` and `target` attributes shared by all links.
+    fn render_link_attrs(&self, url: &str, output: &mut RenderState<'_, '_>) {
+        let new_tab = self.config.open_links_in_new_tab && !self.is_internal_url(url);
+        let tokens = self.rel_tokens(new_tab);
+        if !tokens.is_empty() {
+            write!(output, " rel=\"{}\"", escape_html(&tokens.join(" "))).unwrap();
+        }
+        if new_tab {
+            output.push_str(" target=\"_blank\"");
+        }
+    }
+
+    /// Returns true if `url` points at [`RenderConfig::internal_host`].
+    fn is_internal_url(&self, url: &str) -> bool {
+        // Relative links (`/t/1`, `?page=2`) stay on the site
+        let url = url.trim();
+        let has_scheme = url
+            .find([':', '/', '?', '#'])
+            .is_some_and(|end| url.as_bytes()[end] == b':');
+        if !has_scheme && !url.starts_with("//") {
+            return true;
+        }
+        match (&self.config.internal_host, url_host(url)) {
+            (Some(internal), Some(host)) => host.eq_ignore_ascii_case(internal.trim()),
+            _ => false,
+        }
+    }
+
+    /// Renders a tag node.
+    fn render_tag<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
+        match self.tag_decision(tag) {
+            TagDecision::Render => {}
+            TagDecision::AsText => return self.render_as_text(tag, output),
+            TagDecision::Skip => return,
+        }
+
+        // If broken, render as raw text
+        if tag.broken {
+            self.render_as_text(tag, output);
+            return;
+        }
+
+        // Check custom handlers first
+        let name_lower = tag.name.to_ascii_lowercase();
+        if let Some(handler) = self.custom_handlers.get(&name_lower) {
+            let ctx = self.render_context(output);
+            let mut html = String::new();
+            match handler.try_render(tag, &ctx, &mut html) {
+                Ok(true) => {
+                    output.push_str(&html);
+                    return; // Custom handler processed the tag
+                }
+                Ok(false) => {}
+                Err(error) if output.strict => return output.fail(error),
+                Err(_) => return self.render_as_text(tag, output),
+            }
+        }
+
+        if self.config.merge_style_spans && self.render_merged_styles(tag, output) {
+            return;
+        }
+
+        // Only tags in the registry get their built-in rendering, so removing
+        // a tag from the registry disables it; aliases render like the tag
+        // they stand for
+        let resolved = self.registry.resolve(&tag.name);
+        let name = resolved.as_ref().map_or("", ResolvedTag::name);
+
+        match name {
             // Basic formatting
             "b" | "bold" => self.render_simple_tag(tag, "strong", output),
             "i" | "italic" => self.render_simple_tag(tag, "em", output),
@@ -332,6 +1811,8 @@ impl Renderer {
             "s" | "strike" | "strikethrough" => self.render_simple_tag(tag, "s", output),
             "sub" => self.render_simple_tag(tag, "sub", output),
             "sup" => self.render_simple_tag(tag, "sup", output),
+            "abbr" => self.render_abbr(tag, output),
+            "highlight" | "mark" => self.render_simple_tag(tag, "mark", output),
 
             // Color and font
             "color" | "colour" => self.render_color(tag, output),
@@ -354,7 +1835,9 @@ impl Renderer {
             "plain" | "noparse" | "nobbc" => self.render_plain(tag, output),
 
             // Lists
-            "list" => self.render_list(tag, output),
+            "list" => self.render_list(tag, None, output),
+            "ol" => self.render_list(tag, Some(true), output),
+            "ul" => self.render_list(tag, Some(false), output),
             "*" | "li" => self.render_list_item(tag, output),
 
             // Alignment
@@ -363,16 +1846,18 @@ impl Renderer {
             "right" => self.render_align(tag, "right", output),
             "justify" => self.render_align(tag, "justify", output),
             "indent" => self.render_indent(tag, output),
+            "float" => self.render_float(tag, output),
 
             // Headings
             "heading" | "h" => self.render_heading(tag, output),
 
             // Special
-            "hr" => output.push_str("<hr />"),
-            "br" => output.push_str("<br />"),
+            "hr" => write!(output, "<hr{}", self.void_end()).unwrap(),
+            "br" => write!(output, "<br{}", self.void_end()).unwrap(),
             "spoiler" => self.render_spoiler(tag, output),
             "ispoiler" => self.render_ispoiler(tag, output),
             "user" | "member" => self.render_user(tag, output),
+            "attach" | "attachment" => self.render_attachment(tag, output),
 
             // Tables
             "table" => self.render_table(tag, output),
@@ -383,9 +1868,7 @@ impl Renderer {
             // Unknown tag - render as text
             _ => {
                 self.render_text(&tag.raw_open, output);
-                for child in &tag.children {
-                    self.render_node(child, output);
-                }
+                output.defer(&tag.children);
                 if !tag.raw_close.is_empty() {
                     self.render_text(&tag.raw_close, output);
                 }
@@ -394,38 +1877,164 @@ impl Renderer {
     }
 
     /// Renders a simple tag like <strong>, <em>, etc.
-    fn render_simple_tag(&self, tag: &TagNode, html_tag: &str, output: &mut String) {
+    fn render_simple_tag<'d>(
+        &self,
+        tag: &'d TagNode,
+        html_tag: &str,
+        output: &mut RenderState<'_, 'd>,
+    ) {
         write!(output, "<{}>", html_tag).unwrap();
         self.render_children(tag, output);
         write!(output, "</{}>", html_tag).unwrap();
     }
 
-    /// Renders all children of a tag.
-    fn render_children(&self, tag: &TagNode, output: &mut String) {
-        for child in &tag.children {
-            self.render_node(child, output);
+    fn render_abbr<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
+        match tag.option.as_scalar() {
+            Some(title) => write!(output, "<abbr title=\"{}\">", escape_html(title)).unwrap(),
+            None => output.push_str("<abbr>"),
         }
+        self.render_children(tag, output);
+        output.push_str("</abbr>");
+    }
+
+    /// Renders all children of a tag.
+    fn render_children<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
+        output.defer(&tag.children);
+    }
+
+    /// Returns true if a color value is valid and within the length limit.
+    fn is_allowed_color(&self, color: &str) -> bool {
+        color.len() <= self.config.max_color_length && is_valid_color(color)
     }
 
     /// Gets the inner text of a tag (for verbatim content).
     fn get_inner_text<'a>(&self, tag: &TagNode<'a>) -> Cow<'a, str> {
-        tag.inner_text()
+        normalize_newlines(tag.inner_text())
+    }
+
+    /// Returns the class suffix and CSS declaration for a valid style tag.
+    fn style_declaration(&self, tag: &TagNode) -> Option<(&'static str, String)> {
+        if tag.broken
+            || self.custom_handlers.contains_key(&*tag.name)
+            || !self.registry.is_known(&tag.name)
+        {
+            return None;
+        }
+        let value = tag.option.as_scalar()?;
+        match &*tag.name {
+            "color" | "colour" if self.is_allowed_color(value) => {
+                Some(("color", format!("color: {};", escape_html(value))))
+            }
+            "font" if is_valid_font(value) => {
+                Some(("font", format!("font-family: {};", font_family_css(value))))
+            }
+            "size" => self
+                .css_font_size(value)
+                .map(|size| ("size", format!("font-size: {};", size))),
+            _ => None,
+        }
+    }
+
+    /// Asks [`RenderConfig::tag_filter`] what to do with a tag.
+    fn tag_decision(&self, tag: &TagNode) -> TagDecision {
+        self.config
+            .tag_filter
+            .as_ref()
+            .map_or(TagDecision::Render, |filter| filter(tag))
+    }
+
+    /// Renders a chain of directly nested style tags as one `<span>`.
+    ///
+    /// Returns false (writing nothing) when the tag does not start a chain of
+    /// at least two valid style tags. Each nested tag is passed to the tag
+    /// filter first, and the chain ends at one the filter doesn't render.
+    fn render_merged_styles<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) -> bool {
+        if !self.config.use_inline_styles {
+            return false;
+        }
+
+        let mut classes: Vec<&str> = Vec::new();
+        let mut styles: Vec<String> = Vec::new();
+        let mut current = tag;
+        let mut filtered = None;
+
+        while let Some((class, style)) = self.style_declaration(current) {
+            if !classes.contains(&class) {
+                classes.push(class);
+            }
+            styles.push(style);
+
+            match current.children.as_slice() {
+                [Node::Tag(child)] if self.style_declaration(child).is_some() => {
+                    match self.tag_decision(child) {
+                        TagDecision::Render => current = child,
+                        decision => {
+                            filtered = Some((child, decision));
+                            break;
+                        }
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        // A single style renders the same merged or not, and the filter
+        // must not be asked twice about the child
+        if styles.len() < 2 && filtered.is_none() {
+            return false;
+        }
+
+        output.push_str("<span class=\"");
+        for (i, class) in classes.iter().enumerate() {
+            if i > 0 {
+                output.push(' ');
+            }
+            write!(output, "{}-{}", self.config.class_prefix, class).unwrap();
+        }
+        write!(output, "\" style=\"{}\">", styles.join(" ")).unwrap();
+        match filtered {
+            Some((child, TagDecision::AsText)) => self.render_as_text(child, output),
+            Some(_) => {}
+            None => self.render_children(current, output),
+        }
+        output.push_str("</span>");
+        true
     }
 
     // ============================================================================
     // Specific tag renderers
     // ============================================================================
 
-    fn render_color(&self, tag: &TagNode, output: &mut String) {
+    fn render_color<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
         if let Some(color) = tag.option.as_scalar() {
-            if is_valid_color(color) {
-                write!(
-                    output,
-                    "<span class=\"{}-color\" style=\"color: {};\">",
-                    self.config.class_prefix,
-                    escape_html(color)
-                )
-                .unwrap();
+            if self.is_allowed_color(color) {
+                let prefix = &self.config.class_prefix;
+                if self.config.use_inline_styles {
+                    write!(
+                        output,
+                        "<span class=\"{}-color\" style=\"color: {};\">",
+                        prefix,
+                        escape_html(color)
+                    )
+                    .unwrap();
+                } else if color.bytes().all(|b| b.is_ascii_alphabetic()) {
+                    write!(
+                        output,
+                        "<span class=\"{}-color {}-color-{}\">",
+                        prefix,
+                        prefix,
+                        color.to_ascii_lowercase()
+                    )
+                    .unwrap();
+                } else {
+                    write!(
+                        output,
+                        "<span class=\"{}-color\" data-color=\"{}\">",
+                        prefix,
+                        escape_html(color)
+                    )
+                    .unwrap();
+                }
                 self.render_children(tag, output);
                 output.push_str("</span>");
                 return;
@@ -435,14 +2044,14 @@ impl Renderer {
         self.render_as_text(tag, output);
     }
 
-    fn render_font(&self, tag: &TagNode, output: &mut String) {
+    fn render_font<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
         if let Some(font) = tag.option.as_scalar() {
             if is_valid_font(font) {
                 write!(
                     output,
                     "<span class=\"{}-font\" style=\"font-family: {};\">",
                     self.config.class_prefix,
-                    escape_html(font)
+                    font_family_css(font)
                 )
                 .unwrap();
                 self.render_children(tag, output);
@@ -453,15 +2062,42 @@ impl Renderer {
         self.render_as_text(tag, output);
     }
 
-    fn render_size(&self, tag: &TagNode, output: &mut String) {
+    /// Returns the CSS `font-size` for a `[size]` option, if valid.
+    fn css_font_size(&self, size: &str) -> Option<String> {
+        parse_size(
+            size,
+            self.config.size_mode,
+            self.config.min_font_px,
+            self.config.max_font_px,
+        )
+    }
+
+    fn render_size<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
         if let Some(size) = tag.option.as_scalar() {
-            if let Some(css_size) = parse_size(size) {
-                write!(
-                    output,
-                    "<span class=\"{}-size\" style=\"font-size: {};\">",
-                    self.config.class_prefix, css_size
-                )
-                .unwrap();
+            if let Some(css_size) = self.css_font_size(size) {
+                let prefix = &self.config.class_prefix;
+                if self.config.use_inline_styles {
+                    write!(
+                        output,
+                        "<span class=\"{}-size\" style=\"font-size: {};\">",
+                        prefix, css_size
+                    )
+                    .unwrap();
+                } else if matches!(size.parse::<u8>(), Ok(1..=7)) {
+                    write!(
+                        output,
+                        "<span class=\"{}-size {}-size-{}\">",
+                        prefix, prefix, size
+                    )
+                    .unwrap();
+                } else {
+                    write!(
+                        output,
+                        "<span class=\"{}-size\" data-size=\"{}\">",
+                        prefix, css_size
+                    )
+                    .unwrap();
+                }
                 self.render_children(tag, output);
                 output.push_str("</span>");
                 return;
@@ -470,15 +2106,20 @@ impl Renderer {
         self.render_as_text(tag, output);
     }
 
-    fn render_url(&self, tag: &TagNode, output: &mut String) {
-        // URL can be in option or content
-        let url = if let Some(opt) = tag.option.as_scalar() {
-            opt.clone()
-        } else {
-            tag.inner_text()
+    fn render_url<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
+        // URL can be in option or content, with an optional title:
+        // [url=x title=Foo] or [url title=Foo]x[/url]
+        let (url, title) = match &tag.option {
+            TagOption::Scalar(opt) => {
+                let (url, title) = split_url_title(opt);
+                (Cow::Borrowed(url), title)
+            }
+            TagOption::Map(map) => (tag.inner_text(), map.get("title").map(|t| t.as_ref())),
+            TagOption::None => (tag.inner_text(), None),
         };
 
-        if !is_valid_url(&url, &self.config.allowed_schemes) {
+        let href = with_inferred_scheme(&url);
+        if !self.is_allowed_url(&href) {
             self.render_as_text(tag, output);
             return;
         }
@@ -487,49 +2128,57 @@ impl Renderer {
             output,
             "<a class=\"{}-url\" href=\"{}\"",
             self.config.class_prefix,
-            escape_html(&url)
+            escape_html(&href)
         )
         .unwrap();
-
-        if self.config.nofollow_links {
-            output.push_str(" rel=\"nofollow\"");
+        if let Some(title) = title.filter(|t| !t.is_empty()) {
+            write!(output, " title=\"{}\"", escape_html(title)).unwrap();
         }
-        if self.config.open_links_in_new_tab {
-            output.push_str(" target=\"_blank\"");
+
+        // A URL in the content is displayed as is, unless it is the source of
+        // a child such as [img], which links the rendered image to itself
+        let shows_url =
+            !tag.option.is_scalar() && tag.children.iter().all(|n| n.as_tag().is_none());
+        if shows_url {
+            self.render_url_aria_label(&href, output);
         }
+        self.render_link_attrs(&href, output);
         output.push('>');
 
-        if tag.option.is_scalar() {
-            self.render_children(tag, output);
+        if shows_url {
+            self.render_text(&self.display_url(&url), output);
         } else {
-            // URL is the content, display it
-            self.render_text(&url, output);
+            self.render_children(tag, output);
         }
 
         output.push_str("</a>");
     }
 
-    fn render_email(&self, tag: &TagNode, output: &mut String) {
+    fn render_email<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
         let email = if let Some(opt) = tag.option.as_scalar() {
             opt.clone()
         } else {
             tag.inner_text()
         };
+        let (address, query) = match email.split_once('?') {
+            Some((address, query)) => (address, Some(query)),
+            None => (email.as_ref(), None),
+        };
 
         // Email validation - must contain @ and no dangerous characters
         // Block quotes, angle brackets, and event handler patterns
-        if !email.contains('@')
-            || email.contains('<')
-            || email.contains('>')
-            || email.contains('"')
-            || email.contains('\'')
+        if !address.contains('@')
+            || address.contains('<')
+            || address.contains('>')
+            || address.contains('"')
+            || address.contains('\'')
         {
             self.render_as_text(tag, output);
             return;
         }
 
         // Also check for event handler injection in email
-        let lower = email.to_ascii_lowercase();
+        let lower = address.to_ascii_lowercase();
         if lower.contains("onclick=")
             || lower.contains("onerror=")
             || lower.contains("onmouseover=")
@@ -542,29 +2191,79 @@ impl Renderer {
 
         write!(
             output,
-            "<a class=\"{}-email\" href=\"mailto:{}\">",
-            self.config.class_prefix,
-            escape_html(&email)
+            "<a class=\"{}-email\" href=\"",
+            self.config.class_prefix
         )
         .unwrap();
+        self.render_email_text("mailto:", output);
+        self.render_email_text(address, output);
+        if let Some(query) = query {
+            self.render_mailto_query(query, output);
+        }
+        output.push_str("\">");
 
         if tag.option.is_scalar() {
             self.render_children(tag, output);
         } else {
-            self.render_text(&email, output);
+            self.render_email_text(&email, output);
         }
 
         output.push_str("</a>");
     }
 
-    fn render_img(&self, tag: &TagNode, output: &mut String) {
+    /// Writes part of an email link, as character references if
+    /// `obfuscate_emails` is set.
+    fn render_email_text(&self, text: &str, output: &mut RenderState<'_, '_>) {
+        if self.config.obfuscate_emails {
+            for c in text.chars() {
+                write!(output, "&#{};", u32::from(c)).unwrap();
+            }
+        } else {
+            output.push_str(&escape_html(text));
+        }
+    }
+
+    /// Writes the allowlisted `mailto:` query parameters, re-encoding their
+    /// values. Parameters not in `mailto_params` are dropped.
+    fn render_mailto_query(&self, query: &str, output: &mut RenderState<'_, '_>) {
+        // The href is HTML, so later pairs are joined with an escaped `&`
+        let mut separator = "?";
+        for pair in query.split('&') {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let key = key.trim().to_ascii_lowercase();
+            if !self
+                .config
+                .mailto_params
+                .iter()
+                .any(|param| param.eq_ignore_ascii_case(&key))
+            {
+                continue;
+            }
+            write!(
+                output,
+                "{}{}={}",
+                separator,
+                key,
+                percent_encode(&percent_decode(value))
+            )
+            .unwrap();
+            separator = "&amp;";
+        }
+    }
+
+    fn render_img<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
         let url = tag.inner_text();
 
-        if url.is_empty() || !is_valid_url(&url, &self.config.allowed_schemes) {
+        if url.is_empty() || !self.is_allowed_url(&url) {
             self.render_as_text(tag, output);
             return;
         }
 
+        let url = match &self.config.image_url_rewriter {
+            Some(rewrite) => Cow::Owned(rewrite(&url)),
+            None => url,
+        };
+
         write!(
             output,
             "<img class=\"{}-img\" src=\"{}\"",
@@ -576,24 +2275,79 @@ impl Renderer {
         // Handle dimensions from option
         if let Some(opt) = tag.option.as_scalar() {
             if let Some((width, height)) = parse_dimensions(opt) {
+                let (width, height) = self.clamp_image_dimensions(width, height);
                 write!(output, " width=\"{}\" height=\"{}\"", width, height).unwrap();
             }
         } else if let Some(map) = tag.option.as_map() {
-            if let Some(width) = map.get("width") {
-                write!(output, " width=\"{}\"", escape_html(width)).unwrap();
+            let width = map.get("width");
+            let height = map.get("height");
+            let numeric = (
+                width.and_then(|w| w.parse::<u32>().ok()),
+                height.and_then(|h| h.parse::<u32>().ok()),
+            );
+
+            if let (Some(w), Some(h)) = numeric {
+                let (w, h) = self.clamp_image_dimensions(w, h);
+                write!(output, " width=\"{}\" height=\"{}\"", w, h).unwrap();
+            } else {
+                if let Some(w) = numeric.0 {
+                    let w = self.config.max_image_width.map_or(w, |max| w.min(max));
+                    write!(output, " width=\"{}\"", w).unwrap();
+                } else if let Some(width) = width {
+                    write!(output, " width=\"{}\"", escape_html(width)).unwrap();
+                }
+                if let Some(h) = numeric.1 {
+                    let h = self.config.max_image_height.map_or(h, |max| h.min(max));
+                    write!(output, " height=\"{}\"", h).unwrap();
+                } else if let Some(height) = height {
+                    write!(output, " height=\"{}\"", escape_html(height)).unwrap();
+                }
             }
-            if let Some(height) = map.get("height") {
-                write!(output, " height=\"{}\"", escape_html(height)).unwrap();
+        }
+
+        let map = tag.option.as_map();
+        // An explicit `alt=""` marks a decorative image, so it is kept
+        let alt = match map.and_then(|map| map.get("alt")) {
+            Some(alt) => Some(&**alt),
+            None => Some(&*self.config.default_img_alt).filter(|alt| !alt.is_empty()),
+        };
+        if let Some(alt) = alt {
+            write!(output, " alt=\"{}\"", escape_html(alt)).unwrap();
+        }
+        if let Some(title) = map.and_then(|map| map.get("title")) {
+            write!(output, " title=\"{}\"", escape_html(title)).unwrap();
+        }
+
+        output.push_str(self.void_end());
+    }
+
+    /// Returns the end of a void element such as `<br>` for the output mode.
+    fn void_end(&self) -> &'static str {
+        match self.config.output_mode {
+            OutputMode::Xhtml => " />",
+            OutputMode::Html5 => ">",
+        }
+    }
+
+    /// Clamps image dimensions to the configured maximums, preserving the
+    /// aspect ratio.
+    fn clamp_image_dimensions(&self, mut width: u32, mut height: u32) -> (u32, u32) {
+        if let Some(max) = self.config.max_image_width {
+            if width > max {
+                height = (u64::from(height) * u64::from(max) / u64::from(width)) as u32;
+                width = max;
             }
-            if let Some(alt) = map.get("alt") {
-                write!(output, " alt=\"{}\"", escape_html(alt)).unwrap();
+        }
+        if let Some(max) = self.config.max_image_height {
+            if height > max {
+                width = (u64::from(width) * u64::from(max) / u64::from(height)) as u32;
+                height = max;
             }
         }
-
-        output.push_str(" />");
+        (width, height)
     }
 
-    fn render_quote(&self, tag: &TagNode, output: &mut String) {
+    fn render_quote<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
         write!(
             output,
             "<blockquote class=\"{}-quote\"",
@@ -601,22 +2355,73 @@ impl Renderer {
         )
         .unwrap();
 
-        // Handle quote author
-        if let Some(author) = tag.option.as_scalar() {
-            write!(output, " data-author=\"{}\"", escape_html(author)).unwrap();
+        // Handle quote author and XenForo post metadata
+        let source = tag
+            .option
+            .as_scalar()
+            .map(|option| QuoteSource::parse(option));
+        if let Some(source) = &source {
+            write!(output, " data-author=\"{}\"", escape_html(source.author)).unwrap();
+            if let Some(post) = source.post {
+                write!(output, " data-post=\"{}\"", post).unwrap();
+            }
+            if let Some(member) = source.member {
+                write!(output, " data-member=\"{}\"", member).unwrap();
+            }
         }
+        self.render_cite_attr(tag, output);
 
         output.push('>');
 
         // If author is present, add a header
-        if let Some(author) = tag.option.as_scalar() {
-            write!(
-                output,
-                "<div class=\"{}-quote-author\">{} wrote:</div>",
-                self.config.class_prefix,
-                escape_html(author)
-            )
-            .unwrap();
+        if let (Some(author), Some(source)) = (tag.option.as_scalar(), &source) {
+            if self.config.quote_author_as_link
+                && author.contains("://")
+                && self.is_allowed_url(author)
+            {
+                write!(
+                    output,
+                    "<div class=\"{}-quote-author\"><a class=\"{}-quote-source\" href=\"{}\"",
+                    self.config.class_prefix,
+                    self.config.class_prefix,
+                    escape_html(author)
+                )
+                .unwrap();
+                self.render_link_attrs(author, output);
+                write!(
+                    output,
+                    ">{}</a></div>",
+                    escape_html(&self.config.quote_source_label)
+                )
+                .unwrap();
+            } else {
+                let attribution = self
+                    .config
+                    .quote_attribution_format
+                    .replace("{author}", &escape_html(source.author));
+                let post_url = source.post.and_then(|post| {
+                    let resolve = self.config.post_url_resolver.as_ref()?;
+                    resolve(post)
+                        .filter(|url| is_valid_url(url, &self.config.allowed_schemes, true))
+                });
+                let prefix = &self.config.class_prefix;
+                match post_url {
+                    Some(url) => write!(
+                        output,
+                        "<div class=\"{}-quote-author\"><a class=\"{}-quote-link\" href=\"{}\">{}</a></div>",
+                        prefix,
+                        prefix,
+                        escape_html(&url),
+                        attribution
+                    ),
+                    None => write!(
+                        output,
+                        "<div class=\"{}-quote-author\">{}</div>",
+                        prefix, attribution
+                    ),
+                }
+                .unwrap();
+            }
         }
 
         write!(
@@ -629,7 +2434,16 @@ impl Renderer {
         output.push_str("</div></blockquote>");
     }
 
-    fn render_code(&self, tag: &TagNode, output: &mut String) {
+    /// Emits a `cite` attribute from a `cite=` option, dropping URLs that fail validation.
+    fn render_cite_attr(&self, tag: &TagNode, output: &mut RenderState<'_, '_>) {
+        if let Some(cite) = tag.option.get("cite") {
+            if self.is_allowed_url(cite) {
+                write!(output, " cite=\"{}\"", escape_html(cite)).unwrap();
+            }
+        }
+    }
+
+    fn render_code<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
         let lang = tag.option.as_scalar();
         let content = self.get_inner_text(tag);
 
@@ -638,57 +2452,137 @@ impl Renderer {
         if let Some(lang) = lang {
             write!(output, " data-language=\"{}\"", escape_html(lang)).unwrap();
         }
+        self.render_cite_attr(tag, output);
+        output.push('>');
+        self.render_code_copy_button(output);
 
-        output.push_str("><code");
+        output.push_str("<code");
 
         if let Some(lang) = lang {
-            write!(output, " class=\"language-{}\"", escape_html(lang)).unwrap();
+            self.render_code_class(lang, output);
         }
 
         output.push('>');
-        output.push_str(&escape_html(&content));
-        output.push_str("</code></pre>");
+        self.render_code_content(&content, output);
     }
 
-    fn render_code_with_lang(&self, tag: &TagNode, lang: &str, output: &mut String) {
+    fn render_code_with_lang<'d>(
+        &self,
+        tag: &'d TagNode,
+        lang: &str,
+        output: &mut RenderState<'_, 'd>,
+    ) {
         let content = self.get_inner_text(tag);
 
         write!(
             output,
-            "<pre class=\"{}-code\" data-language=\"{}\"><code class=\"language-{}\">",
-            self.config.class_prefix, lang, lang
+            "<pre class=\"{}-code\" data-language=\"{}\">",
+            self.config.class_prefix, lang
         )
         .unwrap();
-        output.push_str(&escape_html(&content));
+        self.render_code_copy_button(output);
+        output.push_str("<code");
+        self.render_code_class(lang, output);
+        output.push('>');
+        self.render_code_content(&content, output);
+    }
+
+    /// Writes the `class` attribute of a `<code>` from `code_class_format`.
+    fn render_code_class(&self, lang: &str, output: &mut RenderState<'_, '_>) {
+        let class = self
+            .config
+            .code_class_format
+            .replace("{lang}", &escape_html(lang));
+        write!(output, " class=\"{}\"", class).unwrap();
+    }
+
+    /// Writes the copy button, if enabled, at the start of a `<pre>`.
+    fn render_code_copy_button(&self, output: &mut RenderState<'_, '_>) {
+        if self.config.code_copy_button {
+            write!(
+                output,
+                "<button class=\"{}-code-copy\" type=\"button\">{}</button>",
+                self.config.class_prefix,
+                escape_html(&self.config.code_copy_label)
+            )
+            .unwrap();
+        }
+    }
+
+    /// Writes escaped code block content and closes the `<code>` and `<pre>`.
+    fn render_code_content(&self, content: &str, output: &mut RenderState<'_, '_>) {
+        if self.config.code_line_numbers {
+            for (i, line) in content.split('\n').enumerate() {
+                if i > 0 {
+                    output.push('\n');
+                }
+                write!(
+                    output,
+                    "<span class=\"{}-code-line\">{}</span>",
+                    self.config.class_prefix,
+                    escape_html_text(line)
+                )
+                .unwrap();
+            }
+        } else {
+            output.push_str(&escape_html_text(content));
+        }
         output.push_str("</code></pre>");
     }
 
-    fn render_icode(&self, tag: &TagNode, output: &mut String) {
-        let content = self.get_inner_text(tag);
+    fn render_icode<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
         write!(
             output,
             "<code class=\"{}-icode\">",
             self.config.class_prefix
         )
         .unwrap();
-        output.push_str(&escape_html(&content));
+        // Content captured verbatim is a single text node; with
+        // `ParserConfig::icode_verbatim` off, inner BBCode is parsed into tags
+        if tag.children.iter().all(Node::is_text) {
+            let content = self.get_inner_text(tag);
+            output.push_str(&escape_html_text(&content));
+        } else {
+            self.render_children(tag, output);
+        }
         output.push_str("</code>");
     }
 
-    fn render_plain(&self, tag: &TagNode, output: &mut String) {
+    fn render_plain<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
         let content = self.get_inner_text(tag);
-        output.push_str(&escape_html(&content));
+        output.push_str(&escape_html_text(&content));
     }
 
-    fn render_list(&self, tag: &TagNode, output: &mut String) {
-        let is_ordered = tag
-            .option
-            .as_scalar()
-            .map(|s| s == "1" || s == "a" || s == "A" || s == "i" || s == "I")
-            .unwrap_or(false);
+    /// Renders a list. `ordered` forces `<ol>`/`<ul>` for `[ol]` and `[ul]`;
+    /// `[list]` picks from its option.
+    fn render_list<'d>(
+        &self,
+        tag: &'d TagNode,
+        ordered: Option<bool>,
+        output: &mut RenderState<'_, 'd>,
+    ) {
+        let is_ordered = ordered.unwrap_or_else(|| {
+            tag.option
+                .as_scalar()
+                .is_some_and(|s| s == "1" || s == "a" || s == "A" || s == "i" || s == "I")
+        });
 
         let list_tag = if is_ordered { "ol" } else { "ul" };
 
+        // Split off anything before the first [*] so it never becomes a bare
+        // child of the list element.
+        let first_item = tag
+            .children
+            .iter()
+            .position(is_list_item)
+            .unwrap_or(tag.children.len());
+        let (stray, items) = tag.children.split_at(first_item);
+        let stray = trim_whitespace_nodes(stray);
+
+        if self.config.list_stray_content == StrayContent::KeepBefore {
+            output.defer(stray);
+        }
+
         write!(
             output,
             "<{} class=\"{}-list\"",
@@ -696,64 +2590,141 @@ impl Renderer {
         )
         .unwrap();
 
-        // Handle list type
+        // Handle list type; numbering types only apply to ordered lists and
+        // bullet styles only to unordered ones
         if let Some(list_type) = tag.option.as_scalar() {
-            match list_type.as_ref() {
-                "1" => output.push_str(" type=\"1\""),
-                "a" => output.push_str(" type=\"a\""),
-                "A" => output.push_str(" type=\"A\""),
-                "i" => output.push_str(" type=\"i\""),
-                "I" => output.push_str(" type=\"I\""),
-                "disc" => output.push_str(" style=\"list-style-type: disc;\""),
-                "circle" => output.push_str(" style=\"list-style-type: circle;\""),
-                "square" => output.push_str(" style=\"list-style-type: square;\""),
+            match (list_type.as_ref(), is_ordered) {
+                ("1", true) => output.push_str(" type=\"1\""),
+                ("a", true) => output.push_str(" type=\"a\""),
+                ("A", true) => output.push_str(" type=\"A\""),
+                ("i", true) => output.push_str(" type=\"i\""),
+                ("I", true) => output.push_str(" type=\"I\""),
+                ("disc", false) => output.push_str(" style=\"list-style-type: disc;\""),
+                ("circle", false) => output.push_str(" style=\"list-style-type: circle;\""),
+                ("square", false) => output.push_str(" style=\"list-style-type: square;\""),
                 _ => {}
             }
         }
 
         output.push('>');
-        self.render_children(tag, output);
+
+        if !stray.is_empty() && self.config.list_stray_content == StrayContent::WrapInItem {
+            output.push_str("<li>");
+            output.defer(stray);
+            output.push_str("</li>");
+        }
+
+        output.defer(items);
         write!(output, "</{}>", list_tag).unwrap();
     }
 
-    fn render_list_item(&self, tag: &TagNode, output: &mut String) {
+    fn render_list_item<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
         output.push_str("<li>");
         self.render_children(tag, output);
         output.push_str("</li>");
     }
 
-    fn render_align(&self, tag: &TagNode, align: &str, output: &mut String) {
-        write!(
-            output,
-            "<div class=\"{}-align\" style=\"text-align: {};\">",
-            self.config.class_prefix, align
-        )
-        .unwrap();
+    fn render_align<'d>(&self, tag: &'d TagNode, align: &str, output: &mut RenderState<'_, 'd>) {
+        let prefix = &self.config.class_prefix;
+        if self.config.use_inline_styles {
+            write!(
+                output,
+                "<div class=\"{}-align\" style=\"text-align: {};\">",
+                prefix, align
+            )
+            .unwrap();
+        } else {
+            write!(
+                output,
+                "<div class=\"{}-align {}-align-{}\">",
+                prefix, prefix, align
+            )
+            .unwrap();
+        }
         self.render_children(tag, output);
         output.push_str("</div>");
     }
 
-    fn render_indent(&self, tag: &TagNode, output: &mut String) {
-        let level: u8 = tag
+    /// Renders `[indent]`. The option is a level, a side (`left` or
+    /// `right`), or both separated by a comma, e.g. `[indent=2,right]`.
+    fn render_indent<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
+        let mut level: i64 = 1;
+        let mut side = "left";
+        for part in tag
             .option
             .as_scalar()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(1)
-            .min(5);
+            .into_iter()
+            .flat_map(|s| s.split(','))
+        {
+            let part = part.trim();
+            if part.eq_ignore_ascii_case("left") {
+                side = "left";
+            } else if part.eq_ignore_ascii_case("right") {
+                side = "right";
+            } else if let Ok(n) = part.parse::<i64>() {
+                level = n;
+            }
+        }
+        let max_level = i64::from(self.config.max_indent_level.max(1));
+        let level = level.clamp(1, max_level) as u32;
 
-        let margin = level as u32 * 20;
+        let margin = level.saturating_mul(self.config.indent_step_px);
+        let prefix = &self.config.class_prefix;
 
-        write!(
-            output,
-            "<div class=\"{}-indent\" style=\"margin-left: {}px;\">",
-            self.config.class_prefix, margin
-        )
-        .unwrap();
+        if self.config.use_inline_styles {
+            write!(
+                output,
+                "<div class=\"{}-indent\" style=\"margin-{}: {}px;\">",
+                prefix, side, margin
+            )
+            .unwrap();
+        } else {
+            write!(
+                output,
+                "<div class=\"{}-indent {}-indent-{}",
+                prefix, prefix, level
+            )
+            .unwrap();
+            if side == "right" {
+                write!(output, " {}-indent-right", prefix).unwrap();
+            }
+            output.push_str("\">");
+        }
+        self.render_children(tag, output);
+        output.push_str("</div>");
+    }
+
+    fn render_float<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
+        let side = match tag
+            .option
+            .as_scalar()
+            .map(|s| s.trim().to_ascii_lowercase())
+        {
+            Some(side) if side == "left" || side == "right" => side,
+            _ => return self.render_as_text(tag, output),
+        };
+
+        let prefix = &self.config.class_prefix;
+        if self.config.use_inline_styles {
+            write!(
+                output,
+                "<div class=\"{}-float\" style=\"float: {};\">",
+                prefix, side
+            )
+            .unwrap();
+        } else {
+            write!(
+                output,
+                "<div class=\"{}-float {}-float-{}\">",
+                prefix, prefix, side
+            )
+            .unwrap();
+        }
         self.render_children(tag, output);
         output.push_str("</div>");
     }
 
-    fn render_heading(&self, tag: &TagNode, output: &mut String) {
+    fn render_heading<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
         let level: u8 = tag
             .option
             .as_scalar()
@@ -761,60 +2732,182 @@ impl Renderer {
             .unwrap_or(1)
             .clamp(1, 6);
 
-        // XenForo maps 1->h2, 2->h3, 3->h4
-        let html_level = (level + 1).min(6);
+        // XenForo maps 1->h2, 2->h3, 3->h4 (the default base level of 2)
+        let html_level = (self.config.heading_base_level.saturating_add(level) - 1).clamp(1, 6);
+
+        let prefix = &self.config.class_prefix;
+        if !self.config.heading_anchors {
+            write!(output, "<h{} class=\"{}-heading\">", html_level, prefix).unwrap();
+            self.render_children(tag, output);
+            write!(output, "</h{}>", html_level).unwrap();
+            return;
+        }
 
+        let id = self.heading_id(&tag.inner_text(), output);
+        let id = escape_html(&id);
         write!(
             output,
-            "<h{} class=\"{}-heading\">",
-            html_level, self.config.class_prefix
+            "<h{} class=\"{}-heading\" id=\"{}\">",
+            html_level, prefix, id
         )
         .unwrap();
         self.render_children(tag, output);
+        if self.config.heading_anchor_links {
+            write!(
+                output,
+                "<a class=\"{}-heading-anchor\" href=\"#{}\" aria-hidden=\"true\">#</a>",
+                prefix, id
+            )
+            .unwrap();
+        }
         write!(output, "</h{}>", html_level).unwrap();
     }
 
-    fn render_spoiler(&self, tag: &TagNode, output: &mut String) {
+    /// Returns a unique heading id for a title within the current render.
+    fn heading_id(&self, title: &str, output: &mut RenderState<'_, '_>) -> String {
+        let slug = slugify(title);
+        let base = format!("{}{}", self.config.heading_id_prefix, slug);
+        let mut id = base.clone();
+        let mut n = 0;
+        while output.heading_ids.contains(&id) {
+            n += 1;
+            id = format!("{}-{}", base, n);
+        }
+        output.heading_ids.insert(id.clone());
+        id
+    }
+
+    /// Renders `[spoiler]` as `<details>`. Spoilers nested in other spoilers
+    /// carry `data-spoiler-depth` so they can be styled differently.
+    fn render_spoiler<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
         write!(
             output,
-            "<details class=\"{}-spoiler\"><summary>",
+            "<details class=\"{}-spoiler\"",
             self.config.class_prefix
         )
         .unwrap();
-
-        if let Some(title) = tag.option.as_scalar() {
-            output.push_str(&escape_html(title));
-        } else {
-            output.push_str("Spoiler");
+        if output.spoiler_depth > 0 {
+            let depth = output.spoiler_depth;
+            write!(output, " data-spoiler-depth=\"{}\"", depth).unwrap();
         }
+        if self.config.spoilers_open_by_default {
+            output.push_str(" open");
+        }
+        output.push_str("><summary>");
+
+        let title = tag
+            .option
+            .as_scalar()
+            .map_or(&*self.config.default_spoiler_label, |title| title.as_ref());
+        output.push_str(&escape_html_text(title));
 
         output.push_str("</summary><div class=\"spoiler-content\">");
+        output.spoiler_depth += 1;
         self.render_children(tag, output);
         output.push_str("</div></details>");
+        output.leave_spoiler();
     }
 
-    fn render_ispoiler(&self, tag: &TagNode, output: &mut String) {
-        write!(
-            output,
-            "<span class=\"{}-ispoiler\" onclick=\"this.classList.toggle('revealed')\">",
-            self.config.class_prefix
-        )
-        .unwrap();
+    fn render_ispoiler<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
+        let prefix = &self.config.class_prefix;
+        match self.config.spoiler_mode {
+            SpoilerMode::Script => write!(
+                output,
+                "<span class=\"{}-ispoiler\" onclick=\"this.classList.toggle('revealed')\">",
+                prefix
+            )
+            .unwrap(),
+            SpoilerMode::CssOnly => {
+                write!(output, "<span class=\"{}-ispoiler\">", prefix).unwrap()
+            }
+            SpoilerMode::Details => write!(
+                output,
+                "<details class=\"{}-ispoiler\"><summary>{}</summary><span class=\"spoiler-content\">",
+                prefix,
+                escape_html(&self.config.default_spoiler_label)
+            )
+            .unwrap(),
+        }
         self.render_children(tag, output);
-        output.push_str("</span>");
+        if self.config.spoiler_mode == SpoilerMode::Details {
+            output.push_str("</span></details>");
+        } else {
+            output.push_str("</span>");
+        }
+    }
+
+    /// Renders `[attach]` through the attachment resolver. The id is a
+    /// numeric option (`[attachment=123]`) or else the content
+    /// (`[attach=full]123[/attach]`).
+    fn render_attachment<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
+        let id = match tag.option.as_scalar() {
+            Some(id) if !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()) => id.clone(),
+            _ => Cow::Owned(tag.inner_text().trim().to_string()),
+        };
+        let info = self
+            .config
+            .attachment_resolver
+            .as_ref()
+            .filter(|_| !id.is_empty())
+            .and_then(|resolve| resolve(&id))
+            .filter(|info| is_valid_url(&info.url, &self.config.allowed_schemes, true));
+        let Some(info) = info else {
+            return self.render_as_text(tag, output);
+        };
+
+        let prefix = &self.config.class_prefix;
+        let alt = if info.alt.is_empty() {
+            id.as_ref()
+        } else {
+            info.alt.as_str()
+        };
+        if info.mime_type.to_ascii_lowercase().starts_with("image/") {
+            write!(
+                output,
+                "<img class=\"{}-attachment\" src=\"{}\" alt=\"{}\"{}",
+                prefix,
+                escape_html(&info.url),
+                escape_html(alt),
+                self.void_end()
+            )
+            .unwrap();
+        } else {
+            write!(
+                output,
+                "<a class=\"{}-attachment\" href=\"{}\">{}</a>",
+                prefix,
+                escape_html(&info.url),
+                escape_html_text(alt)
+            )
+            .unwrap();
+        }
     }
 
-    fn render_user(&self, tag: &TagNode, output: &mut String) {
+    fn render_user<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
         let user_id = tag.option.as_scalar();
         let username = tag.inner_text();
+        let href = self.config.user_link_resolver.as_ref().and_then(|resolve| {
+            resolve(user_id.unwrap_or(&username))
+                .filter(|url| is_valid_url(url, &self.config.allowed_schemes, true))
+        });
 
         if let Some(id) = user_id {
             write!(
                 output,
-                "<a class=\"{}-user\" data-user-id=\"{}\" href=\"#\">@{}</a>",
+                "<a class=\"{}-user\" data-user-id=\"{}\" href=\"{}\">@{}</a>",
                 self.config.class_prefix,
                 escape_html(id),
-                escape_html(&username)
+                escape_html(href.as_deref().unwrap_or("#")),
+                escape_html_text(&username)
+            )
+            .unwrap();
+        } else if let Some(href) = href {
+            write!(
+                output,
+                "<a class=\"{}-user\" href=\"{}\">@{}</a>",
+                self.config.class_prefix,
+                escape_html(&href),
+                escape_html_text(&username)
             )
             .unwrap();
         } else {
@@ -822,124 +2915,597 @@ impl Renderer {
                 output,
                 "<span class=\"{}-user\">@{}</span>",
                 self.config.class_prefix,
-                escape_html(&username)
+                escape_html_text(&username)
             )
             .unwrap();
         }
     }
 
-    fn render_table(&self, tag: &TagNode, output: &mut String) {
-        write!(
-            output,
-            "<table class=\"{}-table\"",
-            self.config.class_prefix
-        )
-        .unwrap();
+    fn render_table<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
+        let prefix = &self.config.class_prefix;
+        write!(output, "<table class=\"{}-table", prefix).unwrap();
+
+        // A visible border is a class rather than a `border` attribute, so it
+        // can be styled without inline styles
+        let bordered = tag
+            .option
+            .get("border")
+            .and_then(|border| border.trim().parse::<u32>().ok())
+            .is_some_and(|border| border > 0);
+        if bordered {
+            write!(output, " {}-table-bordered", prefix).unwrap();
+        }
+        output.push('"');
 
         if let Some(map) = tag.option.as_map() {
-            if let Some(width) = map.get("width") {
-                write!(output, " style=\"width: {};\"", escape_html(width)).unwrap();
+            if let Some(width) = map.get("width").and_then(|w| parse_css_width(w)) {
+                write!(output, " style=\"width: {};\"", width).unwrap();
             }
         }
 
         output.push('>');
-        self.render_children(tag, output);
+
+        if let Some(caption) = tag.option.get("caption").filter(|c| !c.trim().is_empty()) {
+            write!(output, "<caption>{}</caption>", escape_html_text(caption)).unwrap();
+        }
+
+        // Leading rows made only of [th] cells form the table head
+        let rows = &tag.children;
+        let head_len = rows
+            .iter()
+            .position(|row| !is_whitespace_node(row) && !is_header_row(row))
+            .unwrap_or(rows.len());
+        let (head, body) = rows.split_at(head_len);
+        if head.iter().any(is_header_row) {
+            output.push_str("<thead>");
+            self.render_table_children(head, output);
+            output.push_str("</thead>");
+        }
+        if !body.iter().all(is_whitespace_node) {
+            output.push_str("<tbody>");
+            self.render_table_children(body, output);
+            output.push_str("</tbody>");
+        }
+
         output.push_str("</table>");
     }
 
-    fn render_table_row(&self, tag: &TagNode, output: &mut String) {
+    fn render_table_row<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
         output.push_str("<tr>");
-        self.render_children(tag, output);
+        self.render_table_children(&tag.children, output);
         output.push_str("</tr>");
     }
 
-    fn render_table_header(&self, tag: &TagNode, output: &mut String) {
-        output.push_str("<th");
-
-        if let Some(map) = tag.option.as_map() {
-            if let Some(width) = map.get("width") {
-                write!(output, " style=\"width: {};\"", escape_html(width)).unwrap();
+    /// Renders the children of a table or row, dropping the whitespace and
+    /// line breaks that separate its rows or cells in the source.
+    fn render_table_children<'d>(&self, nodes: &'d [Node<'d>], output: &mut RenderState<'_, 'd>) {
+        if self.config.line_break_in_tables {
+            return output.defer(nodes);
+        }
+        for child in nodes {
+            if !is_whitespace_node(child) {
+                output.defer(core::slice::from_ref(child));
             }
         }
+    }
 
+    fn render_table_header<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
+        output.push_str("<th");
+        self.render_cell_attrs(tag, output);
         output.push('>');
         self.render_children(tag, output);
         output.push_str("</th>");
     }
 
-    fn render_table_cell(&self, tag: &TagNode, output: &mut String) {
+    fn render_table_cell<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
         output.push_str("<td");
-
-        if let Some(map) = tag.option.as_map() {
-            if let Some(width) = map.get("width") {
-                write!(output, " style=\"width: {};\"", escape_html(width)).unwrap();
-            }
-        }
-
+        self.render_cell_attrs(tag, output);
         output.push('>');
         self.render_children(tag, output);
         output.push_str("</td>");
     }
 
-    /// Renders a tag as plain text (for invalid/broken tags).
-    fn render_as_text(&self, tag: &TagNode, output: &mut String) {
-        self.render_text(&tag.raw_open, output);
-        for child in &tag.children {
-            self.render_node(child, output);
+    /// Writes the attributes of a table cell: `colspan`/`rowspan` from the
+    /// span options and a `style` from its `width`, `align`, `valign` and
+    /// `bgcolor` options. Values outside the allowlists are dropped.
+    fn render_cell_attrs<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
+        let Some(map) = tag.option.as_map() else {
+            return;
+        };
+
+        for attr in ["colspan", "rowspan"] {
+            if let Some(span) = map.get(attr).and_then(|v| parse_cell_span(v)) {
+                write!(output, " {}=\"{}\"", attr, span).unwrap();
+            }
         }
-        if !tag.raw_close.is_empty() {
-            self.render_text(&tag.raw_close, output);
+
+        let mut styles: Vec<String> = Vec::new();
+        if let Some(width) = map.get("width").and_then(|w| parse_css_width(w)) {
+            styles.push(format!("width: {};", width));
+        }
+        if let Some(align) = map.get("align") {
+            let align = align.to_ascii_lowercase();
+            if matches!(align.as_str(), "left" | "center" | "right" | "justify") {
+                styles.push(format!("text-align: {};", align));
+            }
+        }
+        if let Some(valign) = map.get("valign") {
+            let valign = valign.to_ascii_lowercase();
+            if matches!(valign.as_str(), "top" | "middle" | "bottom") {
+                styles.push(format!("vertical-align: {};", valign));
+            }
+        }
+        if let Some(color) = map.get("bgcolor") {
+            if self.is_allowed_color(color) {
+                styles.push(format!("background-color: {};", escape_html(color)));
+            }
         }
-    }
-}
 
-impl Default for Renderer {
-    fn default() -> Self {
-        Self::new()
+        if !styles.is_empty() {
+            write!(output, " style=\"{}\"", styles.join(" ")).unwrap();
+        }
     }
-}
-
-// ============================================================================
-// Helper functions
-// ============================================================================
 
-/// Escapes HTML special characters.
-///
-/// This uses cow-utils for efficient zero-copy when no escaping is needed.
-pub fn escape_html(input: &str) -> Cow<'_, str> {
-    // Check if any escaping is needed
-    if !input
-        .bytes()
-        .any(|b| matches!(b, b'<' | b'>' | b'&' | b'"' | b'\''))
-    {
-        return Cow::Borrowed(input);
-    }
+    /// Renders a single node for [`render_text_only`](Self::render_text_only).
+    ///
+    /// Children are pushed onto `work` (in reverse) rather than rendered
+    /// recursively.
+    fn render_plaintext_node<'d>(
+        &self,
+        node: &'d Node,
+        output: &mut String,
+        work: &mut Vec<PlainWork<'d>>,
+    ) {
+        let tag = match node {
+            Node::Text(text) | Node::AutoUrl(text, _) => return output.push_str(text),
+            Node::LineBreak => return output.push('\n'),
+            Node::Tag(tag) => tag,
+        };
 
-    // Need to escape
-    let mut result = String::with_capacity(input.len() + input.len() / 4);
-    for c in input.chars() {
-        match c {
-            '<' => result.push_str("&lt;"),
-            '>' => result.push_str("&gt;"),
-            '&' => result.push_str("&amp;"),
-            '"' => result.push_str("&quot;"),
-            '\'' => result.push_str("&#x27;"),
-            _ => result.push(c),
+        if tag.broken {
+            output.push_str(&tag.raw_open);
+            work.push(PlainWork::Text(&tag.raw_close));
+            work.extend(tag.children.iter().rev().map(PlainWork::Node));
+            return;
         }
-    }
-    Cow::Owned(result)
-}
 
-/// Validates a color value.
-fn is_valid_color(color: &str) -> bool {
-    let lower = color.to_ascii_lowercase();
+        let block = self.registry.is_block_level(&tag.name);
+        if block && !output.is_empty() && !output.ends_with('\n') {
+            output.push('\n');
+        }
+        if block {
+            work.push(PlainWork::EndBlock);
+        }
 
-    // Block "transparent" - can be used to hide text (XenForo pattern)
-    if lower == "transparent" {
-        return false;
+        match &*tag.name {
+            "img" | "image" => {
+                if let Some(alt) = tag.option.as_map().and_then(|m| m.get("alt")) {
+                    output.push_str(alt);
+                }
+            }
+            // Block-level, so already on a line of its own
+            "hr" => {}
+            "br" => output.push('\n'),
+            "code" | "icode" | "c" | "inline" | "php" | "html" | "plain" | "noparse" | "nobbc" => {
+                output.push_str(&tag.inner_text())
+            }
+            _ => work.extend(tag.children.iter().rev().map(PlainWork::Node)),
+        }
     }
 
-    // Block CSS system colors (XenForo pattern) - can cause UI confusion/spoofing
+    /// Renders a single node for [`render_markdown`](Self::render_markdown).
+    ///
+    /// Children are pushed onto `work` (in reverse) rather than rendered
+    /// recursively.
+    fn render_markdown_node<'d>(
+        &self,
+        node: &'d Node,
+        output: &mut MarkdownWriter,
+        work: &mut Vec<MarkdownWork<'d>>,
+    ) {
+        let tag = match node {
+            Node::Text(text) => return output.push_text(text),
+            // Bare URLs and emails are CommonMark autolinks
+            Node::AutoUrl(url, AutoLinkKind::Url | AutoLinkKind::Email) => {
+                return output.push_str(&format!("<{}>", markdown_url(url)))
+            }
+            Node::AutoUrl(url, AutoLinkKind::Www) => return output.push_text(url),
+            Node::LineBreak => return output.line_break(),
+            Node::Tag(tag) => tag,
+        };
+
+        let children = |work: &mut Vec<MarkdownWork<'d>>| {
+            work.extend(tag.children.iter().rev().map(MarkdownWork::Node));
+        };
+
+        if tag.broken {
+            output.push_text(&tag.raw_open);
+            work.push(MarkdownWork::Text(&tag.raw_close));
+            return children(work);
+        }
+
+        match &*tag.name {
+            "b" | "bold" | "i" | "italic" => {
+                let marker = if matches!(&*tag.name, "i" | "italic") {
+                    "_"
+                } else {
+                    "**"
+                };
+                output.open_emphasis(marker);
+                work.push(MarkdownWork::CloseEmphasis(marker));
+                children(work);
+            }
+            "url" | "link" => {
+                let (url, title) = match tag.option.as_scalar() {
+                    Some(opt) => {
+                        let (url, title) = split_url_title(opt);
+                        (Cow::Borrowed(url), title)
+                    }
+                    None => (tag.inner_text(), None),
+                };
+                let url = with_inferred_scheme(&url);
+                if url.is_empty() || !self.is_allowed_url(&url) {
+                    children(work);
+                } else if tag.option.as_scalar().is_none() {
+                    output.push_str(&format!("<{}>", markdown_url(&url)));
+                } else {
+                    let title = title
+                        .map(|t| format!(" \"{}\"", t.replace('"', "\\\"")))
+                        .unwrap_or_default();
+                    output.push_str("[");
+                    work.push(MarkdownWork::Markup(Cow::Owned(format!(
+                        "]({}{})",
+                        markdown_url(&url),
+                        title
+                    ))));
+                    children(work);
+                }
+            }
+            "img" | "image" => {
+                let url = tag.inner_text();
+                if url.is_empty() || !self.is_allowed_url(&url) {
+                    return output.push_text(&url);
+                }
+                let alt = tag.option.as_map().and_then(|map| map.get("alt"));
+                output.push_str(&format!(
+                    "![{}]({})",
+                    escape_markdown(alt.map_or("", |alt| alt)),
+                    markdown_url(&url)
+                ));
+            }
+            "code" | "php" | "html" => {
+                let content = normalize_newlines(tag.inner_text());
+                let content = content.trim_matches('\n');
+                let lang = match &*tag.name {
+                    "code" => tag.option.as_scalar().map_or("", |lang| lang.as_ref()),
+                    name => name,
+                };
+                let lang = if lang
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || "+-#._".contains(c))
+                {
+                    lang
+                } else {
+                    ""
+                };
+                let fence = "`".repeat(longest_backtick_run(content).max(2) + 1);
+                output.block(2);
+                output.push_str(&format!("{fence}{lang}\n{content}\n{fence}"));
+                output.block(2);
+            }
+            "icode" | "c" | "inline" => {
+                let content = tag.inner_text();
+                let fence = "`".repeat(longest_backtick_run(&content) + 1);
+                let pad = if content.starts_with('`') || content.ends_with('`') {
+                    " "
+                } else {
+                    ""
+                };
+                output.push_str(&format!("{fence}{pad}{content}{pad}{fence}"));
+            }
+            "plain" | "noparse" | "nobbc" => output.push_text(&tag.inner_text()),
+            "quote" => {
+                // The blank line before the quote is outside of it
+                output.block(2);
+                output.flush_breaks();
+                work.push(MarkdownWork::EndBlock(2));
+                work.push(MarkdownWork::EndPrefix(output.prefix.len()));
+                output.prefix.push_str("> ");
+                children(work);
+            }
+            "list" | "ol" | "ul" => {
+                let ordered = tag.name == "ol"
+                    || tag.name == "list"
+                        && tag
+                            .option
+                            .as_scalar()
+                            .is_some_and(|s| matches!(&**s, "1" | "a" | "A" | "i" | "I"));
+                let marker = if ordered { "1. " } else { "- " };
+                output.block(2);
+                work.push(MarkdownWork::EndBlock(2));
+                work.extend(tag.children.iter().rev().map(|node| match node {
+                    Node::Tag(item) if item.name == "*" => MarkdownWork::Item(item, marker),
+                    node => MarkdownWork::Node(node),
+                }));
+            }
+            "*" => work.push(MarkdownWork::Item(tag, "- ")),
+            "hr" => {
+                output.block(2);
+                output.push_str("---");
+                output.block(2);
+            }
+            "br" => output.line_break(),
+            _ if self
+                .registry
+                .get(&tag.name)
+                .is_some_and(|def| def.tag_type == TagType::Block) =>
+            {
+                output.block(1);
+                work.push(MarkdownWork::EndBlock(1));
+                children(work);
+            }
+            _ => children(work),
+        }
+    }
+
+    /// Renders a tag as plain text (for invalid/broken tags).
+    fn render_as_text<'d>(&self, tag: &'d TagNode, output: &mut RenderState<'_, 'd>) {
+        self.render_tag_markup(&tag.raw_open, output);
+        output.defer(&tag.children);
+        if !tag.raw_close.is_empty() {
+            self.render_tag_markup(&tag.raw_close, output);
+        }
+    }
+
+    /// Renders the raw open or close markup of a rejected tag.
+    ///
+    /// Quotes are escaped as in attributes: rejected markup is often an
+    /// attempted attribute breakout, and stays inert even if pasted into one.
+    fn render_tag_markup(&self, raw: &str, output: &mut RenderState<'_, '_>) {
+        let text = if self.config.sanitize {
+            escape_html(raw)
+        } else {
+            Cow::Borrowed(raw)
+        };
+        if self.config.escape_rejected_brackets {
+            output.push_str(&text.replace('[', "&#91;").replace(']', "&#93;"));
+        } else {
+            output.push_str(&text);
+        }
+    }
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+/// Escapes HTML special characters.
+///
+/// Quotes are escaped too, so the result is safe inside a quoted attribute
+/// value as well as in text. Use [`escape_html_text`] for text content.
+/// Borrows the input when no escaping is needed.
+pub fn escape_html(input: &str) -> Cow<'_, str> {
+    // Check if any escaping is needed
+    if !input
+        .bytes()
+        .any(|b| matches!(b, b'<' | b'>' | b'&' | b'"' | b'\''))
+    {
+        return Cow::Borrowed(input);
+    }
+
+    // Need to escape
+    let mut result = String::with_capacity(input.len() + input.len() / 4);
+    for c in input.chars() {
+        match c {
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '&' => result.push_str("&amp;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&#x27;"),
+            _ => result.push(c),
+        }
+    }
+    Cow::Owned(result)
+}
+
+/// Escapes `<`, `>` and `&` for HTML text content.
+///
+/// Quotes are left as they are, which is safe between tags but not inside
+/// an attribute value; use [`escape_html`] there. Borrows the input when no
+/// escaping is needed.
+pub fn escape_html_text(input: &str) -> Cow<'_, str> {
+    if !input.bytes().any(|b| matches!(b, b'<' | b'>' | b'&')) {
+        return Cow::Borrowed(input);
+    }
+
+    let mut result = String::with_capacity(input.len() + input.len() / 4);
+    for c in input.chars() {
+        match c {
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '&' => result.push_str("&amp;"),
+            _ => result.push(c),
+        }
+    }
+    Cow::Owned(result)
+}
+
+/// Returns true if `pattern` matches `text` at byte `pos`.
+fn word_at(
+    text: &str,
+    pos: usize,
+    pattern: &str,
+    whole_word: bool,
+    case_insensitive: bool,
+) -> bool {
+    let end = pos + pattern.len();
+    if pattern.is_empty() || end > text.len() {
+        return false;
+    }
+    let candidate = &text.as_bytes()[pos..end];
+    let matched = if case_insensitive {
+        candidate.eq_ignore_ascii_case(pattern.as_bytes())
+    } else {
+        candidate == pattern.as_bytes()
+    };
+    if !matched || !text.is_char_boundary(pos) || !text.is_char_boundary(end) {
+        return false;
+    }
+
+    let is_word_char = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    !whole_word
+        || (!is_word_char(text[..pos].chars().next_back())
+            && !is_word_char(text[end..].chars().next()))
+}
+
+/// Canonical names of the tags handled by `Renderer::render_tag`.
+///
+/// Keep in sync with the match in `render_tag`.
+const RENDERED_TAGS: &[&str] = &[
+    "b",
+    "i",
+    "u",
+    "s",
+    "sub",
+    "sup",
+    "abbr",
+    "highlight",
+    "color",
+    "font",
+    "size",
+    "url",
+    "email",
+    "img",
+    "quote",
+    "code",
+    "icode",
+    "php",
+    "html",
+    "plain",
+    "list",
+    "ol",
+    "ul",
+    "*",
+    "left",
+    "center",
+    "right",
+    "justify",
+    "indent",
+    "float",
+    "heading",
+    "hr",
+    "br",
+    "spoiler",
+    "ispoiler",
+    "user",
+    "attach",
+    "table",
+    "tr",
+    "th",
+    "td",
+];
+
+/// Returns true if the node is a list item (`[*]` or `[li]`).
+fn is_list_item(node: &Node) -> bool {
+    matches!(node, Node::Tag(t) if t.name == "*" || t.name == "li")
+}
+
+/// Returns true if the node is a line break or whitespace-only text.
+fn is_whitespace_node(node: &Node) -> bool {
+    match node {
+        Node::LineBreak => true,
+        Node::Text(t) => t.trim().is_empty(),
+        _ => false,
+    }
+}
+
+/// HTML elements that are visible without any text content.
+const MEDIA_ELEMENTS: &[&str] = &[
+    "img", "iframe", "video", "audio", "object", "embed", "svg", "canvas", "hr",
+];
+
+/// Returns true if `html` has non-whitespace text or a media element.
+fn has_visible_content(html: &str) -> bool {
+    let mut rest = html;
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '<' => {
+                let end = rest.find('>').map_or(rest.len(), |i| i + 1);
+                let name: String = rest[1..end]
+                    .chars()
+                    .take_while(|c| c.is_ascii_alphanumeric())
+                    .collect();
+                if MEDIA_ELEMENTS
+                    .iter()
+                    .any(|media| name.eq_ignore_ascii_case(media))
+                {
+                    return true;
+                }
+                rest = &rest[end..];
+            }
+            '&' => {
+                let space = ["&nbsp;", "&#160;", "&#xa0;", "&#xA0;"]
+                    .into_iter()
+                    .find(|entity| rest.starts_with(entity));
+                match space {
+                    Some(entity) => rest = &rest[entity.len()..],
+                    None => return true,
+                }
+            }
+            c if c.is_whitespace() => rest = &rest[c.len_utf8()..],
+            _ => return true,
+        }
+    }
+    false
+}
+
+/// Returns true for a `[tr]` whose cells are all `[th]`.
+fn is_header_row(node: &Node) -> bool {
+    let Some(row) = node.as_tag() else {
+        return false;
+    };
+    let mut cells = row.children.iter().filter(|cell| !is_whitespace_node(cell));
+    row.name == "tr"
+        && !row.broken
+        && cells.clone().next().is_some()
+        && cells.all(|cell| cell.as_tag().is_some_and(|c| c.name == "th" && !c.broken))
+}
+
+/// Strips leading and trailing whitespace-only nodes from a slice.
+fn trim_whitespace_nodes<'n, 'a>(nodes: &'n [Node<'a>]) -> &'n [Node<'a>] {
+    let start = nodes
+        .iter()
+        .position(|n| !is_whitespace_node(n))
+        .unwrap_or(nodes.len());
+    let end = nodes
+        .iter()
+        .rposition(|n| !is_whitespace_node(n))
+        .map_or(start, |i| i + 1);
+    &nodes[start..end]
+}
+
+/// Validates a color value.
+fn is_valid_color(color: &str) -> bool {
+    let lower = color.to_ascii_lowercase();
+
+    // Never let anything that could load a resource, run an IE expression or
+    // hide a declaration behind a comment through, whatever else validates
+    if has_css_hazard(&lower) {
+        return false;
+    }
+
+    // Block "transparent" - can be used to hide text (XenForo pattern)
+    if lower == "transparent" {
+        return false;
+    }
+
+    // Block CSS system colors (XenForo pattern) - can cause UI confusion/spoofing
     const SYSTEM_COLORS: &[&str] = &[
         "activeborder",
         "activecaption",
@@ -985,45 +3551,514 @@ fn is_valid_color(color: &str) -> bool {
     }
 
     // Hex color
-    if color.starts_with('#') {
-        let hex = &color[1..];
+    if let Some(hex) = color.strip_prefix('#') {
         return (hex.len() == 3 || hex.len() == 6) && hex.chars().all(|c| c.is_ascii_hexdigit());
     }
 
-    // RGB/RGBA
-    if color.starts_with("rgb") {
-        return color
-            .chars()
-            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '(' | ')' | ',' | ' ' | '.'));
+    // Functional notation
+    if let Some((func, rest)) = lower.split_once('(') {
+        return is_valid_color_function(func, rest);
     }
 
     // Named color (simplified validation)
     VALID_COLORS.contains(&lower.as_str())
 }
 
-/// Validates a font family name.
+/// Returns true if a lowercase CSS value contains `url(`, `expression(` or a
+/// comment opener.
+fn has_css_hazard(value: &str) -> bool {
+    ["url(", "expression(", "/*"]
+        .iter()
+        .any(|hazard| value.contains(hazard))
+}
+
+/// Validates the arguments of an `rgb()`, `rgba()`, `hsl()` or `hsla()` color.
+///
+/// `args` is everything after the opening parenthesis.
+fn is_valid_color_function(func: &str, args: &str) -> bool {
+    let Some(args) = args.strip_suffix(')') else {
+        return false;
+    };
+    let args: Vec<&str> = args.split(',').map(str::trim).collect();
+
+    let channels = match (func, args.len()) {
+        ("rgb", 3) | ("rgba", 4) => args[..3]
+            .iter()
+            .all(|a| is_color_percentage(a) || is_color_number(a, 255.0)),
+        ("hsl", 3) | ("hsla", 4) => {
+            is_color_number(args[0], 360.0)
+                && is_color_percentage(args[1])
+                && is_color_percentage(args[2])
+        }
+        _ => return false,
+    };
+
+    channels
+        && args
+            .get(3)
+            .is_none_or(|a| is_color_percentage(a) || is_color_number(a, 1.0))
+}
+
+/// Returns true for a plain decimal number between 0 and `max`.
+fn is_color_number(value: &str, max: f64) -> bool {
+    !value.is_empty()
+        && value.bytes().all(|b| b.is_ascii_digit() || b == b'.')
+        && value.parse::<f64>().is_ok_and(|n| n <= max)
+}
+
+/// Returns true for a percentage between 0% and 100%.
+fn is_color_percentage(value: &str) -> bool {
+    value
+        .strip_suffix('%')
+        .is_some_and(|n| is_color_number(n, 100.0))
+}
+
+/// Decodes `%XX` escapes, leaving malformed ones as they are.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| core::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Percent-encodes everything except RFC 3986 unreserved characters.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            write!(encoded, "%{:02X}", byte).unwrap();
+        }
+    }
+    encoded
+}
+
+/// Backslash-escapes characters that are Markdown syntax.
+fn escape_markdown(text: &str) -> Cow<'_, str> {
+    const SPECIAL: &[char] = &['\\', '`', '*', '_', '[', ']', '<', '>', '#'];
+    if !text.contains(SPECIAL) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        if SPECIAL.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    Cow::Owned(escaped)
+}
+
+/// Backslash-escapes a list, setext heading, rule or fence marker at the
+/// start of an (already escaped) line of text.
+fn escape_line_start(line: &str) -> Cow<'_, str> {
+    if line.starts_with(['-', '+', '=', '~']) {
+        return Cow::Owned(format!("\\{}", line));
+    }
+    // Ordered list items: up to 9 digits followed by `.` or `)`
+    let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+    if (1..=9).contains(&digits) && line[digits..].starts_with(['.', ')']) {
+        return Cow::Owned(format!("{}\\{}", &line[..digits], &line[digits..]));
+    }
+    Cow::Borrowed(line)
+}
+
+/// Percent-encodes the characters that would end a Markdown link
+/// destination.
+fn markdown_url(url: &str) -> Cow<'_, str> {
+    if !url.contains([' ', '(', ')', '<', '>']) {
+        return Cow::Borrowed(url);
+    }
+    Cow::Owned(
+        url.replace(' ', "%20")
+            .replace('(', "%28")
+            .replace(')', "%29")
+            .replace('<', "%3C")
+            .replace('>', "%3E"),
+    )
+}
+
+/// Returns the length of the longest run of backticks in `text`, to pick a
+/// code fence that can't be closed early.
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
+
+/// Parses a table cell `colspan`/`rowspan` value, accepting 1 to 100.
+fn parse_cell_span(value: &str) -> Option<u8> {
+    if !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok().filter(|span| (1..=100).contains(span))
+}
+
+/// Parses a table or cell width: a whole number of pixels (`120`,
+/// `120px`) or a percentage up to 100 (`50%`).
+fn parse_css_width(value: &str) -> Option<String> {
+    let value = value.trim();
+    let (number, unit) = match value.strip_suffix('%') {
+        Some(number) => (number, "%"),
+        None => (value.strip_suffix("px").unwrap_or(value), "px"),
+    };
+    if number.is_empty() || number.len() > 5 || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let number: u32 = number.parse().ok()?;
+    if unit == "%" && number > 100 {
+        return None;
+    }
+    Some(format!("{}{}", number, unit))
+}
+
+/// A tag being rebuilt by [`truncate_document`].
+struct ExcerptFrame<'s, 'a> {
+    tag: Option<TagNode<'a>>,
+    rest: &'s [Node<'a>],
+    nodes: Vec<Node<'a>>,
+}
+
+/// Copies the start of `doc` holding at most `max_chars` characters of text.
+///
+/// Returns the copy and whether anything was left out.
+fn truncate_document<'a>(
+    doc: &Document<'a>,
+    max_chars: usize,
+    registry: &TagRegistry,
+) -> (Document<'a>, bool) {
+    let mut budget = max_chars;
+    let mut truncated = false;
+    let mut stack = vec![ExcerptFrame {
+        tag: None,
+        rest: &doc.nodes,
+        nodes: Vec::new(),
+    }];
+
+    loop {
+        let frame = stack.last_mut().expect("root frame");
+        let Some((node, rest)) = frame.rest.split_first() else {
+            let frame = stack.pop().expect("root frame");
+            let Some(mut tag) = frame.tag else {
+                return (Document::with_nodes(frame.nodes), truncated);
+            };
+            tag.children = frame.nodes;
+            let parent = stack.last_mut().expect("root frame");
+            parent.nodes.push(Node::Tag(tag));
+            continue;
+        };
+        frame.rest = rest;
+
+        if budget == 0 {
+            truncated = true;
+            frame.rest = &[];
+            continue;
+        }
+
+        // Tags whose content is a URL or id are copied whole, since a cut
+        // one would point somewhere else
+        let atomic = match node {
+            Node::AutoUrl(text, _) => Some(text.chars().count()),
+            Node::Tag(tag) => {
+                let resolved = registry.resolve(&tag.name);
+                match resolved.as_ref().map_or(&*tag.name, |r| r.name()) {
+                    "img" | "attach" => Some(0),
+                    "url" | "email" if tag.option.is_none() => {
+                        Some(tag.inner_text().chars().count())
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some(len) = atomic {
+            if len <= budget {
+                budget -= len;
+                frame.nodes.push(node.clone());
+            } else {
+                budget = 0;
+                truncated = true;
+            }
+            continue;
+        }
+
+        match node {
+            Node::Text(text) | Node::AutoUrl(text, _) => {
+                let len = text.chars().count();
+                if len <= budget {
+                    budget -= len;
+                    frame.nodes.push(node.clone());
+                } else {
+                    let cut = text
+                        .char_indices()
+                        .nth(budget)
+                        .map_or(text.len(), |(i, _)| i);
+                    frame.nodes.push(Node::text_owned(text[..cut].to_string()));
+                    budget = 0;
+                    truncated = true;
+                }
+            }
+            Node::LineBreak => frame.nodes.push(Node::LineBreak),
+            Node::Tag(tag) => {
+                let shell = TagNode {
+                    name: tag.name.clone(),
+                    raw_name: tag.raw_name.clone(),
+                    option: tag.option.clone(),
+                    raw_option: tag.raw_option.clone(),
+                    children: Vec::new(),
+                    closed: tag.closed,
+                    raw_open: tag.raw_open.clone(),
+                    raw_close: tag.raw_close.clone(),
+                    broken: tag.broken,
+                    span: tag.span.clone(),
+                };
+                stack.push(ExcerptFrame {
+                    tag: Some(shell),
+                    rest: &tag.children,
+                    nodes: Vec::new(),
+                });
+            }
+        }
+    }
+}
+
+/// The author of a `[quote]` and, in XenForo's `"Name, post: 123, member: 45"`
+/// form, the quoted post and member ids.
+struct QuoteSource<'a> {
+    author: &'a str,
+    post: Option<&'a str>,
+    member: Option<&'a str>,
+}
+
+impl<'a> QuoteSource<'a> {
+    /// Splits a quote option into author and ids. Options that aren't in
+    /// the metadata form (such as `"Smith, John"`) are all author.
+    fn parse(option: &'a str) -> Self {
+        let plain = Self {
+            author: option,
+            post: None,
+            member: None,
+        };
+        let mut parts = option.split(',');
+        let author = parts.next().unwrap_or_default().trim();
+        let mut source = Self {
+            author,
+            post: None,
+            member: None,
+        };
+        let mut any = false;
+        for part in parts {
+            let Some((key, value)) = part.split_once(':') else {
+                return plain;
+            };
+            let value = value.trim();
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return plain;
+            }
+            match key.trim().to_ascii_lowercase().as_str() {
+                "post" => source.post = Some(value),
+                "member" => source.member = Some(value),
+                _ => return plain,
+            }
+            any = true;
+        }
+        if any && !author.is_empty() {
+            source
+        } else {
+            plain
+        }
+    }
+}
+
+/// Returns the host of an absolute (or scheme-relative) URL, without any
+/// user info or port. A backslash ends the host, as browsers treat it like
+/// `/` in web URLs.
+fn url_host(url: &str) -> Option<&str> {
+    let url = url.trim();
+    let rest = match url.find("://") {
+        Some(i) => &url[i + 3..],
+        None => url.strip_prefix("//")?,
+    };
+    let authority = &rest[..rest.find(['/', '\\', '?', '#']).unwrap_or(rest.len())];
+    let host = authority.rsplit('@').next().unwrap_or(authority);
+    let host = match host.rfind(':') {
+        Some(i) if !host.ends_with(']') => &host[..i],
+        _ => host,
+    };
+    (!host.is_empty()).then_some(host)
+}
+
+/// Splits a `[url=x title=Foo]` option into the URL and the title, which may
+/// be quoted. Options without a title are returned whole.
+fn split_url_title(option: &str) -> (&str, Option<&str>) {
+    let Some(pos) = option.to_ascii_lowercase().find(" title=") else {
+        return (option, None);
+    };
+    let title = option[pos + " title=".len()..].trim();
+    let title = ['"', '\'']
+        .iter()
+        .find_map(|&q| title.strip_prefix(q)?.strip_suffix(q))
+        .unwrap_or(title);
+    (option[..pos].trim_end(), Some(title))
+}
+
+/// Converts `\r\n` and lone `\r` line endings in verbatim text to `\n`.
+fn normalize_newlines(text: Cow<'_, str>) -> Cow<'_, str> {
+    if !text.contains('\r') {
+        return text;
+    }
+    Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"))
+}
+
+/// Converts a heading title into a URL fragment slug.
+///
+/// ASCII letters and digits are lowercased, common Latin letters with
+/// diacritics are transliterated (`é` → `e`, `ß` → `ss`), whitespace becomes
+/// `-` and everything else except `-` and `_` is dropped. Leading and
+/// trailing dashes are trimmed; falls back to `section` if nothing is left.
+fn slugify(title: &str) -> String {
+    let mut slug = String::with_capacity(title.len());
+    for c in title.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() || matches!(c, '-' | '_') {
+            slug.push(c);
+        } else if c.is_whitespace() {
+            slug.push('-');
+        } else if let Some(ascii) = transliterate(c) {
+            slug.push_str(ascii);
+        }
+    }
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Transliterates a lowercase Latin letter with diacritics to ASCII.
+fn transliterate(c: char) -> Option<&'static str> {
+    Some(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => "e",
+        'ğ' => "g",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'į' | 'ı' => "i",
+        'ł' | 'ľ' => "l",
+        'ñ' | 'ń' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => "o",
+        'œ' => "oe",
+        'ř' => "r",
+        'ß' => "ss",
+        'ś' | 'š' | 'ş' => "s",
+        'ť' | 'ţ' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' | 'ų' => "u",
+        'ý' | 'ÿ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    })
+}
+
+/// Validates a font family name or a comma-separated font stack.
 fn is_valid_font(font: &str) -> bool {
-    // Block "inherit" keyword (XenForo pattern) - can be used for style inheritance attacks
-    if font.trim().eq_ignore_ascii_case("inherit") {
+    // Block CSS-wide keywords (XenForo pattern) - they can be used for style
+    // inheritance attacks
+    if ["inherit", "initial", "unset", "revert"]
+        .iter()
+        .any(|keyword| font.trim().eq_ignore_ascii_case(keyword))
+    {
         return false;
     }
 
-    // Only allow alphanumeric, spaces, and hyphens
-    font.chars()
-        .all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '-' || c == '_')
+    // Each family: only alphanumerics, spaces, hyphens and underscores
+    font.split(',').all(|family| {
+        !family.trim().is_empty()
+            && family
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '-' || c == '_')
+    })
+}
+
+/// Formats a font value accepted by [`is_valid_font`] as a CSS
+/// `font-family` list, quoting family names but not generic families.
+fn font_family_css(font: &str) -> String {
+    const GENERIC_FAMILIES: &[&str] = &[
+        "serif",
+        "sans-serif",
+        "monospace",
+        "cursive",
+        "fantasy",
+        "system-ui",
+    ];
+
+    let mut css = String::with_capacity(font.len() + 4);
+    for (i, family) in font.split(',').map(str::trim).enumerate() {
+        if i > 0 {
+            css.push_str(", ");
+        }
+        if GENERIC_FAMILIES
+            .iter()
+            .any(|generic| family.eq_ignore_ascii_case(generic))
+        {
+            css.push_str(family);
+        } else {
+            css.push('\'');
+            css.push_str(family);
+            css.push('\'');
+        }
+    }
+    css
 }
 
 /// Parses a size value into CSS.
-fn parse_size(size: &str) -> Option<String> {
+///
+/// Pixel sizes end up within `min_px..=max_px`. Percentages are capped at
+/// `max_px` relative to a 16px base, and raised to `min_px` only when it is
+/// above the default of 8px, which keeps small bare `Auto` percentages.
+fn parse_size(size: &str, mode: SizeMode, min_px: u32, max_px: u32) -> Option<String> {
     // Size can be:
     // - A number 1-7 (XenForo style)
     // - A percentage (phpBB style: 50-200)
     // - A pixel value like "12px"
+    let px = |n: u32| format!("{}px", n.max(min_px).min(max_px));
+    let percent = |n: u32| {
+        let min = if min_px > 8 {
+            min_px.saturating_mul(100).div_ceil(16)
+        } else {
+            0
+        };
+        let max = max_px.saturating_mul(100) / 16;
+        format!("{}%", n.max(min).min(max))
+    };
 
     if let Ok(n) = size.parse::<u8>() {
+        let n = u32::from(n);
+        match mode {
+            SizeMode::Auto | SizeMode::XenForoScale => {}
+            SizeMode::PhpbbPercent => return (50..=200).contains(&n).then(|| percent(n)),
+            SizeMode::Pixels => return (8..=36).contains(&n).then(|| px(n)),
+        }
+
         if (1..=7).contains(&n) {
             // XenForo sizes: 1=9px, 2=10px, 3=12px, 4=15px, 5=18px, 6=22px, 7=26px
-            let px = match n {
+            let scaled = match n {
                 1 => 9,
                 2 => 10,
                 3 => 12,
@@ -1033,29 +4068,29 @@ fn parse_size(size: &str) -> Option<String> {
                 7 => 26,
                 _ => 12,
             };
-            return Some(format!("{}px", px));
-        } else if (8..=200).contains(&n) {
+            return Some(px(scaled));
+        } else if mode == SizeMode::Auto && (8..=200).contains(&n) {
             // Pixel value or percentage
             if n <= 100 {
-                return Some(format!("{}%", n));
+                return Some(percent(n));
             } else {
-                return Some(format!("{}px", n.min(36)));
+                return Some(px(n.min(36)));
             }
         }
     }
 
-    if size.ends_with("px") {
-        if let Ok(n) = size[..size.len() - 2].parse::<u8>() {
+    if let Some(value) = size.strip_suffix("px") {
+        if let Ok(n) = value.parse::<u8>() {
             if (8..=36).contains(&n) {
-                return Some(size.to_string());
+                return Some(px(u32::from(n)));
             }
         }
     }
 
-    if size.ends_with('%') {
-        if let Ok(n) = size[..size.len() - 1].parse::<u16>() {
+    if let Some(value) = size.strip_suffix('%') {
+        if let Ok(n) = value.parse::<u16>() {
             if (50..=200).contains(&n) {
-                return Some(size.to_string());
+                return Some(percent(u32::from(n)));
             }
         }
     }
@@ -1063,17 +4098,46 @@ fn parse_size(size: &str) -> Option<String> {
     None
 }
 
+/// Prepends `https://` to a scheme-less link that starts with a host name,
+/// such as `www.example.com` or `example.com/page`, so it isn't mistaken
+/// for a relative URL, matching auto-linked `www.` hosts. Other URLs are
+/// returned unchanged.
+fn with_inferred_scheme(url: &str) -> Cow<'_, str> {
+    let host_end = url.find(['/', '?', '#']);
+    let host = &url[..host_end.unwrap_or(url.len())];
+    let is_host = host.contains('.')
+        && !host.starts_with('.')
+        && host
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'-')
+        && host
+            .rsplit('.')
+            .next()
+            .is_some_and(|tld| tld.len() >= 2 && tld.bytes().all(|b| b.is_ascii_alphabetic()));
+    // A bare `page.html` is a relative link, not a host
+    let host_like = host
+        .get(..4)
+        .is_some_and(|w| w.eq_ignore_ascii_case("www."))
+        || host_end.is_some_and(|end| url.as_bytes()[end] == b'/');
+    if is_host && host_like {
+        Cow::Owned(format!("https://{}", url))
+    } else {
+        Cow::Borrowed(url)
+    }
+}
+
 /// Validates a URL for safe rendering.
 /// Rejects dangerous protocols and attribute-breaking characters.
-fn is_valid_url(url: &str, allowed_schemes: &[String]) -> bool {
+fn is_valid_url(url: &str, allowed_schemes: &[String], allow_relative: bool) -> bool {
     // Must not be empty
     if url.is_empty() {
         return false;
     }
 
-    // Block URLs containing newlines (XenForo pattern)
-    // Newlines can be used to break out of attributes or inject headers
-    if url.contains('\n') || url.contains('\r') {
+    // Block URLs containing newlines (XenForo pattern) or any other control
+    // character; these can break out of attributes or inject headers.
+    // Percent-encoded sequences such as `%0A` are not affected.
+    if url.chars().any(|c| c.is_ascii_control()) {
         return false;
     }
 
@@ -1115,20 +4179,22 @@ fn is_valid_url(url: &str, allowed_schemes: &[String]) -> bool {
         return false;
     }
 
-    // Check scheme
-    if let Some(colon_pos) = url.find(':') {
-        let scheme = &url[..colon_pos].to_ascii_lowercase();
-        if !allowed_schemes.iter().any(|s| s == scheme) {
-            return false;
+    // Check scheme. A colon only ends a scheme if it comes before any path,
+    // query or fragment; otherwise the URL is relative (`//host` included).
+    match url.find([':', '/', '?', '#']) {
+        Some(end) if url.as_bytes()[end] == b':' => {
+            let scheme = &url[..end];
+            allowed_schemes
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(scheme))
         }
+        _ => allow_relative,
     }
-
-    true
 }
 
 /// Parses image dimensions from option like "100x200" or "100".
 fn parse_dimensions(opt: &str) -> Option<(u32, u32)> {
-    if let Some(x_pos) = opt.find(|c| c == 'x' || c == 'X') {
+    if let Some(x_pos) = opt.find(['x', 'X']) {
         let width: u32 = opt[..x_pos].parse().ok()?;
         let height: u32 = opt[x_pos + 1..].parse().ok()?;
         Some((width.min(2000), height.min(2000)))
@@ -1282,7 +4348,6 @@ static VALID_COLORS: &[&str] = &[
     "teal",
     "thistle",
     "tomato",
-    "transparent",
     "turquoise",
     "violet",
     "wheat",
@@ -1296,6 +4361,7 @@ static VALID_COLORS: &[&str] = &[
 mod tests {
     use super::*;
     use crate::parser::Parser;
+    use crate::tags::CustomTagDef;
 
     fn render(input: &str) -> String {
         let parser = Parser::new();
@@ -1315,13 +4381,21 @@ mod tests {
     fn render_text_with_html_entities() {
         assert_eq!(
             render("<script>alert('xss')</script>"),
-            "&lt;script&gt;alert(&#x27;xss&#x27;)&lt;/script&gt;"
+            "&lt;script&gt;alert('xss')&lt;/script&gt;"
         );
     }
 
     #[test]
-    fn render_quotes_escaped() {
-        assert_eq!(render("He said \"Hello\""), "He said &quot;Hello&quot;");
+    fn render_quotes_kept_in_text() {
+        assert_eq!(render("He said \"Hello\""), "He said \"Hello\"");
+    }
+
+    #[test]
+    fn render_quotes_escaped_in_attributes() {
+        assert_eq!(
+            render("[abbr=a\"b'c]x[/abbr]"),
+            "<abbr title=\"a&quot;b&#x27;c\">x</abbr>"
+        );
     }
 
     #[test]
@@ -1365,6 +4439,28 @@ mod tests {
         assert_eq!(render("x[sup]2[/sup]"), "x<sup>2</sup>");
     }
 
+    #[test]
+    fn render_highlight() {
+        assert_eq!(
+            render("[highlight][b]x[/b][/highlight]"),
+            "<mark><strong>x</strong></mark>"
+        );
+        assert_eq!(render("a [MARK]b[/mark] c"), "a <mark>b</mark> c");
+    }
+
+    #[test]
+    fn render_abbr() {
+        assert_eq!(
+            render("[abbr=HyperText Markup Language]HTML[/abbr]"),
+            "<abbr title=\"HyperText Markup Language\">HTML</abbr>"
+        );
+        assert_eq!(render("[abbr]CSS[/abbr]"), "<abbr>CSS</abbr>");
+        assert_eq!(
+            render("[abbr='<i> \"x\" onclick'][b]X[/b][/abbr]"),
+            "<abbr title=\"&lt;i&gt; &quot;x&quot; onclick\"><strong>X</strong></abbr>"
+        );
+    }
+
     // ==================== Color Tests ====================
 
     #[test]
@@ -1393,8 +4489,172 @@ mod tests {
         assert!(result.contains("[color=notacolor]"));
     }
 
+    #[test]
+    fn render_color_blocked_keywords() {
+        assert_eq!(
+            render("[color=transparent]x[/color]"),
+            "[color=transparent]x[/color]"
+        );
+        assert_eq!(
+            render("[color=ButtonFace]x[/color]"),
+            "[color=ButtonFace]x[/color]"
+        );
+        assert!(render("[color=red]x[/color]").contains("color: red;"));
+    }
+
+    #[test]
+    fn render_color_max_length() {
+        let doc = Parser::new().parse("[color=rgba(0, 0, 0, 0.5)]x[/color]");
+        let renderer = Renderer::with_config(RenderConfig {
+            max_color_length: 10,
+            ..Default::default()
+        });
+        assert!(!renderer.render(&doc).contains("style="));
+        assert!(Renderer::new()
+            .render(&doc)
+            .contains("color: rgba(0, 0, 0, 0.5);"));
+    }
+
+    #[test]
+    fn render_rejected_color_escaped_brackets() {
+        let doc = Parser::new().parse("[color=bad]x[/color]");
+        assert_eq!(Renderer::new().render(&doc), "[color=bad]x[/color]");
+
+        let renderer = Renderer::with_config(RenderConfig {
+            escape_rejected_brackets: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            renderer.render(&doc),
+            "&#91;color=bad&#93;x&#91;/color&#93;"
+        );
+
+        // Brackets in ordinary text are left alone
+        let doc = Parser::new().parse("[b]a [x] b[/b] [b]open");
+        assert_eq!(
+            renderer.render(&doc),
+            "<strong>a [x] b</strong> <strong>open</strong>"
+        );
+    }
+
     // ==================== Size Tests ====================
 
+    #[test]
+    fn render_color_comma_separated() {
+        assert_eq!(
+            render("[color=red, blue]x[/color]"),
+            "<span class=\"bbcode-color\" style=\"color: red;\">x</span>"
+        );
+        assert_eq!(
+            render("[color=bad, red]x[/color]"),
+            "[color=bad, red]x[/color]"
+        );
+        assert!(render("[size=4, 5]x[/size]").contains("font-size: 15px;"));
+    }
+
+    #[test]
+    fn render_color_without_inline_styles() {
+        let renderer = Renderer::with_config(RenderConfig {
+            use_inline_styles: false,
+            ..Default::default()
+        });
+
+        let result = renderer.render(&Parser::new().parse("[color=red]text[/color]"));
+        assert!(!result.contains("style="));
+        assert!(result.contains("class=\"bbcode-color bbcode-color-red\""));
+
+        let result = renderer.render(&Parser::new().parse("[color=#ff0000]text[/color]"));
+        assert!(!result.contains("style="));
+        assert!(result.contains("data-color=\"#ff0000\""));
+    }
+
+    #[test]
+    fn render_layout_without_inline_styles() {
+        let renderer = Renderer::with_config(RenderConfig {
+            use_inline_styles: false,
+            ..Default::default()
+        });
+        let doc = Parser::new()
+            .parse("[size=4]a[/size][size=150%]b[/size][center]c[/center][indent=2]d[/indent]");
+        let result = renderer.render(&doc);
+        assert!(!result.contains("style="));
+        assert!(result.contains("bbcode-size-4"));
+        assert!(result.contains("data-size=\"150%\""));
+        assert!(result.contains("bbcode-align-center"));
+        assert!(result.contains("bbcode-indent-2"));
+    }
+
+    fn render_merged(input: &str) -> String {
+        let renderer = Renderer::with_config(RenderConfig {
+            merge_style_spans: true,
+            ..Default::default()
+        });
+        renderer.render(&Parser::new().parse(input))
+    }
+
+    #[test]
+    fn render_merged_style_spans() {
+        let result = render_merged("[color=red][size=4]x[/size][/color]");
+        assert_eq!(
+            result,
+            "<span class=\"bbcode-color bbcode-size\" style=\"color: red; font-size: 15px;\">x</span>"
+        );
+    }
+
+    #[test]
+    fn render_merged_style_spans_validates_each() {
+        let result = render_merged("[color=red][size=999px]x[/size][/color]");
+        assert_eq!(
+            result,
+            "<span class=\"bbcode-color\" style=\"color: red;\">[size=999px]x[/size]</span>"
+        );
+    }
+
+    #[test]
+    fn render_merged_style_spans_skipped() {
+        // Intervening content
+        let result = render_merged("[color=red]a[size=4]x[/size][/color]");
+        assert_eq!(result.matches("<span").count(), 2);
+
+        // Non-style tag in between
+        let result = render_merged("[color=red][b][size=4]x[/size][/b][/color]");
+        assert_eq!(result.matches("<span").count(), 2);
+        assert!(result.contains("<strong>"));
+    }
+
+    #[test]
+    fn render_size_font_px_bounds() {
+        let renderer = Renderer::with_config(RenderConfig {
+            min_font_px: 12,
+            max_font_px: 20,
+            ..Default::default()
+        });
+        let size = |input: &str| renderer.render(&Parser::new().parse(input));
+        assert!(size("[size=7]x[/size]").contains("font-size: 20px;"));
+        assert!(size("[size=1]x[/size]").contains("font-size: 12px;"));
+        assert!(size("[size=30px]x[/size]").contains("font-size: 20px;"));
+        assert!(size("[size=200%]x[/size]").contains("font-size: 125%;"));
+        assert!(size("[size=50%]x[/size]").contains("font-size: 75%;"));
+        assert!(size("[size=5]x[/size]").contains("font-size: 18px;"));
+        assert_eq!(size("[size=99px]x[/size]"), "[size=99px]x[/size]");
+    }
+
+    #[test]
+    fn render_size_mode() {
+        let doc = Parser::new().parse("[size=20]x[/size]");
+        let render_mode = |size_mode| {
+            Renderer::with_config(RenderConfig {
+                size_mode,
+                ..Default::default()
+            })
+            .render(&doc)
+        };
+        assert!(render_mode(SizeMode::Auto).contains("font-size: 20%;"));
+        assert_eq!(render_mode(SizeMode::XenForoScale), "[size=20]x[/size]");
+        assert_eq!(render_mode(SizeMode::PhpbbPercent), "[size=20]x[/size]");
+        assert!(render_mode(SizeMode::Pixels).contains("font-size: 20px;"));
+    }
+
     #[test]
     fn render_size_numeric() {
         let result = render("[size=4]Large[/size]");
@@ -1415,6 +4675,136 @@ mod tests {
 
     // ==================== URL Tests ====================
 
+    #[test]
+    fn render_relative_urls() {
+        let doc = Parser::new().parse(
+            "[url=/foo/bar]a[/url] [img]//cdn.x.com/a.png[/img] [url=/x:javascript:alert(1)]b[/url]",
+        );
+        assert_eq!(
+            Renderer::new().render(&doc),
+            "[url=/foo/bar]a[/url] [img]//cdn.x.com/a.png[/img] [url=/x:javascript:alert(1)]b[/url]"
+        );
+
+        let renderer = Renderer::with_config(RenderConfig {
+            allow_relative_urls: true,
+            ..Default::default()
+        });
+        let result = renderer.render(&doc);
+        assert!(result.contains("<a class=\"bbcode-url\" href=\"/foo/bar\""));
+        assert!(result.contains("src=\"//cdn.x.com/a.png\""));
+        assert!(result.contains("href=\"/x:javascript:alert(1)\""));
+
+        // Links starting with a host name get a scheme instead; other
+        // scheme-less links are relative
+        let doc = Parser::new()
+            .parse("[url=www.example.com]a[/url] [url]example.com/a[/url] [url=page.html]b[/url]");
+        assert_eq!(
+            Renderer::new().render(&doc),
+            "<a class=\"bbcode-url\" href=\"https://www.example.com\" rel=\"nofollow\">a</a> \
+             <a class=\"bbcode-url\" href=\"https://example.com/a\" rel=\"nofollow\">example.com/a</a> \
+             [url=page.html]b[/url]"
+        );
+        assert!(renderer
+            .render(&doc)
+            .contains("<a class=\"bbcode-url\" href=\"page.html\""));
+        assert_eq!(with_inferred_scheme("docs/page.html"), "docs/page.html");
+        assert_eq!(
+            with_inferred_scheme("WWW.x.com?a=1"),
+            "https://WWW.x.com?a=1"
+        );
+
+        // Schemes and attribute breakouts are still checked
+        let doc = Parser::new().parse(
+            "[url=javascript:alert(1)]a[/url] [url]/a\"onclick=x[/url] [url=ftp://x.com/]b[/url]",
+        );
+        assert!(!renderer.render(&doc).contains("<a "));
+    }
+
+    #[test]
+    fn render_url_internal_host_stays_in_tab() {
+        let renderer = Renderer::with_config(RenderConfig {
+            open_links_in_new_tab: true,
+            internal_host: Some("Forum.Example.com".into()),
+            ..Default::default()
+        });
+        let doc = Parser::new().parse(
+            "[url=http://forum.EXAMPLE.com:8080/t/1]in[/url] \
+             [url]https://other.example.com/forum.example.com[/url] https://user@forum.example.com/x",
+        );
+        let result = renderer.render(&doc);
+        assert!(result.contains(
+            "<a class=\"bbcode-url\" href=\"http://forum.EXAMPLE.com:8080/t/1\" rel=\"nofollow\">in</a>"
+        ));
+        assert!(result.contains(
+            "href=\"https://other.example.com/forum.example.com\" rel=\"nofollow noopener\" target=\"_blank\""
+        ));
+        assert_eq!(result.matches("target=").count(), 1);
+
+        // Relative links are internal; a backslash can't hide the real host
+        let renderer = Renderer::with_config(RenderConfig {
+            open_links_in_new_tab: true,
+            internal_host: Some("forum.example.com".into()),
+            allow_relative_urls: true,
+            ..Default::default()
+        });
+        let doc = Parser::new().parse(
+            "[url=/t/1]a[/url] [url=?page=2]b[/url] [url=https://evil.com\\@forum.example.com]c[/url]",
+        );
+        let result = renderer.render(&doc);
+        assert!(result.contains("href=\"/t/1\" rel=\"nofollow\">a</a>"));
+        assert!(result.contains("href=\"?page=2\" rel=\"nofollow\">b</a>"));
+        assert!(result.contains("rel=\"nofollow noopener\" target=\"_blank\">c</a>"));
+    }
+
+    #[test]
+    fn url_host_test() {
+        assert_eq!(url_host("https://a.com/x"), Some("a.com"));
+        assert_eq!(url_host("HTTP://u:p@a.com:80?q"), Some("a.com"));
+        assert_eq!(url_host("//a.com#f"), Some("a.com"));
+        assert_eq!(url_host("https://[::1]/"), Some("[::1]"));
+        assert_eq!(url_host("/relative"), None);
+        assert_eq!(url_host("https://evil.com\\@a.com/"), Some("evil.com"));
+        assert_eq!(url_host("mailto:a@b.com"), None);
+    }
+
+    #[test]
+    fn render_url_rel_with_new_tab() {
+        let doc = Parser::new().parse("[url=https://example.com]x[/url] https://example.org");
+        let renderer = Renderer::with_config(RenderConfig {
+            open_links_in_new_tab: true,
+            ..Default::default()
+        });
+        let result = renderer.render(&doc);
+        assert_eq!(result.matches("rel=\"nofollow noopener\"").count(), 2);
+    }
+
+    #[test]
+    fn render_url_custom_rel_tokens() {
+        let doc = Parser::new().parse("[url=https://example.com]x[/url]");
+        let renderer = Renderer::with_config(RenderConfig {
+            link_rel: vec!["noreferrer".into(), "noopener".into()],
+            open_links_in_new_tab: true,
+            ..Default::default()
+        });
+        assert!(renderer
+            .render(&doc)
+            .contains("rel=\"noreferrer noopener\""));
+
+        // The deprecated nofollow switch doesn't override a custom link_rel
+        let renderer = Renderer::with_config(RenderConfig {
+            link_rel: vec!["ugc".into()],
+            ..Default::default()
+        });
+        assert!(renderer.render(&doc).contains("rel=\"ugc\""));
+
+        #[allow(deprecated)]
+        let renderer = Renderer::with_config(RenderConfig {
+            nofollow_links: false,
+            ..Default::default()
+        });
+        assert!(!renderer.render(&doc).contains("rel="));
+    }
+
     #[test]
     fn render_url_with_option() {
         let result = render("[url=https://example.com]Click here[/url]");
@@ -1429,6 +4819,82 @@ mod tests {
         assert!(result.contains("href=\"https://example.com\""));
     }
 
+    #[test]
+    fn render_url_wrapping_img() {
+        assert_eq!(
+            render("[url=https://x.com][img]https://y.com/a.png[/img][/url]"),
+            "<a class=\"bbcode-url\" href=\"https://x.com\" rel=\"nofollow\">\
+             <img class=\"bbcode-img\" src=\"https://y.com/a.png\" /></a>"
+        );
+        assert_eq!(
+            render("[url][img]https://y.com/a.png[/img][/url]"),
+            "<a class=\"bbcode-url\" href=\"https://y.com/a.png\" rel=\"nofollow\">\
+             <img class=\"bbcode-img\" src=\"https://y.com/a.png\" /></a>"
+        );
+
+        // The image is still validated on its own
+        let result = render("[url=https://x.com][img]javascript:alert(1)[/img][/url]");
+        assert!(result.contains("href=\"https://x.com\""));
+        assert!(!result.contains("<img"));
+        assert!(!render("[url][img]javascript:alert(1)[/img][/url]").contains("<a"));
+    }
+
+    #[test]
+    fn render_url_title() {
+        assert_eq!(
+            render("[url=https://x.com title=Home]x[/url]"),
+            "<a class=\"bbcode-url\" href=\"https://x.com\" title=\"Home\" rel=\"nofollow\">x</a>"
+        );
+        assert!(render("[url title=\"Our site\"]https://x.com[/url]")
+            .contains("href=\"https://x.com\" title=\"Our site\""));
+
+        let result = render("[url=https://x.com title='\" onmouseover=\"alert(1)']x[/url]");
+        assert!(result.contains("title=\"&quot; onmouseover=&quot;alert(1)\""));
+        assert!(!result.contains("\" onmouseover"));
+    }
+
+    #[test]
+    fn render_url_aria_label() {
+        let long = "[url]https://example.com/a/very/long/path?with=query[/url]";
+        assert!(!render(long).contains("aria-label"));
+
+        let renderer = Renderer::with_config(RenderConfig {
+            url_aria_label_format: Some("Link to {host}".into()),
+            ..Default::default()
+        });
+        let input = format!("{long} https://www.example.org/x [url=https://x.com]X[/url]");
+        let doc = Parser::new().parse(&input);
+        let result = renderer.render(&doc);
+        assert!(result.contains(
+            "href=\"https://example.com/a/very/long/path?with=query\" aria-label=\"Link to example.com\""
+        ));
+        assert!(result.contains("aria-label=\"Link to www.example.org\""));
+        assert_eq!(result.matches("aria-label").count(), 2);
+    }
+
+    #[test]
+    fn render_url_max_display_len() {
+        let url = format!("https://example.com/{}xy", "ä/b".repeat(26));
+        assert_eq!(url.chars().count(), 100);
+
+        let renderer = Renderer::with_config(RenderConfig {
+            max_display_url_len: Some(30),
+            ..Default::default()
+        });
+        for input in [format!("[url]{url}[/url]"), format!("see {url}")] {
+            let result = renderer.render(&Parser::new().parse(&input));
+            assert!(result.contains(&format!("href=\"{url}\"")));
+            assert!(result.contains(">https://example.com/ä/bä/bä/b…</a>"));
+        }
+
+        // Short URLs and link text from the option are left alone
+        let result = renderer.render(
+            &Parser::new().parse(&format!("[url]https://x.com[/url] [url={url}]{url}[/url]")),
+        );
+        assert!(result.contains(">https://x.com</a>"));
+        assert!(result.contains(&format!(">{url}</a>")));
+    }
+
     #[test]
     fn render_url_xss_prevention() {
         let result = render("[url=javascript:alert('xss')]Click[/url]");
@@ -1442,6 +4908,97 @@ mod tests {
         assert!(result.contains("href=\"https://example.com\""));
     }
 
+    #[test]
+    fn render_auto_url_in_inline_formatting() {
+        assert_eq!(
+            render("[b]https://x.com[/b]"),
+            "<strong><a class=\"bbcode-url\" href=\"https://x.com\" rel=\"nofollow\">https://x.com</a></strong>"
+        );
+
+        let result = render("[i][b]go https://x.com[/b][/i]");
+        assert!(result.starts_with("<em><strong>go <a "));
+        assert!(result.contains("href=\"https://x.com\""));
+    }
+
+    #[test]
+    fn render_auto_url_not_nested_in_link() {
+        let result = render("[url=https://a.com]see https://x.com[/url]");
+        assert_eq!(result.matches("<a ").count(), 1);
+        assert!(result.contains("see https://x.com</a>"));
+
+        let result = render("[b][url=https://a.com][i]https://x.com[/i][/url][/b]");
+        assert_eq!(result.matches("<a ").count(), 1);
+    }
+
+    #[test]
+    fn render_auto_url_checks_schemes() {
+        let renderer = Renderer::with_config(RenderConfig::with_schemes(&["https"]));
+        let doc = Parser::new().parse("a http://x.com b https://y.com");
+        assert_eq!(
+            renderer.render(&doc),
+            "a http://x.com b <a class=\"bbcode-url\" href=\"https://y.com\" \
+             rel=\"nofollow\">https://y.com</a>"
+        );
+
+        let parser = Parser::with_config(crate::ParserConfig {
+            auto_link_www: true,
+            ..Default::default()
+        });
+        let renderer = Renderer::with_config(RenderConfig::with_schemes(&["http"]));
+        assert_eq!(
+            renderer.render(&parser.parse("www.example.com")),
+            "www.example.com"
+        );
+    }
+
+    #[test]
+    fn render_auto_url_disabled() {
+        let parser = Parser::with_config(crate::ParserConfig {
+            auto_link: false,
+            ..Default::default()
+        });
+        let doc = parser.parse("[b]https://x.com[/b]");
+        let result = Renderer::new().render(&doc);
+        assert_eq!(result, "<strong>https://x.com</strong>");
+    }
+
+    #[test]
+    fn render_auto_link_www_and_emails() {
+        let parser = Parser::with_config(crate::ParserConfig {
+            auto_link_www: true,
+            auto_link_emails: true,
+            ..Default::default()
+        });
+        let result = Renderer::new().render(&parser.parse("visit www.example.com."));
+        assert_eq!(
+            result,
+            "visit <a class=\"bbcode-url\" href=\"https://www.example.com\" rel=\"nofollow\">\
+             www.example.com</a>."
+        );
+        // Tagged `www.` links get the same scheme
+        assert!(Renderer::new()
+            .render(&parser.parse("[url]www.example.com[/url]"))
+            .contains("href=\"https://www.example.com\""));
+
+        let result = Renderer::new().render(&parser.parse("mail me@x.com."));
+        assert_eq!(
+            result,
+            "mail <a class=\"bbcode-email\" href=\"mailto:me@x.com\">me@x.com</a>."
+        );
+
+        // The parser's link kind decides, not an `@` in the URL
+        let result = Renderer::new().render(&parser.parse("www.x.com?a=b@c.com"));
+        assert_eq!(
+            result,
+            "<a class=\"bbcode-url\" href=\"https://www.x.com?a=b@c.com\" rel=\"nofollow\">\
+             www.x.com?a=b@c.com</a>"
+        );
+
+        // Not inside links
+        let result = Renderer::new().render(&parser.parse("[url=https://a.com]www.x.com[/url]"));
+        assert_eq!(result.matches("<a ").count(), 1);
+    }
+
     // ==================== Email Tests ====================
 
     #[test]
@@ -1457,6 +5014,54 @@ mod tests {
         assert!(result.contains("href=\"mailto:test@example.com\""));
     }
 
+    #[test]
+    fn render_email_obfuscated() {
+        let renderer = Renderer::with_config(RenderConfig {
+            obfuscate_emails: true,
+            ..Default::default()
+        });
+        let doc =
+            Parser::new().parse("[email]a@b.co[/email] [email=a@b.co?subject=Hi]Mail[/email]");
+        let result = renderer.render(&doc);
+        assert!(!result.contains('@'));
+        assert!(!result.contains("mailto"));
+        assert!(result.contains(
+            "href=\"&#109;&#97;&#105;&#108;&#116;&#111;&#58;&#97;&#64;&#98;&#46;&#99;&#111;\">\
+             &#97;&#64;&#98;&#46;&#99;&#111;</a>"
+        ));
+        assert!(result.contains("&#111;?subject=Hi\">Mail</a>"));
+
+        let doc = Parser::with_config(crate::ParserConfig {
+            auto_link_emails: true,
+            ..Default::default()
+        })
+        .parse("mail a@b.co");
+        let result = renderer.render(&doc);
+        assert!(result.contains("&#"));
+        assert!(!result.contains('@'));
+
+        // Validation still applies
+        let doc = Parser::new().parse("[email]a\"@b.co[/email]");
+        assert!(!renderer.render(&doc).contains("<a"));
+    }
+
+    #[test]
+    fn render_email_mailto_params() {
+        let result =
+            render("[email=\"support@x.com?subject=Need help&body=50%25 off\"]Contact[/email]");
+        assert!(result.contains(
+            "href=\"mailto:support@x.com?subject=Need%20help&amp;body=50%25%20off\">Contact</a>"
+        ));
+
+        let result =
+            render("[email=\"a@b.com?onclick=alert(1)&Subject=Hi&x=1&cc=c@d.com\"]Mail[/email]");
+        assert!(result.contains("href=\"mailto:a@b.com?subject=Hi&amp;cc=c%40d.com\""));
+        assert!(!result.contains("onclick"));
+
+        let result = render("[email]a@b.com?subject=x\" onmouseover=\"y[/email]");
+        assert!(result.contains("href=\"mailto:a@b.com?subject=x%22%20onmouseover%3D%22y\""));
+    }
+
     // ==================== Image Tests ====================
 
     #[test]
@@ -1466,6 +5071,33 @@ mod tests {
         assert!(result.contains("<img"));
     }
 
+    #[test]
+    fn render_img_alt_and_title() {
+        let result = render("[img alt=Logo title=Home]https://example.com/logo.png[/img]");
+        assert!(result.contains(" alt=\"Logo\" title=\"Home\""));
+
+        let result = render("[img alt=\"Our logo\" title='<x>']https://example.com/logo.png[/img]");
+        assert!(result.contains(" alt=\"Our logo\" title=\"&lt;x&gt;\""));
+        assert!(!result.contains("<x>"));
+
+        assert!(!render("[img]https://example.com/a.png[/img]").contains("alt="));
+        let renderer = Renderer::with_config(RenderConfig {
+            default_img_alt: "Image".into(),
+            ..Default::default()
+        });
+        let doc = Parser::new().parse(
+            "[img]https://example.com/a.png[/img][img alt=Cat]https://example.com/b.png[/img]",
+        );
+        let result = renderer.render(&doc);
+        assert!(result.contains("a.png\" alt=\"Image\""));
+        assert!(result.contains("b.png\" alt=\"Cat\""));
+
+        let doc = Parser::new().parse("[img alt=\"\"]https://example.com/c.png[/img]");
+        assert!(renderer.render(&doc).contains("c.png\" alt=\"\" />"));
+        assert!(Renderer::new().render(&doc).contains("c.png\" alt=\"\" />"));
+        assert!(!render("[img]https://example.com/d.png[/img]").contains("alt="));
+    }
+
     #[test]
     fn render_img_with_dimensions() {
         let result = render("[img=100x200]https://example.com/image.png[/img]");
@@ -1473,6 +5105,53 @@ mod tests {
         assert!(result.contains("height=\"200\""));
     }
 
+    #[test]
+    fn render_img_clamped_to_max_width() {
+        let doc = Parser::new().parse("[img=1000x800]https://example.com/image.png[/img]");
+        let renderer = Renderer::with_config(RenderConfig {
+            max_image_width: Some(500),
+            ..Default::default()
+        });
+        let result = renderer.render(&doc);
+        assert!(result.contains("width=\"500\""));
+        assert!(result.contains("height=\"400\""));
+    }
+
+    #[test]
+    fn render_img_clamped_to_max_height() {
+        let doc = Parser::new().parse("[img width=300]https://example.com/image.png[/img]");
+        let renderer = Renderer::with_config(RenderConfig {
+            max_image_width: Some(200),
+            max_image_height: Some(100),
+            ..Default::default()
+        });
+        assert!(renderer.render(&doc).contains("width=\"200\""));
+
+        let doc = Parser::new().parse("[img=400x400]https://example.com/image.png[/img]");
+        let result = renderer.render(&doc);
+        assert!(result.contains("width=\"100\""));
+        assert!(result.contains("height=\"100\""));
+    }
+
+    #[test]
+    fn render_img_url_rewriter() {
+        let renderer = Renderer::with_config(RenderConfig {
+            image_url_rewriter: Some(Arc::new(|url: &str| {
+                format!("https://proxy.example/?u={}", url)
+            })),
+            ..Default::default()
+        });
+
+        let doc = Parser::new().parse("[img]http://example.com/a.png[/img]");
+        assert!(renderer
+            .render(&doc)
+            .contains("src=\"https://proxy.example/?u=http://example.com/a.png\""));
+
+        // Dangerous URLs are rejected before the rewriter runs
+        let doc = Parser::new().parse("[img]javascript:alert(1)[/img]");
+        assert!(!renderer.render(&doc).contains("proxy.example"));
+    }
+
     #[test]
     fn render_img_xss_prevention() {
         let result = render("[img]javascript:alert('xss')[/img]");
@@ -1482,18 +5161,865 @@ mod tests {
     // ==================== Quote Tests ====================
 
     #[test]
-    fn render_quote() {
-        let result = render("[quote]Quoted text[/quote]");
-        assert!(result.contains("<blockquote"));
-        assert!(result.contains("Quoted text"));
+    fn render_quote() {
+        let result = render("[quote]Quoted text[/quote]");
+        assert!(result.contains("<blockquote"));
+        assert!(result.contains("Quoted text"));
+    }
+
+    #[test]
+    fn render_quote_with_author() {
+        let result = render("[quote=\"John\"]Quoted text[/quote]");
+        assert!(result.contains("<blockquote"));
+        assert!(result.contains("John wrote:"));
+        assert!(result.contains("Quoted text"));
+    }
+
+    #[test]
+    fn render_quote_attribution_format() {
+        let doc = Parser::new().parse("[quote=\"Jean\"]x[/quote]");
+        let renderer = Renderer::with_config(RenderConfig {
+            quote_attribution_format: "{author} a écrit :".into(),
+            ..Default::default()
+        });
+        assert!(renderer.render(&doc).contains(">Jean a écrit :</div>"));
+
+        let doc = Parser::new().parse("[quote=\"<b>\"]x[/quote]");
+        assert!(renderer.render(&doc).contains(">&lt;b&gt; a écrit :</div>"));
+
+        // The template is HTML, so the author is escaped for attributes too
+        let attr_renderer = Renderer::with_config(RenderConfig {
+            quote_attribution_format: "<cite title=\"{author}\">{author}</cite>".into(),
+            ..Default::default()
+        });
+        let doc = Parser::new().parse("[quote='x\" onmouseover=\"alert(1)']y[/quote]");
+        let result = attr_renderer.render(&doc);
+        assert!(result.contains("<cite title=\"x&quot; onmouseover=&quot;alert(1)\">"));
+        assert!(!result.contains("\" onmouseover"));
+
+        let renderer = Renderer::with_config(RenderConfig {
+            quote_attribution_format: "Quote:".into(),
+            ..Default::default()
+        });
+        assert!(renderer.render(&doc).contains(">Quote:</div>"));
+    }
+
+    #[test]
+    fn render_quote_author_as_link() {
+        let renderer = Renderer::with_config(RenderConfig {
+            quote_author_as_link: true,
+            ..Default::default()
+        });
+
+        let doc = Parser::new().parse("[quote=https://forum.example/x/post/5]x[/quote]");
+        let result = renderer.render(&doc);
+        assert!(result.contains(
+            "<a class=\"bbcode-quote-source\" href=\"https://forum.example/x/post/5\" rel=\"nofollow\">Source</a>"
+        ));
+        assert!(!result.contains("wrote:"));
+
+        let doc = Parser::new().parse("[quote=\"Name\"]x[/quote]");
+        assert!(renderer.render(&doc).contains(">Name wrote:</div>"));
+
+        let doc = Parser::new().parse("[quote=javascript://alert(1)]x[/quote]");
+        assert!(!renderer.render(&doc).contains("<a"));
+
+        let renderer = Renderer::with_config(RenderConfig {
+            quote_author_as_link: true,
+            quote_source_label: "<Quelle>".into(),
+            ..Default::default()
+        });
+        let doc = Parser::new().parse("[quote=https://forum.example/x/post/5]x[/quote]");
+        assert!(renderer
+            .render(&doc)
+            .contains("rel=\"nofollow\">&lt;Quelle&gt;</a></div>"));
+    }
+
+    #[test]
+    fn render_tag_filter() {
+        let renderer = Renderer::with_config(RenderConfig {
+            tag_filter: Some(Arc::new(|tag: &TagNode| {
+                match tag.name.to_ascii_lowercase().as_str() {
+                    "img" | "image" => TagDecision::AsText,
+                    "spoiler" => TagDecision::Skip,
+                    _ => TagDecision::Render,
+                }
+            })),
+            ..Default::default()
+        });
+        let doc = Parser::new()
+            .parse("[b]a[/b][img]https://x.com/a.png[/img][spoiler]secret [b]x[/b][/spoiler]z");
+        assert_eq!(
+            renderer.render(&doc),
+            "<strong>a</strong>[img]https://x.com/a.png[/img]z"
+        );
+
+        let doc = Parser::new().parse("[IMAGE]https://x.com/a.png[/IMAGE]");
+        assert_eq!(renderer.render(&doc), "[IMAGE]https://x.com/a.png[/IMAGE]");
+    }
+
+    #[test]
+    fn render_tag_filter_merged_styles() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        let renderer = Renderer::with_config(RenderConfig {
+            merge_style_spans: true,
+            tag_filter: Some(Arc::new(move |tag: &TagNode| {
+                log.lock().unwrap().push(tag.name.to_string());
+                match &*tag.name {
+                    "font" => TagDecision::AsText,
+                    "size" => TagDecision::Skip,
+                    _ => TagDecision::Render,
+                }
+            })),
+            ..Default::default()
+        });
+        let doc = Parser::new().parse(
+            "[color=red][colour=blue][font=Arial]a[/font][/colour][/color] \
+             [color=red][size=150]b[/size][/color]",
+        );
+        assert_eq!(
+            renderer.render(&doc),
+            "<span class=\"bbcode-color\" style=\"color: red; color: blue;\">\
+             [font=Arial]a[/font]</span> \
+             <span class=\"bbcode-color\" style=\"color: red;\"></span>"
+        );
+        assert_eq!(
+            *seen.lock().unwrap(),
+            ["color", "colour", "font", "color", "size"]
+        );
+    }
+
+    #[test]
+    fn render_code_class_format() {
+        let renderer = Renderer::with_config(RenderConfig {
+            code_class_format: "hljs {lang}".into(),
+            ..Default::default()
+        });
+        let doc = Parser::new().parse("[code=rust]x[/code][php]y[/php][code=a\"b]z[/code]");
+        let result = renderer.render(&doc);
+        assert!(result.contains("<code class=\"hljs rust\">x</code>"));
+        assert!(result.contains("<code class=\"hljs php\">"));
+        assert!(!result.contains("language-"));
+        assert!(result.contains("<code class=\"hljs a&quot;b\">z"));
+    }
+
+    #[test]
+    fn render_attachment() {
+        assert_eq!(render("[attach]123[/attach]"), "[attach]123[/attach]");
+
+        let renderer = Renderer::with_config(RenderConfig {
+            attachment_resolver: Some(Arc::new(|id: &str| match id {
+                "123" => Some(AttachmentInfo {
+                    url: "https://cdn.example/123.png".into(),
+                    alt: "Photo".into(),
+                    mime_type: "image/png".into(),
+                }),
+                "456" => Some(AttachmentInfo {
+                    url: "https://cdn.example/456.zip".into(),
+                    alt: String::new(),
+                    mime_type: "application/zip".into(),
+                }),
+                _ => None,
+            })),
+            ..Default::default()
+        });
+        let doc = Parser::new().parse("[attach]123[/attach]");
+        assert_eq!(
+            renderer.render(&doc),
+            "<img class=\"bbcode-attachment\" src=\"https://cdn.example/123.png\" alt=\"Photo\" />"
+        );
+        let doc = Parser::new().parse("[attachment=456]file[/attachment]");
+        assert_eq!(
+            renderer.render(&doc),
+            "<a class=\"bbcode-attachment\" href=\"https://cdn.example/456.zip\">456</a>"
+        );
+        let doc = Parser::new().parse("[attach]789[/attach]");
+        assert_eq!(renderer.render(&doc), "[attach]789[/attach]");
+    }
+
+    #[test]
+    fn render_quote_post_metadata() {
+        let result = render("[quote=\"Bob, post: 99\"]x[/quote]");
+        assert!(result.starts_with(
+            "<blockquote class=\"bbcode-quote\" data-author=\"Bob\" data-post=\"99\">"
+        ));
+        assert!(result.contains(">Bob wrote:</div>"));
+
+        let renderer = Renderer::with_config(RenderConfig {
+            post_url_resolver: Some(Arc::new(|post: &str| {
+                Some(format!("https://forum.example/posts/{}/", post))
+            })),
+            ..Default::default()
+        });
+        let doc = Parser::new()
+            .parse("[quote=\"Bob, post: 99, member: 7\"]x[/quote][quote=Ann]y[/quote]");
+        let result = renderer.render(&doc);
+        assert!(result.contains("data-author=\"Bob\" data-post=\"99\" data-member=\"7\""));
+        assert!(result.contains(
+            "<div class=\"bbcode-quote-author\"><a class=\"bbcode-quote-link\" \
+             href=\"https://forum.example/posts/99/\">Bob wrote:</a></div>"
+        ));
+        assert!(result.contains("<div class=\"bbcode-quote-author\">Ann wrote:</div>"));
+
+        // Commas that aren't metadata stay part of the name
+        for name in ["Smith, John", "Bob, post: x", "Bob, rank: 1"] {
+            let result = render(&format!("[quote=\"{}\"]x[/quote]", name));
+            assert!(
+                result.contains(&format!("data-author=\"{}\">", name)),
+                "{}",
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn render_quote_cite() {
+        let result = render("[quote cite=https://example.com/post/1]x[/quote]");
+        assert!(result.starts_with(
+            "<blockquote class=\"bbcode-quote\" cite=\"https://example.com/post/1\">"
+        ));
+
+        let result = render("[quote cite=javascript:alert(1)]x[/quote]");
+        assert!(!result.contains("cite="));
+        assert!(result.contains("x</div></blockquote>"));
+
+        let result = render("[code cite=https://example.com/src.rs]fn main() {}[/code]");
+        assert!(result.contains("<pre class=\"bbcode-code\" cite=\"https://example.com/src.rs\">"));
+    }
+
+    // ==================== Smilie Tests ====================
+
+    fn smilie_renderer(max_smilies: Option<usize>) -> Renderer {
+        let mut smilies = Map::new();
+        smilies.insert(":)".to_string(), "/smilies/smile.png".to_string());
+        smilies.insert(":-)".to_string(), "/smilies/smile.png".to_string());
+        smilies.insert("<3".to_string(), "\u{2764}".to_string());
+        Renderer::with_config(RenderConfig {
+            smilies,
+            max_smilies,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn render_smilies() {
+        let doc = Parser::new().parse("Hi :) and :-) [b]<3[/b]");
+        assert_eq!(
+            smilie_renderer(None).render(&doc),
+            "Hi <img class=\"bbcode-smilie\" src=\"/smilies/smile.png\" alt=\":)\" title=\":)\" /> \
+             and <img class=\"bbcode-smilie\" src=\"/smilies/smile.png\" alt=\":-)\" title=\":-)\" /> \
+             <strong>\u{2764}</strong>"
+        );
+
+        let doc = Parser::new().parse("[code]:)[/code]");
+        assert!(!smilie_renderer(None).render(&doc).contains("<img"));
+    }
+
+    #[test]
+    fn render_max_smilies() {
+        let doc = Parser::new().parse(":) :) [i]:)[/i] :) <3");
+        let result = smilie_renderer(Some(2)).render(&doc);
+        assert_eq!(result.matches("<img").count(), 2);
+        assert!(result.ends_with("<em>:)</em> :) &lt;3"));
+
+        let result = smilie_renderer(Some(0)).render(&doc);
+        assert!(!result.contains("<img"));
+    }
+
+    #[test]
+    fn render_smilies_large_input() {
+        let mut smilies: Map<String, String> = (0..50)
+            .map(|i| (format!(":s{}:", i), "/smilies/s.png".to_string()))
+            .collect();
+        smilies.insert(":)".to_string(), "/smilies/smile.png".to_string());
+        let input = ":) ".repeat(30_000);
+        let doc = Parser::new().parse(&input);
+
+        for max in [Some(5), None] {
+            let renderer = Renderer::with_config(RenderConfig {
+                smilies: smilies.clone(),
+                max_smilies: max,
+                ..Default::default()
+            });
+            let result = renderer.render(&doc);
+            let images = result.matches("<img").count();
+            assert_eq!(images, max.unwrap_or(30_000));
+            // Each image repeats its code in `alt` and `title`
+            assert_eq!(result.matches(":)").count(), 30_000 + images);
+        }
+    }
+
+    #[test]
+    fn render_max_output_len() {
+        let input = "[quote][b]x[/b] some text[/quote]\n".repeat(1000);
+        let doc = Parser::new().parse(&input);
+        let full = Renderer::new().render(&doc);
+        assert!(full.len() > 50_000);
+
+        for max in [0, 10, 100, 1000] {
+            let renderer = Renderer::with_config(RenderConfig {
+                max_output_len: Some(max),
+                ..Default::default()
+            });
+            let result = renderer.render(&doc);
+            assert!(result.len() <= max, "{} > {}", result.len(), max);
+            assert_well_formed(&result);
+            assert_eq!(renderer.try_render(&doc).unwrap(), result);
+        }
+
+        let renderer = Renderer::with_config(RenderConfig {
+            max_output_len: Some(full.len()),
+            ..Default::default()
+        });
+        assert_eq!(renderer.render(&doc), full);
+
+        // Long attribute values are never cut
+        let input = "[url=https://example.com/a/long/path]link[/url] ".repeat(50);
+        let url_doc = Parser::new().parse(&input);
+        for max in [30, 60, 200, 500] {
+            let renderer = Renderer::with_config(RenderConfig {
+                max_output_len: Some(max),
+                ..Default::default()
+            });
+            let result = renderer.render(&url_doc);
+            assert!(result.len() <= max, "{} > {}", result.len(), max);
+            assert_well_formed(&result);
+            assert_eq!(
+                result.matches("<a ").count(),
+                result.matches("</a>").count()
+            );
+        }
+    }
+
+    /// Asserts that every element in `html` is closed in order and that no
+    /// tag is cut off.
+    fn assert_well_formed(html: &str) {
+        const VOID: &[&str] = &["br", "hr", "img", "input", "wbr"];
+        let mut open = Vec::new();
+        let mut rest = html;
+        while let Some(start) = rest.find('<') {
+            let end = rest[start..].find('>').expect("unterminated tag") + start;
+            let tag = &rest[start + 1..end];
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(open.pop(), Some(name), "in {}", html);
+            } else {
+                let name = tag.split([' ', '/']).next().unwrap();
+                if !VOID.contains(&name) {
+                    open.push(name);
+                }
+            }
+            rest = &rest[end + 1..];
+        }
+        assert!(open.is_empty(), "unclosed {:?} in {}", open, html);
+    }
+
+    // ==================== Render To Tests ====================
+
+    #[test]
+    fn render_to_shared_buffer() {
+        let parser = Parser::new();
+        let renderer = Renderer::new();
+        let mut buffer = String::from("<div>");
+        renderer
+            .render_to(&parser.parse("[b]one[/b]"), &mut buffer)
+            .unwrap();
+        renderer
+            .render_to(&parser.parse("[i]two[/i]"), &mut buffer)
+            .unwrap();
+        assert_eq!(buffer, "<div><strong>one</strong><em>two</em>");
+    }
+
+    #[test]
+    fn render_to_propagates_writer_error() {
+        struct FailingWriter;
+        impl fmt::Write for FailingWriter {
+            fn write_str(&mut self, _: &str) -> fmt::Result {
+                Err(fmt::Error)
+            }
+        }
+
+        let doc = Parser::new().parse("[b]x[/b]");
+        assert!(Renderer::new().render_to(&doc, &mut FailingWriter).is_err());
+    }
+
+    // ==================== Custom Handler Tests ====================
+
+    struct UserLookup;
+
+    impl CustomTagHandler for UserLookup {
+        fn tag_name(&self) -> &str {
+            "user"
+        }
+
+        fn render(&self, _tag: &TagNode, _ctx: &RenderContext, _output: &mut String) -> bool {
+            false
+        }
+
+        fn try_render(
+            &self,
+            tag: &TagNode,
+            _ctx: &RenderContext,
+            output: &mut String,
+        ) -> Result<bool, RenderError> {
+            let id = tag.option.as_scalar().map_or("", |id| id.as_ref());
+            if id == "1" {
+                output.push_str("<a href=\"/members/1\">@admin</a>");
+                return Ok(true);
+            }
+            Err(RenderError::Generic {
+                message: Cow::Owned(format!("unknown user {}", id)),
+            })
+        }
+    }
+
+    struct Greeting;
+
+    impl CustomTagHandler for Greeting {
+        fn tag_name(&self) -> &str {
+            "greeting"
+        }
+
+        fn render(&self, _tag: &TagNode, ctx: &RenderContext, output: &mut String) -> bool {
+            let name = ctx.get("user_name").unwrap_or("guest");
+            output.push_str("Hello, ");
+            output.push_str(&escape_html_text(name));
+            output.push('!');
+            true
+        }
+    }
+
+    #[test]
+    fn custom_handler_reads_context_data() {
+        let mut registry = TagRegistry::new();
+        registry.register_custom(CustomTagDef::new("greeting"));
+        let parser = Parser::with_registry(registry.clone());
+        let doc = parser.parse("[greeting][/greeting]");
+
+        let mut renderer = Renderer::with_config_and_registry(RenderConfig::default(), registry);
+        renderer.register_handler(Arc::new(Greeting));
+        assert_eq!(renderer.render(&doc), "Hello, guest!");
+
+        let mut context = Map::new();
+        context.insert("user_name".to_string(), "<Alice>".to_string());
+        assert_eq!(
+            renderer.render_with_context(&doc, &context),
+            "Hello, &lt;Alice&gt;!"
+        );
+        assert_eq!(renderer.render(&doc), "Hello, guest!");
+    }
+
+    #[test]
+    fn try_render_propagates_handler_error() {
+        let mut renderer = Renderer::new();
+        renderer.register_handler(Arc::new(UserLookup));
+        let doc = Parser::new().parse("[b]x[/b] [user=1]A[/user] [user=2]B[/user]");
+
+        assert_eq!(
+            renderer.try_render(&doc),
+            Err(RenderError::Generic {
+                message: Cow::Borrowed("unknown user 2"),
+            })
+        );
+        assert_eq!(
+            renderer.render(&doc),
+            "<strong>x</strong> <a href=\"/members/1\">@admin</a> [user=2]B[/user]"
+        );
+    }
+
+    #[test]
+    fn try_render_without_errors() {
+        let mut renderer = Renderer::new();
+        renderer.register_handler(Arc::new(UserLookup));
+        let doc = Parser::new().parse("[i]a[/i][user=1]A[/user]");
+        assert_eq!(renderer.try_render(&doc).unwrap(), renderer.render(&doc));
+    }
+
+    struct WikiLink;
+
+    impl CustomTagHandler for WikiLink {
+        fn tag_name(&self) -> &str {
+            "foo"
+        }
+
+        fn render(&self, tag: &TagNode, _ctx: &RenderContext, output: &mut String) -> bool {
+            let Some(raw) = tag.raw_option.as_deref() else {
+                return false;
+            };
+            let parts = raw.split('|').count();
+            write!(output, "<a data-parts=\"{}\">", parts).unwrap();
+            output.push_str(&escape_html(raw));
+            output.push_str("</a>");
+            true
+        }
+    }
+
+    #[test]
+    fn custom_handler_reads_raw_option() {
+        let mut parser = Parser::new();
+        parser.register_custom_tag(CustomTagDef::new("foo"));
+        let mut renderer = Renderer::new();
+        renderer.register_handler(Arc::new(WikiLink));
+
+        let doc = parser.parse("[foo=a|b|c]x[/foo]");
+        let tag = doc.nodes[0].as_tag().unwrap();
+        assert_eq!(tag.raw_option.as_deref(), Some("a|b|c"));
+        assert_eq!(renderer.render(&doc), "<a data-parts=\"3\">a|b|c</a>");
+
+        let doc = parser.parse("[foo=\"a b\"]x[/foo][foo]y[/foo]");
+        let tags: Vec<_> = doc.nodes.iter().filter_map(Node::as_tag).collect();
+        assert_eq!(tags[0].raw_option.as_deref(), Some("\"a b\""));
+        assert_eq!(tags[1].raw_option, None);
+    }
+
+    struct PlainBold;
+
+    impl CustomTagHandler for PlainBold {
+        fn tag_name(&self) -> &str {
+            "b"
+        }
+
+        fn render(&self, tag: &TagNode, _ctx: &RenderContext, output: &mut String) -> bool {
+            write!(output, "<b>{}</b>", escape_html(&tag.inner_text())).unwrap();
+            true
+        }
+    }
+
+    #[test]
+    fn custom_handler_overrides_builtin() {
+        let mut renderer = Renderer::new();
+        renderer.register_handler(Arc::new(PlainBold));
+        let doc = Parser::new().parse("[b]x[/b] [i]y[/i]");
+        assert_eq!(renderer.render(&doc), "<b>x</b> <em>y</em>");
+    }
+
+    #[test]
+    fn render_tag_missing_from_registry_as_text() {
+        let mut registry = TagRegistry::empty();
+        registry.register(&crate::tags::TAG_ITALIC);
+        let renderer = Renderer::with_config_and_registry(RenderConfig::default(), registry);
+        let doc = Parser::new().parse("[b]x[/b] [i]y[/i]");
+        assert_eq!(renderer.render(&doc), "[b]x[/b] <em>y</em>");
+    }
+
+    #[test]
+    fn render_registered_alias() {
+        let mut registry = TagRegistry::new();
+        registry.register_alias("strong", "b");
+        let doc = Parser::with_registry(registry.clone()).parse("[strong]x[/STRONG]");
+        let renderer = Renderer::with_config_and_registry(RenderConfig::default(), registry);
+        assert_eq!(renderer.render(&doc), "<strong>x</strong>");
+    }
+
+    struct Big;
+
+    impl CustomTagHandler for Big {
+        fn tag_name(&self) -> &str {
+            "big"
+        }
+
+        fn render(&self, tag: &TagNode, _ctx: &RenderContext, output: &mut String) -> bool {
+            write!(
+                output,
+                "<span style=\"font-size:200%\">{}</span>",
+                escape_html(&tag.inner_text())
+            )
+            .unwrap();
+            true
+        }
+    }
+
+    #[test]
+    fn render_registered_custom_tag() {
+        let mut registry = TagRegistry::new();
+        registry.register(CustomTagDef::new("big"));
+        let parser = Parser::with_registry(registry);
+
+        let mut renderer = Renderer::with_config(RenderConfig::default());
+        renderer.register_handler(Arc::new(Big));
+        let doc = parser.parse("Say [big]hi <there>[/big]!");
+        assert_eq!(
+            renderer.render(&doc),
+            "Say <span style=\"font-size:200%\">hi &lt;there&gt;</span>!"
+        );
+
+        // Without the handler the renderer leaves the tag as text
+        assert_eq!(
+            Renderer::new().render(&doc),
+            "Say [big]hi &lt;there&gt;[/big]!"
+        );
+    }
+
+    // ==================== Empty Render Tests ====================
+
+    #[test]
+    fn is_empty_render_whitespace() {
+        let renderer = Renderer::new();
+        for input in [
+            "",
+            "  \n\t\n",
+            "[b] [/b]\n[i][u]\n[/u][/i]",
+            "[center] [/center]",
+        ] {
+            assert!(
+                renderer.is_empty_render(&Parser::new().parse(input)),
+                "{input:?}"
+            );
+        }
+        for input in [
+            "x",
+            "[b] . [/b]",
+            "&",
+            "[hr]",
+            "[img]https://x.com/a.png[/img]",
+        ] {
+            assert!(
+                !renderer.is_empty_render(&Parser::new().parse(input)),
+                "{input:?}"
+            );
+        }
+    }
+
+    struct HiddenMedia;
+
+    impl CustomTagHandler for HiddenMedia {
+        fn tag_name(&self) -> &str {
+            "media"
+        }
+
+        fn render(&self, _tag: &TagNode, _ctx: &RenderContext, _output: &mut String) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn is_empty_render_suppressed_media() {
+        let mut parser = Parser::new();
+        parser.register_custom_tag(CustomTagDef::new("media"));
+        let doc = parser.parse("[media=youtube]dQw4w9WgXcQ[/media]\n");
+
+        let mut renderer = Renderer::new();
+        renderer.register_handler(Arc::new(HiddenMedia));
+        assert!(renderer.is_empty_render(&doc));
+        // Without the suppressing handler the tag is shown as text
+        assert!(!doc.is_empty_render(&RenderConfig::default()));
+    }
+
+    #[test]
+    fn has_visible_content_entities() {
+        assert!(!has_visible_content("<p>&nbsp;&#160;</p>"));
+        assert!(has_visible_content("<p>&amp;</p>"));
+        assert!(has_visible_content("<span><IMG src=\"x\"></span>"));
+        assert!(!has_visible_content("<div class=\"hr-like\"></div>"));
+    }
+
+    // ==================== Supported Tags Tests ====================
+
+    #[test]
+    fn supported_tags_lists_builtins() {
+        let tags = Renderer::new().supported_tags();
+        for name in ["b", "quote", "code", "url", "img", "list", "table"] {
+            assert!(tags.contains(&name), "missing {}", name);
+        }
+    }
+
+    #[test]
+    fn supported_tags_in_sync_with_registry() {
+        let registry = TagRegistry::new();
+        let tags = Renderer::new().supported_tags();
+        let mut standard: Vec<_> = registry.iter().map(|t| t.name).collect();
+        standard.sort_unstable();
+        assert_eq!(tags, standard);
+    }
+
+    // ==================== Text Only Tests ====================
+
+    fn render_text_only(input: &str) -> String {
+        Renderer::new().render_text_only(&Parser::new().parse(input))
+    }
+
+    #[test]
+    fn render_text_only_strips_formatting() {
+        assert_eq!(
+            render_text_only("[b]Hi[/b] [url=https://x.com]link[/url]"),
+            "Hi link"
+        );
+        assert_eq!(render_text_only("a < b & [i]c[/i]"), "a < b & c");
+    }
+
+    #[test]
+    fn render_text_only_blocks_and_code() {
+        assert_eq!(
+            render_text_only("Intro[quote=\"Bob\"]Quoted[/quote][code][b]x[/b][/code]"),
+            "Intro\nQuoted\n[b]x[/b]"
+        );
+        assert_eq!(render_text_only("[list][*]One[*]Two[/list]"), "One\nTwo");
+        assert_eq!(
+            render_text_only(
+                "[img alt=\"A cat\"]https://x.com/cat.png[/img][img]https://x.com/y.png[/img]"
+            ),
+            "A cat"
+        );
+    }
+
+    #[test]
+    fn render_text_only_registry_blocks() {
+        assert_eq!(render_text_only("a[hr]b"), "a\nb");
+
+        let mut registry = TagRegistry::new();
+        registry.register(CustomTagDef {
+            name: "sql".into(),
+            tag_type: TagType::Verbatim,
+            html_tag: Some("pre".into()),
+            ..Default::default()
+        });
+        registry.register_alias("bq", "quote");
+        let parser = Parser::with_registry(registry.clone());
+        let renderer = Renderer::with_config_and_registry(RenderConfig::default(), registry);
+        let doc = parser.parse("See[sql]SELECT 1[/sql]and[bq]this[/bq]");
+        assert_eq!(renderer.render_text_only(&doc), "See\nSELECT 1\nand\nthis");
+    }
+
+    // ==================== Markdown Tests ====================
+
+    fn render_markdown(input: &str) -> String {
+        Renderer::new().render_markdown(&Parser::new().parse(input))
+    }
+
+    #[test]
+    fn render_markdown_inline() {
+        assert_eq!(
+            render_markdown("[b]bold[/b], [i]italic[/i] and [b][i]both[/i][/b]"),
+            "**bold**, _italic_ and **_both_**"
+        );
+        assert_eq!(
+            render_markdown("a[b] spaced [/b]b[i] x[/i][b][/b]"),
+            "a **spaced** b _x_"
+        );
+        assert_eq!(
+            render_markdown(
+                "[url=https://x.com/a b]the [b]site[/b][/url] [url]https://y.com[/url]"
+            ),
+            "[the **site**](https://x.com/a%20b) <https://y.com>"
+        );
+        assert_eq!(
+            render_markdown("[url=javascript:alert(1)]x[/url] https://z.com"),
+            "x <https://z.com>"
+        );
+        assert_eq!(
+            render_markdown(
+                "[img alt=\"A cat\"]https://x.com/cat.png[/img][img]https://x.com/y.png[/img]"
+            ),
+            "![A cat](https://x.com/cat.png)![](https://x.com/y.png)"
+        );
+        assert_eq!(render_markdown("[icode]a`b[/icode]"), "``a`b``");
+    }
+
+    #[test]
+    fn render_markdown_code() {
+        assert_eq!(
+            render_markdown("Run:[code=rust]fn main() {\n    run();\n}[/code]Done"),
+            "Run:\n\n```rust\nfn main() {\n    run();\n}\n```\n\nDone"
+        );
+        assert_eq!(
+            render_markdown("[code]a ``` b[/code]"),
+            "````\na ``` b\n````"
+        );
+        assert_eq!(
+            render_markdown("[php]echo 1;[/php]"),
+            "```php\necho 1;\n```"
+        );
+    }
+
+    #[test]
+    fn render_markdown_quote() {
+        assert_eq!(
+            render_markdown("Intro\n[quote=Bob]One\ntwo[quote]inner[/quote][/quote]\nOutro"),
+            "Intro\n\n> One\n> two\n>\n> > inner\n\nOutro"
+        );
+        assert_eq!(
+            render_markdown("[quote][code]a\n\nb[/code][/quote]"),
+            "> ```\n> a\n>\n> b\n> ```"
+        );
+    }
+
+    #[test]
+    fn render_markdown_lists() {
+        assert_eq!(
+            render_markdown("[list]\n[*]One\n[*]Two\nlines\n[/list]\nAfter"),
+            "- One\n- Two\n  lines\n\nAfter"
+        );
+        assert_eq!(render_markdown("[list=1][*]a[*]b[/list]"), "1. a\n1. b");
+        assert_eq!(render_markdown("[ul][*]x[/ul]"), "- x");
+    }
+
+    #[test]
+    fn render_markdown_text_content() {
+        assert_eq!(
+            render_markdown("[color=red]red[/color] [u]under[/u] [center]mid[/center]end"),
+            "red under\nmid\nend"
+        );
+        assert_eq!(
+            render_markdown("2*3 = 6_0 [b]x [not a tag] <br> # 1"),
+            "2\\*3 = 6\\_0 **x \\[not a tag\\] \\<br\\> \\# 1**"
+        );
+
+        // Block markers at the start of a line stay text
+        assert_eq!(
+            render_markdown("- not a list\n1. not ordered\n+ x\nTitle\n---\n[quote]2) y[/quote]"),
+            "\\- not a list\n1\\. not ordered\n\\+ x\nTitle\n\\---\n\n> 2\\) y"
+        );
+        assert_eq!(render_markdown("[list][*]- a[/list]"), "- \\- a");
+        assert_eq!(render_markdown("a - b 1. c"), "a - b 1. c");
+    }
+
+    // ==================== Word Filter Tests ====================
+
+    fn render_censored(input: &str) -> String {
+        let renderer = Renderer::with_config(RenderConfig {
+            word_filters: vec![("darn".into(), "****".into())],
+            ..Default::default()
+        });
+        renderer.render(&Parser::new().parse(input))
+    }
+
+    #[test]
+    fn render_word_filter_prose() {
+        let result = render_censored("Well Darn, [b]darn[/b] it. darned");
+        assert_eq!(result, "Well ****, <strong>****</strong> it. darned");
+    }
+
+    #[test]
+    fn render_word_filter_skips_code_and_urls() {
+        let result = render_censored(
+            "[code]darn[/code][icode]darn[/icode][plain]darn[/plain][url=https://darn.example/]x[/url]",
+        );
+        assert_eq!(result.matches("darn").count(), 4);
+        assert!(!result.contains("****"));
+    }
+
+    #[test]
+    fn word_at_options() {
+        assert!(word_at("darned", 0, "DARN", false, true));
+        assert!(!word_at("darned", 0, "darn", true, true));
+        assert!(!word_at("DARN darn", 0, "darn", true, false));
+        assert!(word_at("DARN darn", 5, "darn", true, false));
     }
 
     #[test]
-    fn render_quote_with_author() {
-        let result = render("[quote=\"John\"]Quoted text[/quote]");
-        assert!(result.contains("<blockquote"));
-        assert!(result.contains("John wrote:"));
-        assert!(result.contains("Quoted text"));
+    fn render_word_filter_escapes_replacement() {
+        let renderer = Renderer::with_config(RenderConfig {
+            word_filters: vec![
+                ("lt".into(), "LT".into()),
+                ("<b>".into(), "<em>b</em>".into()),
+            ],
+            ..Default::default()
+        });
+        let doc = Parser::new().parse("a < b & <b> lt");
+        assert_eq!(
+            renderer.render(&doc),
+            "a &lt; b &amp; &lt;em&gt;b&lt;/em&gt; LT"
+        );
     }
 
     // ==================== Code Tests ====================
@@ -1513,6 +6039,96 @@ mod tests {
         assert!(result.contains("language-javascript"));
     }
 
+    #[test]
+    fn render_code_line_numbers() {
+        let renderer = Renderer::with_config(RenderConfig {
+            code_line_numbers: true,
+            ..Default::default()
+        });
+        let doc = Parser::new().parse("[code]a < b\nc[/code]");
+        assert_eq!(
+            renderer.render(&doc),
+            "<pre class=\"bbcode-code\"><code><span class=\"bbcode-code-line\">a &lt; b</span>\n\
+             <span class=\"bbcode-code-line\">c</span></code></pre>"
+        );
+        assert!(!render("[code]a\nb[/code]").contains("bbcode-code-line"));
+    }
+
+    #[test]
+    fn render_code_copy_button() {
+        let renderer = Renderer::with_config(RenderConfig {
+            code_copy_button: true,
+            ..Default::default()
+        });
+        let doc = Parser::new().parse("[code=rust]x[/code]");
+        assert!(renderer.render(&doc).starts_with(
+            "<pre class=\"bbcode-code\" data-language=\"rust\">\
+             <button class=\"bbcode-code-copy\" type=\"button\">Copy</button><code"
+        ));
+        let doc = Parser::new().parse("[php]x[/php]");
+        assert!(renderer.render(&doc).contains("bbcode-code-copy"));
+        assert!(!render("[code]x[/code]").contains("<button"));
+
+        let renderer = Renderer::with_config(RenderConfig {
+            code_copy_button: true,
+            code_copy_label: "<Kopieren>".into(),
+            ..Default::default()
+        });
+        assert!(renderer
+            .render(&doc)
+            .contains("type=\"button\">&lt;Kopieren&gt;</button>"));
+    }
+
+    #[test]
+    fn render_excerpt_closes_tags() {
+        let doc = Parser::new().parse("[b]The quick brown fox jumps over the lazy dog[/b]");
+        let html = Renderer::new().render_excerpt(&doc, 10);
+        assert_eq!(html, "<strong>The quick </strong>\u{2026}");
+        assert!(html.ends_with('\u{2026}'));
+    }
+
+    #[test]
+    fn render_excerpt_counts_code_and_skips_short_docs() {
+        let doc = Parser::new().parse("ab[code]0123456789[/code]tail");
+        assert_eq!(
+            Renderer::new().render_excerpt(&doc, 6),
+            "ab<pre class=\"bbcode-code\"><code>0123</code></pre>\u{2026}"
+        );
+
+        let doc = Parser::new().parse("[i]short[/i]");
+        assert_eq!(Renderer::new().render_excerpt(&doc, 5), "<em>short</em>");
+    }
+
+    #[test]
+    fn render_excerpt_keeps_urls_whole() {
+        let excerpt =
+            |input: &str, max| Renderer::new().render_excerpt(&Parser::new().parse(input), max);
+
+        assert_eq!(
+            excerpt("see [url]https://example.com/a/b[/url] now", 10),
+            "see \u{2026}"
+        );
+        assert_eq!(
+            excerpt("see [url]https://example.com/a/b[/url] now", 28),
+            "see <a class=\"bbcode-url\" href=\"https://example.com/a/b\" \
+             rel=\"nofollow\">https://example.com/a/b</a> \u{2026}"
+        );
+        assert_eq!(
+            excerpt("mail [email]admin@example.com[/email]", 8),
+            "mail \u{2026}"
+        );
+        assert_eq!(excerpt("at https://example.com/a/b", 10), "at \u{2026}");
+        assert!(excerpt("[image]https://example.com/a.png[/image] text", 2)
+            .contains("src=\"https://example.com/a.png\""));
+
+        // Link text given separately from the URL is cut as usual
+        assert_eq!(
+            excerpt("[url=https://example.com/a/b]Example site[/url]", 7),
+            "<a class=\"bbcode-url\" href=\"https://example.com/a/b\" \
+             rel=\"nofollow\">Example</a>\u{2026}"
+        );
+    }
+
     #[test]
     fn render_code_escapes_html() {
         let result = render("[code]<script>alert('xss')</script>[/code]");
@@ -1520,6 +6136,18 @@ mod tests {
         assert!(!result.contains("<script>"));
     }
 
+    #[test]
+    fn render_verbatim_followed_by_tag() {
+        assert_eq!(
+            render("[code]x[/code][b]y[/b]"),
+            "<pre class=\"bbcode-code\"><code>x</code></pre><strong>y</strong>"
+        );
+        assert_eq!(
+            render("[icode]x[/icode][i]y[/i]"),
+            "<code class=\"bbcode-icode\">x</code><em>y</em>"
+        );
+    }
+
     #[test]
     fn render_icode() {
         let result = render("Use [icode]console.log()[/icode] to debug");
@@ -1544,14 +6172,125 @@ mod tests {
         assert!(result.contains("type=\"1\""));
     }
 
+    #[test]
+    fn render_nested_list() {
+        let result = render("[list][*]A[list][*]A.1[/list][*]B[/list]");
+        assert_eq!(
+            result,
+            "<ul class=\"bbcode-list\"><li>A<ul class=\"bbcode-list\"><li>A.1</li></ul></li>\
+             <li>B</li></ul>"
+        );
+    }
+
+    #[test]
+    fn render_nested_list_per_level_style() {
+        let result =
+            render("[list=1][*]A[list=a][*]A.a[list][*]deep[/list][*]A.b[/list][*]B[/list]");
+        assert_eq!(
+            result,
+            "<ol class=\"bbcode-list\" type=\"1\"><li>A<ol class=\"bbcode-list\" type=\"a\">\
+             <li>A.a<ul class=\"bbcode-list\"><li>deep</li></ul></li><li>A.b</li></ol></li>\
+             <li>B</li></ol>"
+        );
+    }
+
+    #[test]
+    fn render_list_item_formatting() {
+        let result = render("[list][*][b]x[i]y[/i]z[/b][*][u]open[*]c[/list]");
+        assert!(result.contains("<li><strong>x<em>y</em>z</strong></li>"));
+        assert!(result.contains("<li><u>open</u></li><li>c</li>"));
+    }
+
+    #[test]
+    fn render_list_stray_text_wrapped_by_default() {
+        let result = render("[list]\nText before first item\n[*]a[/list]");
+        assert!(result.contains("<ul class=\"bbcode-list\"><li>Text before first item</li><li>"));
+    }
+
+    #[test]
+    fn render_list_leading_whitespace_dropped() {
+        let result = render("[list]\n  \n[*]a[/list]");
+        assert!(result.starts_with("<ul class=\"bbcode-list\"><li>a"));
+    }
+
+    #[test]
+    fn render_list_stray_content_modes() {
+        let doc = Parser::new().parse("[list]Intro[*]a[/list]");
+
+        let renderer = Renderer::with_config(RenderConfig {
+            list_stray_content: StrayContent::Drop,
+            ..Default::default()
+        });
+        let result = renderer.render(&doc);
+        assert!(!result.contains("Intro"));
+        assert!(result.contains("<li>a</li>"));
+
+        let renderer = Renderer::with_config(RenderConfig {
+            list_stray_content: StrayContent::KeepBefore,
+            ..Default::default()
+        });
+        let result = renderer.render(&doc);
+        assert!(result.starts_with("Intro<ul"));
+    }
+
     #[test]
     fn render_list_alpha() {
         let result = render("[list=a][*]One[*]Two[/list]");
         assert!(result.contains("type=\"a\""));
     }
 
+    #[test]
+    fn render_explicit_ol_ul() {
+        assert_eq!(
+            render("[ol][*]x[/ol]"),
+            "<ol class=\"bbcode-list\"><li>x</li></ol>"
+        );
+        assert_eq!(
+            render("[ul][*]x[/ul]"),
+            "<ul class=\"bbcode-list\"><li>x</li></ul>"
+        );
+
+        // The tag decides the element; the option only styles it
+        let result = render("[ol=disc][*]x[/ol]");
+        assert!(result.starts_with("<ol class=\"bbcode-list\">"));
+        let result = render("[ul=1][*]x[/ul]");
+        assert!(result.starts_with("<ul class=\"bbcode-list\">"));
+        assert!(render("[ol=i][*]x[/ol]").contains("type=\"i\""));
+        assert!(render("[ul=square][*]x[/ul]").contains("list-style-type: square;"));
+    }
+
     // ==================== Alignment Tests ====================
 
+    #[test]
+    fn render_indent_levels() {
+        assert!(render("[indent]x[/indent]").contains("margin-left: 20px;"));
+        assert!(render("[indent=2]x[/indent]").contains("margin-left: 40px;"));
+        assert!(render("[indent=9]x[/indent]").contains("margin-left: 100px;"));
+        assert!(render("[indent=-3]x[/indent]").contains("margin-left: 20px;"));
+        assert!(render("[indent=0]x[/indent]").contains("margin-left: 20px;"));
+        assert!(render("[indent=2,right]x[/indent]").contains("margin-right: 40px;"));
+
+        let doc = Parser::new().parse("[indent=2]x[/indent] [indent=8]y[/indent]");
+        let renderer = Renderer::with_config(RenderConfig {
+            indent_step_px: 30,
+            max_indent_level: 3,
+            ..Default::default()
+        });
+        let result = renderer.render(&doc);
+        assert!(result.contains("margin-left: 60px;"));
+        assert!(result.contains("margin-left: 90px;"));
+
+        let renderer = Renderer::with_config(RenderConfig {
+            use_inline_styles: false,
+            ..Default::default()
+        });
+        let doc = Parser::new().parse("[indent=right]x[/indent]");
+        assert_eq!(
+            renderer.render(&doc),
+            "<div class=\"bbcode-indent bbcode-indent-1 bbcode-indent-right\">x</div>"
+        );
+    }
+
     #[test]
     fn render_center() {
         let result = render("[center]Centered[/center]");
@@ -1564,6 +6303,29 @@ mod tests {
         assert!(result.contains("text-align: right;"));
     }
 
+    #[test]
+    fn render_float() {
+        assert_eq!(
+            render("[float=left]x[/float]"),
+            "<div class=\"bbcode-float\" style=\"float: left;\">x</div>"
+        );
+        assert!(render("[float=RIGHT]x[/float]").contains("float: right;"));
+        assert_eq!(
+            render("[float=diagonal]x[/float]"),
+            "[float=diagonal]x[/float]"
+        );
+
+        let renderer = Renderer::with_config(RenderConfig {
+            use_inline_styles: false,
+            ..Default::default()
+        });
+        let doc = Parser::new().parse("[float=right]x[/float]");
+        assert_eq!(
+            renderer.render(&doc),
+            "<div class=\"bbcode-float bbcode-float-right\">x</div>"
+        );
+    }
+
     // ==================== Heading Tests ====================
 
     #[test]
@@ -1573,6 +6335,74 @@ mod tests {
         assert!(result.contains("Title"));
     }
 
+    #[test]
+    fn render_heading_base_level() {
+        let doc = Parser::new().parse("[heading=1]Title[/heading][heading=5]Deep[/heading]");
+        let renderer = Renderer::with_config(RenderConfig {
+            heading_base_level: 3,
+            ..Default::default()
+        });
+        let result = renderer.render(&doc);
+        assert!(result.contains("<h3 class=\"bbcode-heading\">Title</h3>"));
+        assert!(result.contains("<h6 class=\"bbcode-heading\">Deep</h6>"));
+    }
+
+    #[test]
+    fn render_heading_anchors() {
+        let doc = Parser::new()
+            .parse("[heading=1]Getting Started[/heading][heading=2]Getting started![/heading]");
+        let renderer = Renderer::with_config(RenderConfig {
+            heading_anchors: true,
+            ..Default::default()
+        });
+        let result = renderer.render(&doc);
+        assert!(result.contains("<h2 class=\"bbcode-heading\" id=\"heading-getting-started\">"));
+        assert!(result.contains("<h3 class=\"bbcode-heading\" id=\"heading-getting-started-1\">"));
+
+        // Ids don't leak between renders
+        assert_eq!(renderer.render(&doc), result);
+    }
+
+    #[test]
+    fn render_heading_anchors_without_prefix() {
+        let doc = Parser::new()
+            .parse("[heading=1]My Section[/heading]text[heading=1]My Section[/heading]");
+        let renderer = Renderer::with_config(RenderConfig {
+            heading_anchors: true,
+            heading_id_prefix: Cow::Borrowed(""),
+            ..Default::default()
+        });
+        let result = renderer.render(&doc);
+        assert!(result.contains("<h2 class=\"bbcode-heading\" id=\"my-section\">My Section</h2>"));
+        assert!(result.contains("<h2 class=\"bbcode-heading\" id=\"my-section-1\">My Section</h2>"));
+    }
+
+    #[test]
+    fn render_heading_anchor_links() {
+        let doc = Parser::new().parse("[heading=1]<Hi> & [b]bye[/b][/heading]");
+        let renderer = Renderer::with_config(RenderConfig {
+            heading_anchors: true,
+            heading_anchor_links: true,
+            heading_id_prefix: Cow::Borrowed(""),
+            ..Default::default()
+        });
+        assert_eq!(
+            renderer.render(&doc),
+            "<h2 class=\"bbcode-heading\" id=\"hi--bye\">&lt;Hi&gt; &amp; <strong>bye</strong>\
+             <a class=\"bbcode-heading-anchor\" href=\"#hi--bye\" aria-hidden=\"true\">#</a></h2>"
+        );
+    }
+
+    #[test]
+    fn slugify_test() {
+        assert_eq!(slugify("Hello World"), "hello-world");
+        assert_eq!(slugify("  C++ & Rust_2  "), "c--rust_2");
+        assert_eq!(slugify("Ünïcödé Straße"), "unicode-strasse");
+        assert_eq!(slugify("日本語 FAQ"), "faq");
+        assert_eq!(slugify("日本語"), "section");
+        assert_eq!(slugify("!!!"), "section");
+    }
+
     #[test]
     fn render_heading_level_3() {
         let result = render("[heading=3]Subtitle[/heading]");
@@ -1595,6 +6425,74 @@ mod tests {
         assert!(result.contains("Click to reveal"));
     }
 
+    #[test]
+    fn render_spoiler_open_by_default() {
+        assert!(!render("[spoiler]x[/spoiler]").contains(" open"));
+        let renderer = Renderer::with_config(RenderConfig {
+            spoilers_open_by_default: true,
+            ..Default::default()
+        });
+        let doc = Parser::new().parse("[spoiler]x[/spoiler]");
+        assert!(renderer
+            .render(&doc)
+            .starts_with("<details class=\"bbcode-spoiler\" open><summary>"));
+    }
+
+    #[test]
+    fn render_spoiler_depth() {
+        let result = render(
+            "[spoiler]a[spoiler]b[spoiler]c[/spoiler][/spoiler][/spoiler][spoiler]d[/spoiler]",
+        );
+        assert_eq!(
+            result,
+            "<details class=\"bbcode-spoiler\"><summary>Spoiler</summary><div class=\"spoiler-content\">a\
+             <details class=\"bbcode-spoiler\" data-spoiler-depth=\"1\"><summary>Spoiler</summary><div class=\"spoiler-content\">b\
+             <details class=\"bbcode-spoiler\" data-spoiler-depth=\"2\"><summary>Spoiler</summary><div class=\"spoiler-content\">c\
+             </div></details></div></details></div></details>\
+             <details class=\"bbcode-spoiler\"><summary>Spoiler</summary><div class=\"spoiler-content\">d</div></details>"
+        );
+
+        struct Depth;
+        impl CustomTagHandler for Depth {
+            fn tag_name(&self) -> &str {
+                "user"
+            }
+            fn render(&self, _tag: &TagNode, ctx: &RenderContext, output: &mut String) -> bool {
+                output.push_str(&ctx.spoiler_depth.to_string());
+                true
+            }
+        }
+        let mut renderer = Renderer::new();
+        renderer.register_handler(Arc::new(Depth));
+        let doc = Parser::new()
+            .parse("[user=1]a[/user][spoiler][user=2]b[/user][/spoiler][user=3]c[/user]");
+        let result = renderer.render(&doc);
+        assert!(result.starts_with('0'));
+        assert!(result.contains(">1</div>"));
+        assert!(result.ends_with("</details>0"));
+    }
+
+    #[test]
+    fn render_spoiler_default_label() {
+        let renderer = Renderer::with_config(RenderConfig {
+            default_spoiler_label: "Spoiler anzeigen".into(),
+            ..Default::default()
+        });
+        let doc = Parser::new().parse("[spoiler]x[/spoiler][spoiler=Titel]y[/spoiler]");
+        let result = renderer.render(&doc);
+        assert!(result.contains("<summary>Spoiler anzeigen</summary>"));
+        assert!(result.contains("<summary>Titel</summary>"));
+
+        let renderer = Renderer::with_config(RenderConfig {
+            default_spoiler_label: "<Spoiler>".into(),
+            ..Default::default()
+        });
+        let doc = Parser::new().parse("[spoiler]x[/spoiler]");
+        assert!(renderer
+            .render(&doc)
+            .contains("<summary>&lt;Spoiler&gt;</summary>"));
+    }
+
     #[test]
     fn render_ispoiler() {
         let result = render("This is [ispoiler]hidden[/ispoiler] text");
@@ -1602,6 +6500,63 @@ mod tests {
         assert!(result.contains("hidden"));
     }
 
+    #[test]
+    fn render_ispoiler_modes() {
+        let doc = Parser::new().parse("[ispoiler]x[/ispoiler]");
+        let render_mode = |spoiler_mode| {
+            Renderer::with_config(RenderConfig {
+                spoiler_mode,
+                ..Default::default()
+            })
+            .render(&doc)
+        };
+
+        assert!(render_mode(SpoilerMode::Script).contains(" onclick="));
+
+        let result = render_mode(SpoilerMode::CssOnly);
+        assert_eq!(result, "<span class=\"bbcode-ispoiler\">x</span>");
+        assert!(!result.contains("onclick="));
+
+        let result = render_mode(SpoilerMode::Details);
+        assert_eq!(
+            result,
+            "<details class=\"bbcode-ispoiler\"><summary>Spoiler</summary>\
+             <span class=\"spoiler-content\">x</span></details>"
+        );
+    }
+
+    // ==================== User Tests ====================
+
+    #[test]
+    fn render_user_link_resolver() {
+        let renderer = Renderer::with_config(RenderConfig {
+            user_link_resolver: Some(Arc::new(|user: &str| match user {
+                "42" | "alice" => Some(format!("/members/{}", user)),
+                "evil" => Some("javascript:alert(1)".to_string()),
+                _ => None,
+            })),
+            ..Default::default()
+        });
+        let render_user = |input| renderer.render(&Parser::new().parse(input));
+
+        assert_eq!(
+            render_user("[user=42]Alice[/user]"),
+            "<a class=\"bbcode-user\" data-user-id=\"42\" href=\"/members/42\">@Alice</a>"
+        );
+        assert!(render_user("[user=7]Bob[/user]").contains("href=\"#\""));
+        assert!(render_user("[user=evil]Eve[/user]").contains("href=\"#\""));
+
+        let parser = Parser::with_config(crate::ParserConfig {
+            parse_mentions: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            renderer.render(&parser.parse("cc @alice @bob")),
+            "cc <a class=\"bbcode-user\" href=\"/members/alice\">@alice</a> \
+             <span class=\"bbcode-user\">@bob</span>"
+        );
+    }
+
     // ==================== Table Tests ====================
 
     #[test]
@@ -1613,6 +6568,117 @@ mod tests {
         assert!(result.contains("Cell"));
     }
 
+    #[test]
+    fn render_table_multiline_source() {
+        let input = "[table]\n[tr]\n  [th]H[/th]\n[/tr]\n[tr]\n  [td]a\nb[/td]\n  [td]c[/td]\n[/tr]\n[/table]";
+        assert_eq!(
+            render(input),
+            "<table class=\"bbcode-table\"><thead><tr><th>H</th></tr></thead>\
+             <tbody><tr><td>a<br />b</td><td>c</td></tr></tbody></table>"
+        );
+
+        let renderer = Renderer::with_config(RenderConfig {
+            line_break_in_tables: true,
+            ..Default::default()
+        });
+        let result = renderer.render(&Parser::new().parse(input));
+        assert!(result.starts_with("<table class=\"bbcode-table\"><thead><br /><tr><br />"));
+    }
+
+    #[test]
+    fn render_table_sections() {
+        let result = render(
+            "[table][tr][th]A[/th][th]B[/th][/tr][tr][th]C[/th][th]D[/th][/tr]\
+             [tr][th]1[/th][td]2[/td][/tr][/table]",
+        );
+        assert_eq!(
+            result,
+            "<table class=\"bbcode-table\"><thead><tr><th>A</th><th>B</th></tr>\
+             <tr><th>C</th><th>D</th></tr></thead><tbody><tr><th>1</th><td>2</td></tr></tbody></table>"
+        );
+
+        let result = render("[table][tr][td]1[/td][/tr][tr][th]H[/th][/tr][/table]");
+        assert_eq!(
+            result,
+            "<table class=\"bbcode-table\"><tbody><tr><td>1</td></tr>\
+             <tr><th>H</th></tr></tbody></table>"
+        );
+
+        let result = render("[table][tr][th]Only[/th][/tr][/table]");
+        assert!(result.contains("</thead></table>"));
+        assert!(!result.contains("<tbody>"));
+    }
+
+    #[test]
+    fn render_table_caption_and_border() {
+        assert_eq!(
+            render("[table caption=\"Pricing <b> & plans\" border=1][tr][td]a[/td][/tr][/table]"),
+            "<table class=\"bbcode-table bbcode-table-bordered\"><caption>Pricing &lt;b&gt; &amp; plans</caption>\
+             <tbody><tr><td>a</td></tr></tbody></table>"
+        );
+        assert_eq!(
+            render("[table border=0][tr][td]a[/td][/tr][/table]"),
+            "<table class=\"bbcode-table\"><tbody><tr><td>a</td></tr></tbody></table>"
+        );
+    }
+
+    #[test]
+    fn render_table_cell_options() {
+        let result = render(
+            "[table][tr][th align=right valign=BOTTOM]H[/th]\
+             [td align=center bgcolor=#eee]A[/td][td bgcolor=rgb(1,2,3) width=50%]B[/td][/tr][/table]",
+        );
+        assert!(result.contains("<th style=\"text-align: right; vertical-align: bottom;\">H</th>"));
+        assert!(result.contains("<td style=\"text-align: center; background-color: #eee;\">A</td>"));
+        assert!(result.contains("<td style=\"width: 50%; background-color: rgb(1,2,3);\">B</td>"));
+    }
+
+    #[test]
+    fn render_table_cell_invalid_options_dropped() {
+        let result = render(
+            "[table][tr][td align=\"left; onclick: alert(1)\" valign=baseline]A[/td]\
+             [td bgcolor=\"red;background:url(x)\" align=\"center\\\" onmouseover=x\"]B[/td][/tr][/table]",
+        );
+        assert!(result.contains("<td>A</td>"));
+        assert!(result.contains("<td>B</td>"));
+        assert!(!result.contains("onclick"));
+        assert!(!result.contains("url("));
+    }
+
+    #[test]
+    fn render_table_widths_validated() {
+        let result = render(
+            "[table width=\"1px;background-image:url(x)\"][tr]\
+             [td width=\"1px;background-image:url(x)\"]a[/td][td width=120]b[/td]\
+             [td width=\" 40px \"]c[/td][td width=150%]d[/td][/tr][/table]",
+        );
+        assert!(!result.contains("url("));
+        assert!(result.starts_with("<table class=\"bbcode-table\"><tbody>"));
+        assert!(result.contains("<td>a</td><td style=\"width: 120px;\">b</td>"));
+        assert!(result.contains("<td style=\"width: 40px;\">c</td><td>d</td>"));
+
+        let result = render("[table width=80%][tr][td]a[/td][/tr][/table]");
+        assert!(result.starts_with("<table class=\"bbcode-table\" style=\"width: 80%;\">"));
+    }
+
+    #[test]
+    fn render_table_cell_spans() {
+        let result = render(
+            "[table][tr][th colspan=2 rowspan=3]H[/th][td colspan=2]x[/td]\
+             [td rowspan=2 align=right]y[/td][/tr][/table]",
+        );
+        assert!(result.contains("<th colspan=\"2\" rowspan=\"3\">H</th>"));
+        assert!(result.contains("<td colspan=\"2\">x</td>"));
+        assert!(result.contains("<td rowspan=\"2\" style=\"text-align: right;\">y</td>"));
+
+        let result = render(
+            "[table][tr][td colspan=\"2\\\" onclick=\\\"alert(1)\"]a[/td][td colspan=0]b[/td]\
+             [td rowspan=101]c[/td][td colspan=+2]d[/td][/tr][/table]",
+        );
+        assert!(!result.contains("onclick"));
+        assert!(!result.contains("span="));
+    }
+
     // ==================== Self-Closing Tag Tests ====================
 
     #[test]
@@ -1629,6 +6695,60 @@ mod tests {
         assert!(result.contains("<br />"));
     }
 
+    #[test]
+    fn render_collapse_blank_lines() {
+        let renderer = Renderer::with_config(RenderConfig {
+            collapse_blank_lines: Some(2),
+            ..Default::default()
+        });
+        let render_capped = |input: &str| renderer.render(&Parser::new().parse(input));
+        assert_eq!(render_capped("a\n\n\n\nb"), "a<br /><br />b");
+        assert_eq!(
+            render_capped("a\n \n\t\n\nb\nc"),
+            "a<br /> <br />\tb<br />c"
+        );
+        assert_eq!(
+            render_capped("a\n\n[b]x[/b]\n\n\nc")
+                .matches("<br />")
+                .count(),
+            4
+        );
+        assert_eq!(render("a\n\n\n\nb").matches("<br />").count(), 4);
+    }
+
+    #[test]
+    fn render_crlf_single_break() {
+        assert_eq!(render("Line1\r\nLine2"), "Line1<br />Line2");
+        assert_eq!(render("Line1\r\nLine2"), render("Line1\nLine2"));
+        assert_eq!(render("Line1\rLine2"), render("Line1\nLine2"));
+    }
+
+    #[test]
+    fn render_crlf_in_verbatim() {
+        assert_eq!(
+            render("[code]a\r\nb\rc[/code]"),
+            render("[code]a\nb\nc[/code]")
+        );
+        assert!(!render("[noparse]a\r\nb[/noparse]").contains('\r'));
+    }
+
+    #[test]
+    fn render_output_mode_html5() {
+        let renderer = Renderer::with_config(RenderConfig {
+            output_mode: OutputMode::Html5,
+            ..Default::default()
+        });
+        let render_html5 = |input| renderer.render(&Parser::new().parse(input));
+
+        assert_eq!(render_html5("Line\nBreak"), "Line<br>Break");
+        assert_eq!(render_html5("a[hr]b[br]c"), "a<hr>b<br>c");
+        assert_eq!(
+            render_html5("[img=10x20]https://x.com/a.png[/img]"),
+            "<img class=\"bbcode-img\" src=\"https://x.com/a.png\" width=\"10\" height=\"20\">"
+        );
+        assert!(!render_html5("x\n[img]https://x.com/a.png[/img]").contains("/>"));
+    }
+
     // ==================== Unknown Tag Tests ====================
 
     #[test]
@@ -1654,6 +6774,13 @@ mod tests {
         assert_eq!(&*result, "&lt;script&gt;&amp;&quot;&#x27;");
     }
 
+    #[test]
+    fn escape_html_text_keeps_quotes() {
+        let result = escape_html_text("a < b & \"c\"");
+        assert_eq!(&*result, "a &lt; b &amp; \"c\"");
+        assert!(matches!(escape_html_text("\"c\" 'd'"), Cow::Borrowed(_)));
+    }
+
     #[test]
     fn is_valid_color_test() {
         assert!(is_valid_color("red"));
@@ -1661,6 +6788,39 @@ mod tests {
         assert!(is_valid_color("#ff0000"));
         assert!(is_valid_color("#f00"));
         assert!(is_valid_color("rgb(255, 0, 0)"));
+        assert!(is_valid_color("rgb(0,0,0)"));
+        assert!(is_valid_color("rgba(0,0,0,0.5)"));
+        assert!(is_valid_color("RGB(100%, 50%, 0%)"));
+        assert!(is_valid_color("hsl(120, 100%, 50%)"));
+        assert!(is_valid_color("hsla(120, 100%, 50%, 0.25)"));
+        assert!(is_valid_color("hsl(120,50%,50%)"));
+
+        assert!(!is_valid_color("rgb(0,0,0);x"));
+        assert!(!is_valid_color("rgbxx("));
+        assert!(!is_valid_color("rgbwtf(1,2,3)"));
+        assert!(!is_valid_color("rgb(0,0)"));
+        assert!(!is_valid_color("rgba(0,0,0)"));
+        assert!(!is_valid_color("rgb(256,0,0)"));
+        assert!(!is_valid_color("rgb(-1,0,0)"));
+        assert!(!is_valid_color("rgb(1e2,0,0)"));
+        assert!(!is_valid_color("rgba(0,0,0,2)"));
+        assert!(!is_valid_color("hsl(120, 100, 50%)"));
+        assert!(!is_valid_color("hsl(400, 100%, 50%)"));
+
+        assert!(!is_valid_color("rgb(0,0,0);background:url(x)"));
+        assert!(!is_valid_color("URL(x)"));
+        assert!(!is_valid_color("expression(alert(1))"));
+        assert!(!is_valid_color("red/**/"));
+
+        assert!(is_valid_color("cornflowerblue"));
+        assert!(is_valid_color("CornflowerBlue"));
+
+        assert!(!is_valid_color("transparent"));
+        assert!(!is_valid_color("Transparent"));
+        assert!(!is_valid_color("buttonface"));
+        assert!(!is_valid_color("ButtonFace"));
+        assert!(!is_valid_color("canvas"));
+        assert!(!is_valid_color("WINDOW"));
 
         assert!(!is_valid_color("notacolor"));
         assert!(!is_valid_color("#gggggg"));
@@ -1675,28 +6835,135 @@ mod tests {
 
         assert!(!is_valid_font("font<script>"));
         assert!(!is_valid_font("font;color:red"));
+
+        assert!(is_valid_font("Arial, sans-serif"));
+        assert!(!is_valid_font("Arial,,serif"));
+        assert!(!is_valid_font("Arial, 'x'"));
+        for keyword in ["inherit", "INITIAL", " unset ", "Revert"] {
+            assert!(!is_valid_font(keyword), "{keyword}");
+        }
+    }
+
+    #[test]
+    fn render_font_quotes_families() {
+        assert_eq!(
+            render("[font=Arial]x[/font]"),
+            "<span class=\"bbcode-font\" style=\"font-family: 'Arial';\">x</span>"
+        );
+        assert!(render("[font=\"Times New Roman, sans-serif\"]x[/font]")
+            .contains("font-family: 'Times New Roman', sans-serif;"));
+        assert_eq!(render("[font=inherit]x[/font]"), "[font=inherit]x[/font]");
     }
 
     #[test]
     fn parse_size_test() {
-        assert_eq!(parse_size("4"), Some("15px".to_string()));
-        assert_eq!(parse_size("20px"), Some("20px".to_string()));
-        assert_eq!(parse_size("150%"), Some("150%".to_string()));
+        assert_eq!(
+            parse_size("4", SizeMode::Auto, 8, 36),
+            Some("15px".to_string())
+        );
+        assert_eq!(
+            parse_size("20px", SizeMode::Auto, 8, 36),
+            Some("20px".to_string())
+        );
+        assert_eq!(
+            parse_size("150%", SizeMode::Auto, 8, 36),
+            Some("150%".to_string())
+        );
+
+        assert!(parse_size("999px", SizeMode::Auto, 8, 36).is_none());
+        assert!(parse_size("abc", SizeMode::Auto, 8, 36).is_none());
+    }
+
+    #[test]
+    fn parse_size_modes_test() {
+        assert_eq!(
+            parse_size("20", SizeMode::Auto, 8, 36),
+            Some("20%".to_string())
+        );
+        assert_eq!(parse_size("20", SizeMode::XenForoScale, 8, 36), None);
+        assert_eq!(parse_size("20", SizeMode::PhpbbPercent, 8, 36), None);
+        assert_eq!(
+            parse_size("20", SizeMode::Pixels, 8, 36),
+            Some("20px".to_string())
+        );
+
+        assert_eq!(
+            parse_size("4", SizeMode::XenForoScale, 8, 36),
+            Some("15px".to_string())
+        );
+        assert_eq!(
+            parse_size("150", SizeMode::PhpbbPercent, 8, 36),
+            Some("150%".to_string())
+        );
+        assert_eq!(parse_size("72", SizeMode::Pixels, 8, 36), None);
+
+        // Explicit units work in every mode
+        for mode in [
+            SizeMode::XenForoScale,
+            SizeMode::PhpbbPercent,
+            SizeMode::Pixels,
+        ] {
+            assert_eq!(parse_size("20px", mode, 8, 36), Some("20px".to_string()));
+            assert_eq!(parse_size("150%", mode, 8, 36), Some("150%".to_string()));
+        }
+    }
 
-        assert!(parse_size("999px").is_none());
-        assert!(parse_size("abc").is_none());
+    #[test]
+    fn parse_size_huge_bounds() {
+        assert_eq!(
+            parse_size("150%", SizeMode::Auto, u32::MAX, u32::MAX),
+            Some(format!("{}%", u32::MAX / 16))
+        );
+        assert_eq!(
+            parse_size("150%", SizeMode::Auto, 8, u32::MAX),
+            Some("150%".to_string())
+        );
     }
 
     #[test]
     fn is_valid_url_test() {
         let schemes = vec!["http".to_string(), "https".to_string()];
 
-        assert!(is_valid_url("https://example.com", &schemes));
-        assert!(is_valid_url("http://example.com", &schemes));
+        assert!(is_valid_url("https://example.com", &schemes, false));
+        assert!(is_valid_url("http://example.com", &schemes, false));
+
+        assert!(!is_valid_url("javascript:alert('xss')", &schemes, false));
+        assert!(!is_valid_url("data:text/html,<script>", &schemes, false));
+        assert!(!is_valid_url("vbscript:alert", &schemes, false));
+
+        for url in ["/foo/bar", "//cdn.x.com/a.png", "?page=2", "#top", "a/b:c"] {
+            assert!(!is_valid_url(url, &schemes, false), "{url}");
+            assert!(is_valid_url(url, &schemes, true), "{url}");
+        }
+        assert!(!is_valid_url("javascript:alert(1)", &schemes, true));
+        assert!(!is_valid_url("/a'b", &schemes, true));
+    }
+
+    #[test]
+    fn is_valid_url_scheme_case() {
+        let schemes = vec!["HTTPS".to_string(), "ftp".to_string()];
+        assert!(is_valid_url("https://x.com", &schemes, false));
+        assert!(is_valid_url("HTTPS://x.com", &schemes, false));
+        assert!(is_valid_url("Ftp://x.com/file", &schemes, false));
+        assert!(!is_valid_url("http://x.com", &schemes, false));
+    }
+
+    #[test]
+    fn is_valid_url_control_chars() {
+        let schemes = vec!["http".to_string(), "https".to_string()];
 
-        assert!(!is_valid_url("javascript:alert('xss')", &schemes));
-        assert!(!is_valid_url("data:text/html,<script>", &schemes));
-        assert!(!is_valid_url("vbscript:alert", &schemes));
+        for url in [
+            "https://x.com\n/path",
+            "https://x.com\r/path",
+            "https://x.com/\tpath",
+            "https://x.com/\0",
+            "https://x.com/\x1b[0m",
+            "https://x.com/\x7f",
+        ] {
+            assert!(!is_valid_url(url, &schemes, false), "{url:?}");
+        }
+        assert!(is_valid_url("https://x.com/%0Apath", &schemes, false));
+        assert!(is_valid_url("https://x.com/%09%7F", &schemes, false));
     }
 
     #[test]
@@ -20,10 +20,13 @@
 //! });
 //! ```
 
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
 use crate::ast::TagType;
-use std::borrow::Cow;
-use std::collections::HashMap;
-use std::sync::Arc;
+use alloc::collections::{BTreeMap, BTreeSet};
 
 /// Definition of a BBCode tag (static, compile-time).
 ///
@@ -115,6 +118,14 @@ impl TagDef {
     pub fn is_inline(&self) -> bool {
         self.tag_type == TagType::Inline
     }
+
+    /// Returns true if this tag renders as a block-level element: a block
+    /// tag, or one whose `html_tag` is a block element (`[code]` as `<pre>`,
+    /// `[hr]`, list items).
+    #[inline]
+    pub fn is_block_level(&self) -> bool {
+        self.is_block() || self.html_tag.is_some_and(is_block_element)
+    }
 }
 
 /// Definition of a custom BBCode tag (runtime, owned strings).
@@ -232,6 +243,50 @@ impl CustomTagDef {
     pub fn is_inline(&self) -> bool {
         self.tag_type == TagType::Inline
     }
+
+    /// Returns true if this tag renders as a block-level element: a block
+    /// tag, or one whose `html_tag` is a block element such as `pre`.
+    #[inline]
+    pub fn is_block_level(&self) -> bool {
+        self.is_block() || self.html_tag.as_deref().is_some_and(is_block_element)
+    }
+}
+
+/// Returns true for HTML elements that are block-level (and `li`, which
+/// starts its own line too).
+fn is_block_element(html_tag: &str) -> bool {
+    matches!(
+        html_tag,
+        "address"
+            | "article"
+            | "aside"
+            | "blockquote"
+            | "details"
+            | "dd"
+            | "div"
+            | "dl"
+            | "dt"
+            | "figure"
+            | "footer"
+            | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "header"
+            | "hr"
+            | "li"
+            | "ol"
+            | "p"
+            | "pre"
+            | "section"
+            | "table"
+            | "td"
+            | "th"
+            | "tr"
+            | "ul"
+    )
 }
 
 /// A resolved tag definition that can be either static or custom.
@@ -243,6 +298,24 @@ pub enum ResolvedTag {
     Custom(Arc<CustomTagDef>),
 }
 
+impl From<&'static TagDef> for ResolvedTag {
+    fn from(tag: &'static TagDef) -> Self {
+        Self::Static(tag)
+    }
+}
+
+impl From<&&'static TagDef> for ResolvedTag {
+    fn from(tag: &&'static TagDef) -> Self {
+        Self::Static(tag)
+    }
+}
+
+impl From<CustomTagDef> for ResolvedTag {
+    fn from(tag: CustomTagDef) -> Self {
+        Self::Custom(Arc::new(tag))
+    }
+}
+
 impl ResolvedTag {
     /// Returns the tag name.
     pub fn name(&self) -> &str {
@@ -284,6 +357,14 @@ impl ResolvedTag {
         }
     }
 
+    /// Returns true if this tag renders as a block-level element.
+    pub fn is_block_level(&self) -> bool {
+        match self {
+            ResolvedTag::Static(t) => t.is_block_level(),
+            ResolvedTag::Custom(t) => t.is_block_level(),
+        }
+    }
+
     /// Returns true if content should be trimmed.
     pub fn trim_content(&self) -> bool {
         match self {
@@ -324,18 +405,25 @@ impl ResolvedTag {
         }
     }
 
+    /// Returns true if the tag forbids any ancestors.
+    pub fn has_forbidden_ancestors(&self) -> bool {
+        match self {
+            ResolvedTag::Static(t) => !t.forbidden_ancestors.is_empty(),
+            ResolvedTag::Custom(t) => !t.forbidden_ancestors.is_empty(),
+        }
+    }
+
     /// Checks if the given ancestor name is forbidden.
     pub fn is_ancestor_forbidden(&self, ancestor: &str) -> bool {
-        let ancestor_lower = ancestor.to_ascii_lowercase();
         match self {
             ResolvedTag::Static(t) => t
                 .forbidden_ancestors
                 .iter()
-                .any(|a| a.eq_ignore_ascii_case(&ancestor_lower)),
+                .any(|a| a.eq_ignore_ascii_case(ancestor)),
             ResolvedTag::Custom(t) => t
                 .forbidden_ancestors
                 .iter()
-                .any(|a| a.eq_ignore_ascii_case(&ancestor_lower)),
+                .any(|a| a.eq_ignore_ascii_case(ancestor)),
         }
     }
 
@@ -364,18 +452,18 @@ impl ResolvedTag {
 }
 
 /// Registry of all supported BBCode tags.
+#[derive(Clone)]
 pub struct TagRegistry {
-    static_tags: HashMap<&'static str, &'static TagDef>,
-    custom_tags: HashMap<String, Arc<CustomTagDef>>,
+    static_tags: BTreeMap<&'static str, &'static TagDef>,
+    custom_tags: BTreeMap<String, Arc<CustomTagDef>>,
+    /// Lowercase alias -> lowercase canonical name, see `register_alias`.
+    aliases: BTreeMap<String, String>,
 }
 
 impl TagRegistry {
     /// Creates a new registry with all standard tags registered.
     pub fn new() -> Self {
-        let mut registry = Self {
-            static_tags: HashMap::new(),
-            custom_tags: HashMap::new(),
-        };
+        let mut registry = Self::empty();
 
         // Register all standard tags
         for tag in STANDARD_TAGS.iter() {
@@ -388,22 +476,19 @@ impl TagRegistry {
     /// Creates an empty registry with no tags.
     pub fn empty() -> Self {
         Self {
-            static_tags: HashMap::new(),
-            custom_tags: HashMap::new(),
-        }
-    }
-
-    /// Registers a static tag definition.
-    pub fn register(&mut self, tag: &'static TagDef) {
-        self.static_tags.insert(tag.name, tag);
-        for alias in tag.aliases {
-            self.static_tags.insert(alias, tag);
+            static_tags: BTreeMap::new(),
+            custom_tags: BTreeMap::new(),
+            aliases: BTreeMap::new(),
         }
     }
 
-    /// Registers a custom tag definition.
+    /// Registers a tag definition, either a static [`TagDef`] or a
+    /// [`CustomTagDef`].
     ///
-    /// Custom tags take precedence over static tags with the same name.
+    /// Custom tags take precedence over static tags with the same name. To
+    /// render a custom tag, register a
+    /// [`CustomTagHandler`](crate::CustomTagHandler) for it with
+    /// [`Renderer::register_handler`](crate::Renderer::register_handler).
     ///
     /// # Example
     ///
@@ -411,7 +496,7 @@ impl TagRegistry {
     /// use bbcode::{TagRegistry, CustomTagDef, TagType};
     ///
     /// let mut registry = TagRegistry::new();
-    /// registry.register_custom(CustomTagDef {
+    /// registry.register(CustomTagDef {
     ///     name: "attach".into(),
     ///     aliases: vec!["attachment".into()],
     ///     tag_type: TagType::Inline,
@@ -419,22 +504,93 @@ impl TagRegistry {
     ///     trim_content: true,
     ///     ..Default::default()
     /// });
+    /// assert!(registry.is_known("attachment"));
     /// ```
+    pub fn register(&mut self, tag: impl Into<ResolvedTag>) {
+        match tag.into() {
+            ResolvedTag::Static(tag) => {
+                self.static_tags.insert(tag.name, tag);
+                for alias in tag.aliases {
+                    self.static_tags.insert(alias, tag);
+                }
+            }
+            ResolvedTag::Custom(tag) => {
+                let name = tag.name.to_ascii_lowercase();
+                self.custom_tags.insert(name, Arc::clone(&tag));
+                for alias in &tag.aliases {
+                    let alias_lower = alias.to_ascii_lowercase();
+                    self.custom_tags.insert(alias_lower, Arc::clone(&tag));
+                }
+            }
+        }
+    }
+
+    /// Registers a custom tag definition, like [`register`](Self::register).
     pub fn register_custom(&mut self, tag: CustomTagDef) {
-        let tag = Arc::new(tag);
-        let name = tag.name.to_ascii_lowercase();
-        self.custom_tags.insert(name, Arc::clone(&tag));
-        for alias in &tag.aliases {
-            let alias_lower = alias.to_ascii_lowercase();
-            self.custom_tags.insert(alias_lower, Arc::clone(&tag));
+        self.register(tag);
+    }
+
+    /// Makes `alias` an extra name for the tag registered as `canonical`
+    /// (both case-insensitive), e.g. a forum-specific `[b2]` that behaves
+    /// exactly like `[b]`.
+    ///
+    /// The alias resolves to the canonical tag, so it opens and closes like
+    /// any other tag name and renders with the canonical tag's logic. Give
+    /// the renderer a registry with the same alias. Does nothing if
+    /// `canonical` is not registered.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use bbcode::{Parser, RenderConfig, Renderer, TagRegistry};
+    ///
+    /// let mut registry = TagRegistry::new();
+    /// registry.register_alias("b2", "b");
+    ///
+    /// let doc = Parser::with_registry(registry.clone()).parse("[B2]x[/b2]");
+    /// let renderer = Renderer::with_config_and_registry(RenderConfig::default(), registry);
+    /// assert_eq!(renderer.render(&doc), "<strong>x</strong>");
+    /// ```
+    pub fn register_alias(&mut self, alias: &str, canonical: &str) {
+        let canonical = self.canonical_name(canonical);
+        if self.custom_tags.contains_key(&canonical)
+            || self.static_tags.contains_key(canonical.as_str())
+        {
+            self.aliases.insert(alias.to_ascii_lowercase(), canonical);
         }
     }
 
-    /// Looks up a tag by name (case-insensitive).
+    /// Returns the lowercase name `name` stands for, following aliases
+    /// added with [`register_alias`](Self::register_alias).
+    fn canonical_name(&self, name: &str) -> String {
+        let lower = name.to_ascii_lowercase();
+        match self.aliases.get(&lower) {
+            Some(canonical) => canonical.clone(),
+            None => lower,
+        }
+    }
+
+    /// Looks up the definition a tag name stands for.
+    ///
+    /// Names are case-insensitive, and built-in aliases (`bold` for `b`) and
+    /// those added with [`register_alias`](Self::register_alias) resolve to
+    /// the canonical definition. Custom tags take precedence over static
+    /// tags. The returned value is a cheap handle (a static reference or an
+    /// `Arc`), so embedders can inspect a tag's properties before rendering.
     ///
-    /// Custom tags take precedence over static tags.
+    /// # Example
+    ///
+    /// ```rust
+    /// use bbcode::{TagRegistry, TagType};
+    ///
+    /// let registry = TagRegistry::new();
+    /// let bold = registry.resolve("BOLD").unwrap();
+    /// assert_eq!(bold.name(), "b");
+    /// assert_eq!(bold.tag_type(), TagType::Inline);
+    /// assert!(registry.resolve("nope").is_none());
+    /// ```
     pub fn resolve(&self, name: &str) -> Option<ResolvedTag> {
-        let lower = name.to_ascii_lowercase();
+        let lower = self.canonical_name(name);
 
         // Check custom tags first
         if let Some(tag) = self.custom_tags.get(&lower) {
@@ -451,20 +607,53 @@ impl TagRegistry {
     ///
     /// This only returns built-in static tags, not custom tags.
     pub fn get(&self, name: &str) -> Option<&'static TagDef> {
-        let lower = name.to_ascii_lowercase();
+        let lower = self.canonical_name(name);
         self.static_tags.get(lower.as_str()).copied()
     }
 
+    /// Returns true if the tag renders as a block-level element, following
+    /// aliases (see [`TagDef::is_block_level`]). Unknown tags are inline.
+    pub fn is_block_level(&self, name: &str) -> bool {
+        self.resolve(name).is_some_and(|tag| tag.is_block_level())
+    }
+
+    /// Returns true if some registered tag must be a direct child of `name`
+    /// (like `[*]` in `[list]` or `[td]` in `[tr]`), so `name` holds only
+    /// those children directly.
+    pub fn restricts_children(&self, name: &str) -> bool {
+        let Some(tag) = self.resolve(name) else {
+            return false;
+        };
+        let name = tag.name();
+        self.iter().any(|def| def.required_parents.contains(&name))
+            || self
+                .iter_custom()
+                .any(|def| def.required_parents.iter().any(|parent| parent == name))
+    }
+
     /// Returns true if the tag is known (either static or custom).
     pub fn is_known(&self, name: &str) -> bool {
-        let lower = name.to_ascii_lowercase();
+        let lower = self.canonical_name(name);
         self.custom_tags.contains_key(&lower) || self.static_tags.contains_key(lower.as_str())
     }
 
+    /// Returns the canonical names of all registered tags (static and custom),
+    /// sorted alphabetically. Aliases are not included.
+    pub fn tag_names(&self) -> impl Iterator<Item = &str> + '_ {
+        let mut names: Vec<&str> = self
+            .iter()
+            .map(|tag| tag.name)
+            .chain(self.iter_custom().map(|tag| tag.name.as_ref()))
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names.into_iter()
+    }
+
     /// Returns an iterator over all registered static tags.
     pub fn iter(&self) -> impl Iterator<Item = &'static TagDef> + '_ {
         // Deduplicate by name
-        let mut seen = std::collections::HashSet::new();
+        let mut seen = BTreeSet::new();
         self.static_tags
             .values()
             .filter(move |tag| seen.insert(tag.name))
@@ -473,7 +662,7 @@ impl TagRegistry {
 
     /// Returns an iterator over all registered custom tags.
     pub fn iter_custom(&self) -> impl Iterator<Item = &Arc<CustomTagDef>> + '_ {
-        let mut seen = std::collections::HashSet::new();
+        let mut seen = BTreeSet::new();
         self.custom_tags
             .values()
             .filter(move |tag| seen.insert(tag.name.as_ref()))
@@ -682,7 +871,7 @@ pub static TAG_CODE: TagDef = TagDef {
     name: "code",
     aliases: &[],
     tag_type: TagType::Verbatim,
-    html_tag: None, // Custom rendering with pre/code
+    html_tag: Some("pre"), // Custom rendering with pre/code
     option_required: false,
     option_allowed: true,
     has_content: true,
@@ -716,7 +905,7 @@ pub static TAG_PHP: TagDef = TagDef {
     name: "php",
     aliases: &[],
     tag_type: TagType::Verbatim,
-    html_tag: None,
+    html_tag: Some("pre"),
     option_required: false,
     option_allowed: false,
     has_content: true,
@@ -733,7 +922,7 @@ pub static TAG_HTML: TagDef = TagDef {
     name: "html",
     aliases: &[],
     tag_type: TagType::Verbatim,
-    html_tag: None,
+    html_tag: Some("pre"),
     option_required: false,
     option_allowed: false,
     has_content: true,
@@ -779,6 +968,40 @@ pub static TAG_LIST: TagDef = TagDef {
     trim_content: true,
 };
 
+/// Ordered list: [ol]...[/ol]
+pub static TAG_OL: TagDef = TagDef {
+    name: "ol",
+    aliases: &[],
+    tag_type: TagType::Block,
+    html_tag: Some("ol"),
+    option_required: false,
+    option_allowed: true,
+    has_content: true,
+    forbidden_ancestors: &[],
+    required_parents: &[],
+    stop_smilies: false,
+    stop_auto_link: false,
+    convert_newlines: false,
+    trim_content: true,
+};
+
+/// Unordered list: [ul]...[/ul]
+pub static TAG_UL: TagDef = TagDef {
+    name: "ul",
+    aliases: &[],
+    tag_type: TagType::Block,
+    html_tag: Some("ul"),
+    option_required: false,
+    option_allowed: true,
+    has_content: true,
+    forbidden_ancestors: &[],
+    required_parents: &[],
+    stop_smilies: false,
+    stop_auto_link: false,
+    convert_newlines: false,
+    trim_content: true,
+};
+
 /// List item: [*]
 pub static TAG_LIST_ITEM: TagDef = TagDef {
     name: "*",
@@ -789,7 +1012,7 @@ pub static TAG_LIST_ITEM: TagDef = TagDef {
     option_allowed: false,
     has_content: true, // Content until next [*] or [/list]
     forbidden_ancestors: &[],
-    required_parents: &["list"],
+    required_parents: &["list", "ol", "ul"],
     stop_smilies: false,
     stop_auto_link: false,
     convert_newlines: true,
@@ -915,6 +1138,23 @@ pub static TAG_INDENT: TagDef = TagDef {
     trim_content: false,
 };
 
+/// Float: [float=left]...[/float] or [float=right]...[/float]
+pub static TAG_FLOAT: TagDef = TagDef {
+    name: "float",
+    aliases: &[],
+    tag_type: TagType::Block,
+    html_tag: None, // Custom rendering with float
+    option_required: true,
+    option_allowed: true,
+    has_content: true,
+    forbidden_ancestors: &[],
+    required_parents: &[],
+    stop_smilies: false,
+    stop_auto_link: false,
+    convert_newlines: true,
+    trim_content: false,
+};
+
 /// Heading: [heading=1]...[/heading]
 pub static TAG_HEADING: TagDef = TagDef {
     name: "heading",
@@ -983,6 +1223,23 @@ pub static TAG_USER: TagDef = TagDef {
     trim_content: false,
 };
 
+/// Attachment: [attach]123[/attach] or [attachment=123]...[/attachment]
+pub static TAG_ATTACH: TagDef = TagDef {
+    name: "attach",
+    aliases: &["attachment"],
+    tag_type: TagType::Inline,
+    html_tag: None, // Rendered through the attachment resolver
+    option_required: false,
+    option_allowed: true,
+    has_content: true,
+    forbidden_ancestors: &[],
+    required_parents: &[],
+    stop_smilies: true,
+    stop_auto_link: true,
+    convert_newlines: false,
+    trim_content: true,
+};
+
 /// Subscript: [sub]...[/sub]
 pub static TAG_SUB: TagDef = TagDef {
     name: "sub",
@@ -1089,6 +1346,40 @@ pub static TAG_TD: TagDef = TagDef {
     trim_content: false,
 };
 
+/// Abbreviation: [abbr=title]...[/abbr]
+pub static TAG_ABBR: TagDef = TagDef {
+    name: "abbr",
+    aliases: &[],
+    tag_type: TagType::Inline,
+    html_tag: Some("abbr"),
+    option_required: false,
+    option_allowed: true,
+    has_content: true,
+    forbidden_ancestors: &[],
+    required_parents: &[],
+    stop_smilies: false,
+    stop_auto_link: false,
+    convert_newlines: true,
+    trim_content: false,
+};
+
+/// Highlight: [highlight]...[/highlight]
+pub static TAG_HIGHLIGHT: TagDef = TagDef {
+    name: "highlight",
+    aliases: &["mark"],
+    tag_type: TagType::Inline,
+    html_tag: Some("mark"),
+    option_required: false,
+    option_allowed: false,
+    has_content: true,
+    forbidden_ancestors: &[],
+    required_parents: &[],
+    stop_smilies: false,
+    stop_auto_link: false,
+    convert_newlines: true,
+    trim_content: false,
+};
+
 // ============================================================================
 // Collection of all standard tags
 // ============================================================================
@@ -1108,6 +1399,8 @@ pub static STANDARD_TAGS: &[&TagDef] = &[
     &TAG_SIZE,
     &TAG_SUB,
     &TAG_SUP,
+    &TAG_ABBR,
+    &TAG_HIGHLIGHT,
     // Links and images
     &TAG_URL,
     &TAG_EMAIL,
@@ -1120,6 +1413,8 @@ pub static STANDARD_TAGS: &[&TagDef] = &[
     &TAG_HTML,
     &TAG_PLAIN,
     &TAG_LIST,
+    &TAG_OL,
+    &TAG_UL,
     &TAG_LIST_ITEM,
     // Alignment
     &TAG_LEFT,
@@ -1127,6 +1422,7 @@ pub static STANDARD_TAGS: &[&TagDef] = &[
     &TAG_RIGHT,
     &TAG_JUSTIFY,
     &TAG_INDENT,
+    &TAG_FLOAT,
     &TAG_HEADING,
     // Special
     &TAG_HR,
@@ -1134,6 +1430,7 @@ pub static STANDARD_TAGS: &[&TagDef] = &[
     &TAG_SPOILER,
     &TAG_ISPOILER,
     &TAG_USER,
+    &TAG_ATTACH,
     // Tables
     &TAG_TABLE,
     &TAG_TR,
@@ -1145,6 +1442,34 @@ pub static STANDARD_TAGS: &[&TagDef] = &[
 mod tests {
     use super::*;
 
+    #[test]
+    fn registry_resolve() {
+        let registry = TagRegistry::new();
+        let bold = registry.resolve("B").unwrap();
+        assert!(matches!(bold, ResolvedTag::Static(def) if core::ptr::eq(def, &TAG_BOLD)));
+        assert_eq!(bold.name(), "b");
+        assert_eq!(bold.tag_type(), TagType::Inline);
+        assert!(registry.resolve("nope").is_none());
+
+        let code = registry.resolve("Code").unwrap();
+        assert!(code.is_verbatim());
+        assert!(code.option_allowed());
+    }
+
+    #[test]
+    fn registry_alias() {
+        let mut registry = TagRegistry::new();
+        registry.register_alias("Strong", "BOLD");
+        registry.register_alias("nothing", "missing");
+
+        assert!(registry.is_known("STRONG"));
+        assert_eq!(registry.resolve("strong").unwrap().name(), "b");
+        assert_eq!(registry.get("strong").unwrap().name, "b");
+        assert!(!registry.is_known("nothing"));
+        // Aliases are not canonical names
+        assert!(!registry.tag_names().any(|name| name == "strong"));
+    }
+
     #[test]
     fn registry_creation() {
         let registry = TagRegistry::new();
@@ -1262,6 +1587,22 @@ mod tests {
         assert!(names.contains(&"code"));
     }
 
+    #[test]
+    fn registry_tag_names() {
+        let mut registry = TagRegistry::new();
+        registry.register_custom(CustomTagDef {
+            aliases: vec!["attachment".into()],
+            ..CustomTagDef::new("attach")
+        });
+
+        let names: Vec<_> = registry.tag_names().collect();
+        assert!(names.contains(&"b"));
+        assert!(names.contains(&"attach"));
+        assert!(!names.contains(&"bold"));
+        assert!(!names.contains(&"attachment"));
+        assert!(names.windows(2).all(|w| w[0] < w[1]));
+    }
+
     #[test]
     fn tag_aliases() {
         let registry = TagRegistry::new();
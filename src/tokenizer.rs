@@ -3,6 +3,9 @@
 //! This module converts raw BBCode input into a stream of tokens using
 //! zero-copy parsing. All string data references the original input.
 
+use alloc::vec::Vec;
+use core::ops::Range;
+
 use winnow::combinator::{alt, delimited};
 use winnow::error::{ContextError, ErrMode};
 use winnow::token::take_till;
@@ -88,11 +91,20 @@ impl<'a> Token<'a> {
 
 type PResult<O> = Result<O, ErrMode<ContextError>>;
 
+/// A token together with its byte range in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpannedToken<'a> {
+    /// The token.
+    pub token: Token<'a>,
+    /// Byte range of the token within the tokenized input.
+    pub span: Range<usize>,
+}
+
 /// Tokenizes BBCode input into a vector of tokens.
 ///
 /// This is a zero-copy operation - all string data in tokens reference
 /// the original input string. No heap allocations are performed for
-/// string content.
+/// string content. Use [`Tokenizer`] to scan tokens lazily instead.
 ///
 /// # Example
 /// ```
@@ -103,96 +115,129 @@ type PResult<O> = Result<O, ErrMode<ContextError>>;
 /// ```
 #[inline]
 pub fn tokenize(input: &str) -> Vec<Token<'_>> {
-    if input.is_empty() {
-        return Vec::new();
-    }
-
     // Estimate token count: roughly 1 token per 10 characters for typical BBCode
-    let estimated_tokens = (input.len() / 10).max(4);
-    let mut tokens = Vec::with_capacity(estimated_tokens);
-    let mut remaining = input;
-    let original_input = input;
-
-    while !remaining.is_empty() {
-        let start_offset = original_input.len() - remaining.len();
+    let mut tokens = Vec::with_capacity((input.len() / 10).max(4));
+    tokens.extend(Tokenizer::new(input));
+    tokens
+}
 
-        match parse_token(&mut remaining, original_input, start_offset) {
-            Ok(token) => {
-                // Skip null/empty tokens
-                if !matches!(&token, Token::Text(s) if s.is_empty()) {
-                    tokens.push(token);
-                }
-            }
-            Err(_) => {
-                // On error, consume one character as text and continue
-                if let Some(c) = remaining.chars().next() {
-                    let char_len = c.len_utf8();
-                    let char_str = &original_input[start_offset..start_offset + char_len];
-                    remaining = &remaining[char_len..];
-
-                    // Merge with previous text token if possible (inline for performance)
-                    if let Some(Token::Text(prev)) = tokens.last_mut() {
-                        let prev_start = prev.as_ptr() as usize - original_input.as_ptr() as usize;
-                        let prev_end = prev_start + prev.len();
-                        if prev_end == start_offset {
-                            // Adjacent, so extend
-                            *prev = &original_input[prev_start..start_offset + char_len];
-                            continue;
-                        }
-                    }
-                    tokens.push(Token::Text(char_str));
-                }
-            }
-        }
+/// Tokenizes BBCode input like [`tokenize`], keeping each token's byte
+/// range in the input.
+///
+/// # Example
+/// ```
+/// use bbcode::tokenize_spanned;
+///
+/// let tokens = tokenize_spanned("ab[b]c[/b]");
+/// assert_eq!(tokens[1].span, 2..5);
+/// ```
+pub fn tokenize_spanned(input: &str) -> Vec<SpannedToken<'_>> {
+    let mut tokens = Vec::with_capacity((input.len() / 10).max(4));
+    let mut tokenizer = Tokenizer::new(input);
+    while let Some(token) = tokenizer.next_spanned() {
+        tokens.push(token);
     }
+    tokens
+}
 
-    // Merge adjacent text tokens (in-place, no allocation)
-    merge_text_tokens(tokens, original_input)
+/// A lazy tokenizer yielding the same tokens as [`tokenize`].
+///
+/// Tokens are produced on demand, so a scan can stop early without
+/// tokenizing (or allocating for) the rest of the input. Adjacent text is
+/// merged into a single [`Token::Text`].
+///
+/// # Example
+/// ```
+/// use bbcode::{Token, Tokenizer};
+///
+/// let has_image = Tokenizer::new("Look: [img]https://x.com/a.png[/img] ...")
+///     .any(|token| matches!(token, Token::OpenTag { name, .. } if name.eq_ignore_ascii_case("img")));
+/// assert!(has_image);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Tokenizer<'a> {
+    input: &'a str,
+    remaining: &'a str,
+    /// A token read ahead while merging text.
+    pending: Option<SpannedToken<'a>>,
 }
 
-fn merge_text_tokens<'a>(mut tokens: Vec<Token<'a>>, input: &'a str) -> Vec<Token<'a>> {
-    if tokens.len() <= 1 {
-        return tokens;
+impl<'a> Tokenizer<'a> {
+    /// Creates a tokenizer over `input`.
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            remaining: input,
+            pending: None,
+        }
     }
 
-    let input_start = input.as_ptr() as usize;
-    let mut write_idx = 0;
-    let mut read_idx = 1;
+    /// Returns the next token together with its byte range in the input.
+    ///
+    /// # Example
+    /// ```
+    /// use bbcode::{Token, Tokenizer};
+    ///
+    /// let mut tokenizer = Tokenizer::new("ab[b]");
+    /// tokenizer.next_spanned();
+    /// let tag = tokenizer.next_spanned().unwrap();
+    /// assert!(matches!(tag.token, Token::OpenTag { name: "b", .. }));
+    /// assert_eq!(tag.span, 2..5);
+    /// ```
+    pub fn next_spanned(&mut self) -> Option<SpannedToken<'a>> {
+        let first = self.pending.take().or_else(|| self.next_unmerged())?;
+        if !first.token.is_text() {
+            return Some(first);
+        }
 
-    while read_idx < tokens.len() {
-        let should_merge = match (&tokens[write_idx], &tokens[read_idx]) {
-            (Token::Text(existing), Token::Text(new_text)) => {
-                // Check if adjacent in the original input
-                let existing_start = existing.as_ptr() as usize - input_start;
-                let existing_end = existing_start + existing.len();
-                let new_start = new_text.as_ptr() as usize - input_start;
-                existing_end == new_start
-            }
-            _ => false,
-        };
-
-        if should_merge {
-            // Merge: extend the existing text token
-            if let (Token::Text(existing), Token::Text(new_text)) =
-                (&tokens[write_idx], &tokens[read_idx])
-            {
-                let existing_start = existing.as_ptr() as usize - input_start;
-                let new_start = new_text.as_ptr() as usize - input_start;
-                let merged = &input[existing_start..new_start + new_text.len()];
-                tokens[write_idx] = Token::Text(merged);
-            }
-        } else {
-            // Move to next write position
-            write_idx += 1;
-            if write_idx != read_idx {
-                tokens.swap(write_idx, read_idx);
+        // Tokens are contiguous, so a run of text tokens covers one slice
+        let mut span = first.span;
+        while let Some(next) = self.next_unmerged() {
+            if next.token.is_text() {
+                span.end = next.span.end;
+            } else {
+                self.pending = Some(next);
+                break;
             }
         }
-        read_idx += 1;
+        Some(SpannedToken {
+            token: Token::Text(&self.input[span.clone()]),
+            span,
+        })
     }
 
-    tokens.truncate(write_idx + 1);
-    tokens
+    /// Reads the next token without merging adjacent text.
+    fn next_unmerged(&mut self) -> Option<SpannedToken<'a>> {
+        while !self.remaining.is_empty() {
+            let start_offset = self.input.len() - self.remaining.len();
+
+            let token = match parse_token(&mut self.remaining, self.input, start_offset) {
+                // Skip null/empty tokens
+                Ok(Token::Text("")) => continue,
+                Ok(token) => token,
+                Err(_) => {
+                    // On error, consume one character as text and continue
+                    let char_len = self.remaining.chars().next()?.len_utf8();
+                    self.remaining = &self.remaining[char_len..];
+                    Token::Text(&self.input[start_offset..start_offset + char_len])
+                }
+            };
+            let end = self.input.len() - self.remaining.len();
+            return Some(SpannedToken {
+                token,
+                span: start_offset..end,
+            });
+        }
+        None
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        self.next_spanned().map(|spanned| spanned.token)
+    }
 }
 
 /// Parses a single token from the input.
@@ -352,12 +397,10 @@ fn parse_url<'a>(input: &mut &'a str) -> PResult<Token<'a>> {
         .find(|c: char| c.is_whitespace() || matches!(c, '[' | ']' | '<' | '>'))
         .unwrap_or(rest.len());
 
-    let total_len = protocol_len + url_end;
-    let mut url = &input[..total_len];
-    *input = &input[total_len..];
-
-    // Trim trailing punctuation that's likely not part of the URL
-    url = url.trim_end_matches(|c: char| matches!(c, '.' | ',' | ')' | '!' | '?' | ':' | ';'));
+    // Trim trailing punctuation that's likely not part of the URL; it is
+    // left in the input to be tokenized as text
+    let url = input[..protocol_len + url_end].trim_end_matches(['.', ',', ')', '!', '?', ':', ';']);
+    *input = &input[url.len()..];
 
     Ok(Token::Url(url))
 }
@@ -380,9 +423,7 @@ fn parse_linebreak<'a>(input: &mut &'a str) -> PResult<Token<'a>> {
 
 /// Parses plain text until a special character.
 fn parse_text<'a>(input: &mut &'a str) -> PResult<Token<'a>> {
-    let end = input
-        .find(|c: char| c == '[' || c == '\n' || c == '\r' || c == 'h')
-        .unwrap_or(input.len());
+    let end = input.find(['[', '\n', '\r', 'h']).unwrap_or(input.len());
 
     if end == 0 {
         return Err(ErrMode::Backtrack(ContextError::new()));
@@ -420,7 +461,7 @@ fn find_case_insensitive(haystack: &str, needle: &str) -> Option<usize> {
         for j in 1..needle_bytes.len() {
             let h = haystack_bytes[i + j];
             let n = needle_bytes[j];
-            if h.to_ascii_lowercase() != n.to_ascii_lowercase() {
+            if !h.eq_ignore_ascii_case(&n) {
                 continue 'outer;
             }
         }
@@ -451,7 +492,7 @@ pub fn tokenize_until_close<'a>(input: &'a str, tag_name: &str) -> (&'a str, &'a
     pattern_buf[2 + tag_name.len()] = b']';
 
     // SAFETY: We're only copying ASCII bytes from tag_name which should be ASCII
-    let pattern = unsafe { std::str::from_utf8_unchecked(&pattern_buf[..pattern_len]) };
+    let pattern = unsafe { core::str::from_utf8_unchecked(&pattern_buf[..pattern_len]) };
 
     // Search for the close tag (case-insensitive) without allocating
     if let Some(pos) = find_case_insensitive(input, pattern) {
@@ -746,4 +787,64 @@ mod tests {
         assert!(tokens.iter().any(|t| matches!(t, Token::OpenTag { .. })));
         assert!(tokens.iter().any(|t| matches!(t, Token::Url(_))));
     }
+
+    #[test]
+    fn tokenize_spans() {
+        let input = "ab[b]c[/b]\r\nx [y";
+        let tokens = tokenize_spanned(input);
+        let spans: Vec<_> = tokens.iter().map(|t| t.span.clone()).collect();
+        assert_eq!(spans, vec![0..2, 2..5, 5..6, 6..10, 10..12, 12..16]);
+        for token in &tokens {
+            assert_eq!(&input[token.span.clone()], token.token.as_raw());
+        }
+        let plain: Vec<_> = tokens.into_iter().map(|t| t.token).collect();
+        assert_eq!(plain, tokenize(input));
+    }
+
+    #[test]
+    fn tokenizer_iterator_matches_tokenize() {
+        let input = "Hi [b]there[/b]\r\n[url=https://x.com]x[/url] see https://y.com] [ok";
+        let lazy: Vec<_> = Tokenizer::new(input).collect();
+        assert_eq!(lazy, tokenize(input));
+        assert_eq!(
+            lazy,
+            vec![
+                Token::Text("Hi "),
+                Token::OpenTag {
+                    name: "b",
+                    arg: None,
+                    raw: "[b]"
+                },
+                Token::Text("there"),
+                Token::CloseTag {
+                    name: "b",
+                    raw: "[/b]"
+                },
+                Token::LineBreak("\r\n"),
+                Token::OpenTag {
+                    name: "url",
+                    arg: Some("https://x.com"),
+                    raw: "[url=https://x.com]"
+                },
+                Token::Text("x"),
+                Token::CloseTag {
+                    name: "url",
+                    raw: "[/url]"
+                },
+                Token::Text(" see "),
+                Token::Url("https://y.com"),
+                Token::Text("] [ok"),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenizer_stops_early() {
+        let mut tokens = Tokenizer::new("[img]a.png[/img] rest");
+        assert!(matches!(
+            tokens.next(),
+            Some(Token::OpenTag { name: "img", .. })
+        ));
+        assert_eq!(tokens.next(), Some(Token::Text("a.png")));
+    }
 }
@@ -188,7 +188,7 @@ mod edge_cases {
 
     #[test]
     fn quotes_in_content() {
-        assert_eq!(parse("He said \"Hello\""), "He said &quot;Hello&quot;");
+        assert_eq!(parse("He said \"Hello\""), "He said \"Hello\"");
     }
 }
 
@@ -223,6 +223,27 @@ mod url_handling {
         assert!(result.contains("href=\"https://example.com?q=test\""));
     }
 
+    // URLs are passed through verbatim (only HTML-escaped) so signed and CDN
+    // URLs keep working.
+
+    #[test]
+    fn url_dot_segments_not_normalized() {
+        let result = parse("[url=https://x.com/a/../b]Link[/url]");
+        assert!(result.contains("href=\"https://x.com/a/../b\""));
+    }
+
+    #[test]
+    fn url_trailing_slash_preserved() {
+        let result = parse("[url=https://x.com/path/]Link[/url]");
+        assert!(result.contains("href=\"https://x.com/path/\""));
+    }
+
+    #[test]
+    fn url_case_and_encoding_preserved() {
+        let result = parse("[url]https://X.com/A/./b/?sig=AbC%2F&x=1[/url]");
+        assert!(result.contains("href=\"https://X.com/A/./b/?sig=AbC%2F&amp;x=1\""));
+    }
+
     #[test]
     fn url_with_fragment() {
         let result = parse("[url=https://example.com#section]Link[/url]");
@@ -459,7 +480,7 @@ mod security {
 
             // If last < is after last > (or no >), we might be inside a tag
             if let Some(open_pos) = last_open {
-                if last_close.map_or(true, |close_pos| open_pos > close_pos) {
+                if last_close.is_none_or(|close_pos| open_pos > close_pos) {
                     // We're after a < without a closing >
                     // Check if there's a > coming after the handler
                     if after.contains('>') {
@@ -488,7 +509,7 @@ mod security {
 
             // If last < is after last > (or no >), we're inside a tag
             if let Some(open_pos) = last_open {
-                if last_close.map_or(true, |close_pos| open_pos > close_pos) {
+                if last_close.is_none_or(|close_pos| open_pos > close_pos) {
                     // We're inside an HTML tag - check for dangerous CSS
                     let style_content = after;
                     if style_content.contains("expression(")
@@ -728,7 +749,7 @@ mod security {
         fn multiple_equals_confusion() {
             let result = parse("[url=foo=bar onclick=alert(1)]Click Me[/url]");
             assert!(
-                !result.contains(" onclick="),
+                !has_dangerous_event_handler(&result, "onclick"),
                 "onclick injection via multiple = blocked"
             );
         }
@@ -1065,6 +1086,49 @@ mod security {
             assert!(!result.is_empty(), "Should handle deep formatting nesting");
         }
 
+        #[test]
+        fn pathologically_deep_nesting() {
+            // Unclosed, with the default depth limit: excess tags become text
+            let input = "[b]".repeat(100_000);
+            let result = parse(&input);
+            assert!(result.starts_with("<strong>"), "Should render without overflow");
+
+            // Fully nested, with the depth limit raised past the input
+            let depth = 50_000;
+            let input = "[b]".repeat(depth) + "x" + &"[/b]".repeat(depth);
+            let parser = bbcode::Parser::with_config(bbcode::ParserConfig {
+                max_depth: depth,
+                ..Default::default()
+            });
+            let doc = parser.parse(&input);
+            let renderer = bbcode::Renderer::new();
+            let result = renderer.render(&doc);
+            assert_eq!(result.matches("<strong>").count(), depth);
+            assert_eq!(renderer.render_text_only(&doc), "x");
+            renderer.collect_from_document(&doc);
+
+            let copy = doc.clone();
+            assert_eq!(copy, doc);
+            let owned = copy.into_owned();
+            assert!(owned == doc);
+            assert_ne!(parser.parse(&input.replacen('x', "y", 1)), doc);
+        }
+
+        #[test]
+        fn pathologically_deep_document_walks() {
+            let depth = 50_000;
+            let input = "[b]".repeat(depth)
+                + "[img]https://x.com/a.png[/img]\n[quote]x[/quote]"
+                + &"[/b]".repeat(depth);
+            let parser = bbcode::Parser::with_config(bbcode::ParserConfig {
+                max_depth: depth + 1,
+                ..Default::default()
+            });
+            let doc = parser.parse(&input);
+            assert_eq!(doc.first_image(), Some("https://x.com/a.png"));
+            assert_eq!(doc.summary(10), "x");
+        }
+
         // --- Unclosed Tags causing "Tag Soup" ---
 
         #[test]
@@ -2625,13 +2689,10 @@ mod security {
         fn xf_newline_in_url_blocked() {
             // XenForo blocks URLs containing newlines (line 1680-1683)
             let result = parse("[url=https://example.com\n/path]Click[/url]");
-            // Either reject the URL or sanitize it
-            if result.contains("href=") {
-                assert!(
-                    !result.contains("href=\"https://example.com\n"),
-                    "Newline in URL should be blocked or sanitized"
-                );
-            }
+            assert!(!result.contains("href="), "Newline in URL should be blocked");
+
+            let result = parse("[url]https://example.com/\tpath[/url]");
+            assert!(!result.contains("href="), "Tab in URL should be blocked");
         }
 
         // --- CSS System Colors Blacklist (from XF Html.php:1309-1360) ---
@@ -2718,15 +2779,10 @@ mod security {
         fn xf_font_inherit_blocked() {
             // XenForo blocks "inherit" as font-family to prevent style inheritance attacks
             let result = parse("[font=inherit]Text[/font]");
-            // Should either block entirely or not use inherit
-            if result.contains("font-family:") {
-                assert!(
-                    !result.contains("font-family: inherit")
-                        && !result.contains("font-family: 'inherit'")
-                        && !result.contains("font-family:inherit"),
-                    "inherit keyword should be blocked in font-family"
-                );
-            }
+            assert!(
+                !result.contains("font-family"),
+                "inherit keyword should be blocked in font-family"
+            );
         }
 
         #[test]
@@ -480,7 +480,7 @@ proptest! {
         for _ in 0..depth {
             input.push_str("[b]");
         }
-        input.push_str("X");
+        input.push('X');
         for _ in 0..depth {
             input.push_str("[/b]");
         }
@@ -661,10 +661,8 @@ fn is_in_html_tag(html: &str, pattern: &str) -> bool {
         let last_close = before.rfind('>');
 
         if let Some(open_pos) = last_open {
-            if last_close.map_or(true, |close_pos| open_pos > close_pos) {
-                if after.contains('>') {
-                    return true;
-                }
+            if last_close.is_none_or(|close_pos| open_pos > close_pos) && after.contains('>') {
+                return true;
             }
         }
     }
@@ -33,7 +33,7 @@ fn has_dangerous_event_handler(output: &str, handler: &str) -> bool {
         let last_close = before.rfind('>');
 
         if let Some(open_pos) = last_open {
-            if last_close.map_or(true, |close_pos| open_pos > close_pos) {
+            if last_close.is_none_or(|close_pos| open_pos > close_pos) {
                 // We're after a < without a closing > - potentially inside a tag
                 // Now check if we're inside an attribute value (between quotes)
                 let tag_content = &before[open_pos..];
@@ -203,7 +203,7 @@ mod nested_tag_attacks {
         // Attempt to use img onerror inside url tag
         let result = parse(r#"[url=http://x.com][img]x" onerror="alert(1)[/img][/url]"#);
         assert!(
-            !result.contains(" onerror=") || result.contains("&quot;"),
+            !has_dangerous_event_handler(&result, "onerror"),
             "Nested img onerror in url"
         );
     }
@@ -230,7 +230,7 @@ mod nested_tag_attacks {
     fn deeply_nested_mixed_tags() {
         let result = parse("[b][url][i][email]a@a.a\" onclick=\"alert(1)[/email][/i][/url][/b]");
         assert!(
-            !result.contains(" onclick=") || result.contains("&quot;"),
+            !has_dangerous_event_handler(&result, "onclick"),
             "Deeply nested mixed tags with injection"
         );
     }
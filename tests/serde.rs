@@ -0,0 +1,32 @@
+//! Serialization round-trip tests for the AST (requires the `serde` feature).
+
+#![cfg(feature = "serde")]
+
+use bbcode::{Document, Parser, Renderer};
+
+fn round_trip(input: &str) {
+    let parser = Parser::new();
+    let renderer = Renderer::new();
+
+    let doc = parser.parse(input);
+    let json = serde_json::to_string(&doc).unwrap();
+    let restored: Document<'static> = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(restored, doc);
+    assert_eq!(renderer.render(&restored), renderer.render(&doc));
+}
+
+#[test]
+fn round_trip_quote() {
+    round_trip("[quote=\"Bob\"][b]hi[/b][/quote]");
+}
+
+#[test]
+fn round_trip_keyed_options_and_verbatim() {
+    round_trip("[img width=100 height=50]https://example.com/a.png[/img]\n[code=rust]let x = [b];[/code]");
+}
+
+#[test]
+fn round_trip_broken_and_unclosed() {
+    round_trip("[b][i]unclosed [/b] [list][*]one\nhttps://example.com");
+}